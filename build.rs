@@ -0,0 +1,14 @@
+//! Generates the gRPC service of `serve --grpc` from proto/wol.proto, with the grpc feature. The
+//! proto is parsed by protox, so that building needs no protoc.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/wol.proto");
+        let files = protox::compile(["wol.proto"], ["proto"])?;
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(files)?;
+    }
+    Ok(())
+}
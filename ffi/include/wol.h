@@ -0,0 +1,53 @@
+/*
+ * libwol: building and sending Wake-on-LAN magic packets, the C ABI of wol-cli.
+ *
+ * Every function returns WOL_OK, or a negative error code that wol_strerror describes.
+ */
+#ifndef WOL_H
+#define WOL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define WOL_OK 0
+/* A required pointer is null. */
+#define WOL_ERR_NULL -1
+/* The MAC address is invalid. */
+#define WOL_ERR_INVALID_MAC -2
+/* The SecureOn password is not six bytes in the notation of a MAC address. */
+#define WOL_ERR_INVALID_PASSWORD -3
+/* The destination is not an IP address. */
+#define WOL_ERR_INVALID_ADDRESS -4
+/* The buffer is too small for the packet. */
+#define WOL_ERR_BUFFER -5
+/* Sending the packet failed. */
+#define WOL_ERR_SEND -6
+
+/* The size of the largest packet, one with a password. */
+#define WOL_PACKET_MAX 108
+
+/*
+ * Writes the magic packet waking mac, e.g. "aa:bb:cc:dd:ee:ff", to buf, which holds *len
+ * bytes, and sets *len to the size of the packet: 102 bytes, or 108 with a password, which may
+ * be NULL. If buf is too small, returns WOL_ERR_BUFFER with *len set to the size needed.
+ */
+int wol_create_packet(const char *mac, const char *password, uint8_t *buf, size_t *len);
+
+/*
+ * Sends the magic packet waking mac to addr and port, usually 9, with the SecureOn password if
+ * it is not NULL. A NULL addr is the limited broadcast address, 255.255.255.255.
+ */
+int wol_send(const char *mac, const char *password, const char *addr, uint16_t port);
+
+/* Describes an error code, as a static string. */
+const char *wol_strerror(int code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,229 @@
+//! A C ABI over wol-cli, built as a shared and a static library (libwol) for applications not
+//! written in Rust. The declarations are in include/wol.h.
+//!
+//! Every function returns `WOL_OK`, or a negative error code that `wol_strerror` describes.
+use std::ffi::{c_char, c_int, CStr};
+use std::net::{IpAddr, Ipv4Addr};
+
+use wol_cli::{MacAddr, MagicPacket, SendOptions};
+
+/// Success.
+pub const WOL_OK: c_int = 0;
+/// A required pointer is null.
+pub const WOL_ERR_NULL: c_int = -1;
+/// The MAC address is invalid.
+pub const WOL_ERR_INVALID_MAC: c_int = -2;
+/// The SecureOn password is not six bytes in the notation of a MAC address.
+pub const WOL_ERR_INVALID_PASSWORD: c_int = -3;
+/// The destination is not an IP address.
+pub const WOL_ERR_INVALID_ADDRESS: c_int = -4;
+/// The buffer is too small for the packet.
+pub const WOL_ERR_BUFFER: c_int = -5;
+/// Sending the packet failed.
+pub const WOL_ERR_SEND: c_int = -6;
+
+/// The size of the largest packet, one with a password.
+pub const WOL_PACKET_MAX: usize = 108;
+
+/// Reads a string argument, `None` if it is null and an error if it is not UTF-8.
+///
+/// # Safety
+///
+/// `s` is null or a valid C string.
+unsafe fn string<'a>(s: *const c_char, invalid: c_int) -> Result<Option<&'a str>, c_int> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s).to_str().map(Some).map_err(|_| invalid)
+}
+
+/// Builds the packet waking `mac`, with the SecureOn `password` if it is not null.
+///
+/// # Safety
+///
+/// `mac` and `password` are null or valid C strings.
+unsafe fn packet(mac: *const c_char, password: *const c_char) -> Result<MagicPacket, c_int> {
+    let mac: MacAddr = string(mac, WOL_ERR_INVALID_MAC)?
+        .ok_or(WOL_ERR_NULL)?
+        .parse()
+        .map_err(|_| WOL_ERR_INVALID_MAC)?;
+    let packet = MagicPacket::new(mac);
+    match string(password, WOL_ERR_INVALID_PASSWORD)? {
+        Some(password) => packet
+            .with_password(password)
+            .map_err(|_| WOL_ERR_INVALID_PASSWORD),
+        None => Ok(packet),
+    }
+}
+
+/// Writes the magic packet waking `mac` to `buf`, which holds `*len` bytes, and sets `*len` to
+/// the size of the packet: 102 bytes, or 108 with a `password`, which may be null.
+///
+/// # Safety
+///
+/// `mac` is a valid C string, `password` is null or one, and `buf` points to `*len` writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn wol_create_packet(
+    mac: *const c_char,
+    password: *const c_char,
+    buf: *mut u8,
+    len: *mut usize,
+) -> c_int {
+    if buf.is_null() || len.is_null() {
+        return WOL_ERR_NULL;
+    }
+    let packet = match packet(mac, password) {
+        Ok(packet) => packet,
+        Err(code) => return code,
+    };
+    let bytes = packet.as_bytes();
+    if *len < bytes.len() {
+        *len = bytes.len();
+        return WOL_ERR_BUFFER;
+    }
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+    *len = bytes.len();
+    WOL_OK
+}
+
+/// Sends the magic packet waking `mac` to `addr` and `port`, with the SecureOn `password` if it
+/// is not null. A null `addr` is the limited broadcast address, 255.255.255.255.
+///
+/// # Safety
+///
+/// `mac` is a valid C string, and `password` and `addr` are null or valid C strings.
+#[no_mangle]
+pub unsafe extern "C" fn wol_send(
+    mac: *const c_char,
+    password: *const c_char,
+    addr: *const c_char,
+    port: u16,
+) -> c_int {
+    let packet = match packet(mac, password) {
+        Ok(packet) => packet,
+        Err(code) => return code,
+    };
+    let addr = match string(addr, WOL_ERR_INVALID_ADDRESS) {
+        Ok(Some(addr)) => match addr.parse() {
+            Ok(addr) => addr,
+            Err(_) => return WOL_ERR_INVALID_ADDRESS,
+        },
+        Ok(None) => IpAddr::V4(Ipv4Addr::BROADCAST),
+        Err(code) => return code,
+    };
+    match packet.send_to(addr, port, &SendOptions::default()) {
+        Ok(()) => WOL_OK,
+        Err(_) => WOL_ERR_SEND,
+    }
+}
+
+/// Describes an error code, as a static C string.
+#[no_mangle]
+pub extern "C" fn wol_strerror(code: c_int) -> *const c_char {
+    let message: &'static CStr = match code {
+        WOL_OK => c"success",
+        WOL_ERR_NULL => c"null pointer",
+        WOL_ERR_INVALID_MAC => c"invalid MAC address",
+        WOL_ERR_INVALID_PASSWORD => c"invalid password",
+        WOL_ERR_INVALID_ADDRESS => c"invalid address",
+        WOL_ERR_BUFFER => c"buffer too small",
+        WOL_ERR_SEND => c"unable to send packet",
+        _ => c"unknown error",
+    };
+    message.as_ptr()
+}
+
+#[test]
+fn test_create_packet() {
+    let mut buf = [0; WOL_PACKET_MAX];
+    let mut len = buf.len();
+    let code = unsafe {
+        wol_create_packet(
+            c"aa:bb:cc:dd:ee:ff".as_ptr(),
+            std::ptr::null(),
+            buf.as_mut_ptr(),
+            &mut len,
+        )
+    };
+    assert_eq!(code, WOL_OK);
+    assert_eq!(len, 102);
+    assert_eq!(buf[..6], [0xff; 6]);
+    assert_eq!(buf[96..102], [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+
+    let mut len = buf.len();
+    let code = unsafe {
+        wol_create_packet(
+            c"aa:bb:cc:dd:ee:ff".as_ptr(),
+            c"01-02-03-04-05-06".as_ptr(),
+            buf.as_mut_ptr(),
+            &mut len,
+        )
+    };
+    assert_eq!(code, WOL_OK);
+    assert_eq!(len, 108);
+    assert_eq!(buf[102..], [1, 2, 3, 4, 5, 6]);
+
+    let mut len = 50;
+    let code = unsafe {
+        wol_create_packet(
+            c"aa:bb:cc:dd:ee:ff".as_ptr(),
+            std::ptr::null(),
+            buf.as_mut_ptr(),
+            &mut len,
+        )
+    };
+    assert_eq!(code, WOL_ERR_BUFFER);
+    assert_eq!(len, 102);
+}
+
+#[test]
+fn test_errors() {
+    let mut buf = [0; WOL_PACKET_MAX];
+    let mut len = buf.len();
+    let mut create = |mac: *const c_char, password: *const c_char, len: &mut usize| unsafe {
+        wol_create_packet(mac, password, buf.as_mut_ptr(), len)
+    };
+    assert_eq!(
+        create(std::ptr::null(), std::ptr::null(), &mut len),
+        WOL_ERR_NULL
+    );
+    assert_eq!(
+        create(c"not a mac".as_ptr(), std::ptr::null(), &mut len),
+        WOL_ERR_INVALID_MAC
+    );
+    assert_eq!(
+        create(c"aa:bb:cc:dd:ee:ff".as_ptr(), c"secret".as_ptr(), &mut len),
+        WOL_ERR_INVALID_PASSWORD
+    );
+    let code = unsafe {
+        wol_send(
+            c"aa:bb:cc:dd:ee:ff".as_ptr(),
+            std::ptr::null(),
+            c"broadcast".as_ptr(),
+            9,
+        )
+    };
+    assert_eq!(code, WOL_ERR_INVALID_ADDRESS);
+
+    let message = |code| unsafe { CStr::from_ptr(wol_strerror(code)) };
+    assert_eq!(message(WOL_ERR_INVALID_MAC), c"invalid MAC address");
+    assert_eq!(message(1), c"unknown error");
+}
+
+#[test]
+fn test_send() {
+    let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+    let code = unsafe {
+        wol_send(
+            c"aa:bb:cc:dd:ee:ff".as_ptr(),
+            std::ptr::null(),
+            c"127.0.0.1".as_ptr(),
+            port,
+        )
+    };
+    assert_eq!(code, WOL_OK);
+    let mut buf = [0; 200];
+    assert_eq!(receiver.recv(&mut buf).unwrap(), 102);
+}
@@ -0,0 +1,64 @@
+//! systemd socket activation (sd_listen_fds(3)), through which `serve` and `relay` can be
+//! started on demand, on sockets systemd listens on in their place until the first request.
+use std::env;
+use std::io;
+use std::process;
+
+use crate::platform::InheritedSocket;
+
+/// The first file descriptor passed, following stdin, stdout and stderr.
+#[cfg(target_os = "linux")]
+const LISTEN_FDS_START: i32 = 3;
+
+/// The number of sockets passed according to LISTEN_PID and LISTEN_FDS, which are meant for the
+/// process with the given id only.
+fn count(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    if listen_pid.and_then(|listen_pid| listen_pid.parse().ok()) != Some(pid) {
+        return 0;
+    }
+    listen_fds
+        .and_then(|listen_fds| listen_fds.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Takes the sockets passed by systemd, in the order of the socket unit, or none when not
+/// started by it. The variables passing them are removed, so that the commands of hooks do not
+/// think they were passed sockets too.
+pub fn sockets() -> io::Result<Vec<InheritedSocket>> {
+    let listen_pid = env::var("LISTEN_PID").ok();
+    let listen_fds = env::var("LISTEN_FDS").ok();
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    let count = count(listen_pid.as_deref(), listen_fds.as_deref(), process::id());
+    inherit(count)
+}
+
+#[cfg(target_os = "linux")]
+fn inherit(count: usize) -> io::Result<Vec<InheritedSocket>> {
+    (LISTEN_FDS_START..)
+        .take(count)
+        // the descriptors are ours alone, as systemd passed them to this process only
+        .map(|fd| unsafe { crate::platform::inherit_socket(fd) })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn inherit(count: usize) -> io::Result<Vec<InheritedSocket>> {
+    if count > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "socket activation is not supported on this platform",
+        ));
+    }
+    Ok(Vec::new())
+}
+
+#[test]
+fn test_count() {
+    assert_eq!(count(Some("42"), Some("2"), 42), 2);
+    assert_eq!(count(Some("42"), Some("2"), 43), 0);
+    assert_eq!(count(None, Some("2"), 42), 0);
+    assert_eq!(count(Some("42"), None, 42), 0);
+    assert_eq!(count(Some("42"), Some("two"), 42), 0);
+}
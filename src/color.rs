@@ -0,0 +1,78 @@
+//! Colors of the results printed to standard output, so that those of batches stand out. Off
+//! unless it is a terminal and NO_COLOR is unset, or --color says otherwise.
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cli::ColorChoice;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns colors on or off for the rest of the run.
+pub fn init(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    };
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether colors are on, for what is drawn other than by these functions.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Wraps text in the SGR escape `code`, if `enabled`.
+fn paint(enabled: bool, code: &str, text: &str) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// For what went well.
+pub fn green(text: &str) -> String {
+    paint(ENABLED.load(Ordering::Relaxed), "32", text)
+}
+
+/// For what failed.
+pub fn red(text: &str) -> String {
+    paint(ENABLED.load(Ordering::Relaxed), "31", text)
+}
+
+/// For what is neither, like hosts whose state is unknown.
+pub fn yellow(text: &str) -> String {
+    paint(ENABLED.load(Ordering::Relaxed), "33", text)
+}
+
+/// For the headers of tables.
+pub fn bold(text: &str) -> String {
+    paint(ENABLED.load(Ordering::Relaxed), "1", text)
+}
+
+/// The width of text on a terminal, leaving out its color escapes.
+pub fn width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // an escape runs up to its final letter, e.g. the m of \x1b[32m
+            chars.find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+#[test]
+fn test_paint() {
+    assert_eq!(paint(true, "32", "up"), "\x1b[32mup\x1b[0m");
+    assert_eq!(paint(false, "32", "up"), "up");
+    assert_eq!(width(&paint(true, "31", "down")), 4);
+    assert_eq!(width("unknown: no ip"), 14);
+}
@@ -0,0 +1,144 @@
+//! `dbus`: the D-Bus service waking the hosts of the registry, on the session or system bus.
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::cli::SendArgs;
+use crate::targets::Target;
+use crate::{dbus, history};
+
+use super::load_config;
+use super::wake::wake_all;
+
+/// The name, object path and interface of the D-Bus service.
+const DBUS_NAME: &str = "org.wolcli.Wol";
+
+const DBUS_PATH: &str = "/org/wolcli/Wol";
+
+const DBUS_INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.wolcli.Wol">
+    <method name="Wake">
+      <arg name="alias" type="s" direction="in"/>
+    </method>
+    <method name="ListHosts">
+      <arg name="hosts" type="as" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="xml" type="s" direction="out"/>
+    </method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Peer">
+    <method name="Ping"/>
+  </interface>
+</node>
+"#;
+
+/// Owns the D-Bus service name and answers the calls made to it, until the connection to the
+/// bus is lost.
+pub fn dbus_service(system: bool, send: &SendArgs, config_path: &Path) -> ExitCode {
+    let bus = if system {
+        dbus::Bus::System
+    } else {
+        dbus::Bus::Session
+    };
+    let connection = dbus::Connection::connect(bus).and_then(|mut connection| {
+        connection.request_name(DBUS_NAME)?;
+        Ok(connection)
+    });
+    let mut connection = match connection {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!(
+                "unable to register {} on the {} bus: {}",
+                DBUS_NAME, bus, err
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("serving {} on the {} bus", DBUS_NAME, bus);
+    loop {
+        let call = match connection.next_message() {
+            Ok(call) => call,
+            Err(err) => {
+                eprintln!("lost the connection to the {} bus: {}", bus, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        if call.kind != dbus::METHOD_CALL {
+            continue;
+        }
+        let reply = answer_call(&call, send, config_path);
+        if call.expects_reply() {
+            if let Err(err) = connection.send(reply) {
+                eprintln!("lost the connection to the {} bus: {}", bus, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+}
+
+/// Answers a method call made to the D-Bus service.
+fn answer_call(call: &dbus::Message, send: &SendArgs, config_path: &Path) -> dbus::Message {
+    let failed = |text: &str| dbus::Message::error(call, "org.wolcli.Wol.Error.Failed", text);
+    if call.path.as_deref() != Some(DBUS_PATH) {
+        let text = format!("no object at {}", call.path.as_deref().unwrap_or_default());
+        return dbus::Message::error(call, "org.freedesktop.DBus.Error.UnknownObject", &text);
+    }
+    // the interface may be left out, as the method names do not clash
+    let member = call.member.as_deref().unwrap_or_default();
+    match (call.interface.as_deref(), member) {
+        (None | Some("org.freedesktop.DBus.Introspectable"), "Introspect") => {
+            dbus::Message::method_return(call, &[dbus::Value::Str(DBUS_INTROSPECTION.into())])
+        }
+        (None | Some("org.freedesktop.DBus.Peer"), "Ping") => {
+            dbus::Message::method_return(call, &[])
+        }
+        (None | Some(DBUS_NAME), "ListHosts") => match load_config(config_path) {
+            Some(config) => {
+                let names = config.hosts.into_iter().map(|host| host.name);
+                dbus::Message::method_return(call, &[dbus::Value::strings(names)])
+            }
+            None => failed("unable to load the registry"),
+        },
+        (None | Some(DBUS_NAME), "Wake") => {
+            let alias = match call.args().as_deref() {
+                Ok([dbus::Value::Str(alias)]) => alias.clone(),
+                _ => {
+                    return dbus::Message::error(
+                        call,
+                        "org.freedesktop.DBus.Error.InvalidArgs",
+                        "Wake takes the alias of a host or group",
+                    )
+                }
+            };
+            eprintln!(
+                "{} {}: waking {}",
+                history::format_time(history::now()),
+                call.sender.as_deref().unwrap_or_default(),
+                alias
+            );
+            // the registry is read for every call, so that changes to it take effect right away
+            let Some(config) = load_config(config_path) else {
+                return failed("unable to load the registry");
+            };
+            let failures: Vec<String> = wake_all(vec![Target::new(&alias)], send, &config)
+                .into_iter()
+                .filter(|(_, outcome)| !outcome.is_woken())
+                .map(|(target, outcome)| format!("{} {}", target, outcome.name()))
+                .collect();
+            if failures.is_empty() {
+                dbus::Message::method_return(call, &[])
+            } else {
+                failed(&failures.join(", "))
+            }
+        }
+        _ => dbus::Message::error(
+            call,
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("no method {}", member),
+        ),
+    }
+}
@@ -0,0 +1,385 @@
+//! The subcommands finding the hosts on the network: `neighbors`, `learn`, `scan` and `discover`.
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use wol_cli::MacAddr;
+
+use crate::config::{Config, Host};
+use crate::subnet::Subnet;
+use crate::{duration, json, learn, mdns, neighbors, netbios, oui, probe, table, wsd};
+
+use super::{confirm, load_config, local_subnets, save_config};
+
+/// The most addresses `scan` sends requests to on a subnet, that of a /16.
+const MAX_SCAN: u64 = 1 << 16;
+
+/// Prints the neighbor table, or the part of it on a subnet, along with the vendors of the MAC
+/// addresses and the names of the hosts already in the registry.
+pub fn list_neighbors(subnet: Option<Subnet>, json: bool, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let mut neighbors = match neighbors::table() {
+        Ok(neighbors) => neighbors,
+        Err(err) => {
+            eprintln!("unable to read the neighbor table: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(subnet) = subnet {
+        neighbors.retain(|neighbor| match neighbor.ip {
+            IpAddr::V4(ip) => subnet.contains(ip),
+            IpAddr::V6(_) => false,
+        });
+    }
+    neighbors.sort_by_key(|neighbor| neighbor.ip);
+
+    let host = |mac| config.host_with_mac(mac).map(|host| host.name.as_str());
+    if json {
+        let neighbors = neighbors
+            .iter()
+            .map(|neighbor| {
+                json::Value::object([
+                    ("ip", neighbor.ip.to_string().as_str().into()),
+                    ("mac", neighbor.mac.to_string().into()),
+                    ("vendor", oui::vendor(neighbor.mac).into()),
+                    ("interface", neighbor.interface.as_deref().into()),
+                    ("host", host(neighbor.mac).into()),
+                ])
+            })
+            .collect();
+        println!("{}", json::Value::Array(neighbors));
+        return ExitCode::SUCCESS;
+    }
+
+    if neighbors.is_empty() {
+        match subnet {
+            Some(subnet) => eprintln!("no neighbors on {}", subnet),
+            None => eprintln!("the neighbor table is empty"),
+        }
+        return ExitCode::SUCCESS;
+    }
+    let rows: Vec<Vec<String>> = neighbors
+        .iter()
+        .map(|neighbor| {
+            vec![
+                neighbor.ip.to_string(),
+                neighbor.mac.to_string(),
+                oui::vendor(neighbor.mac).unwrap_or_default().to_string(),
+                neighbor.interface.clone().unwrap_or_default(),
+                host(neighbor.mac).unwrap_or_default().to_string(),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        table::format(&["IP", "MAC", "VENDOR", "INTERFACE", "HOST"], &rows)
+    );
+    ExitCode::SUCCESS
+}
+
+/// Listens for hosts on an interface, prints those seen and suggests registry entries for the
+/// ones not in the registry.
+pub fn learn(interface: &str, duration: Duration, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    eprintln!(
+        "listening on {} for {}",
+        interface,
+        duration::format(duration)
+    );
+    let seen = match learn::listen(interface, duration) {
+        Ok(seen) => seen,
+        Err(err) => {
+            eprintln!("unable to listen on {}: {}", interface, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    if seen.is_empty() {
+        eprintln!("no hosts were seen");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut suggested = Config::default();
+    let rows: Vec<Vec<String>> = seen
+        .into_iter()
+        .map(|(ip, mac)| {
+            let host = config.host_with_mac(mac).map(|host| host.name.clone());
+            if host.is_none() {
+                suggested.hosts.push(Host {
+                    name: format!("host-{}", ip.to_string().replace('.', "-")),
+                    mac,
+                    ip: Some(ip.into()),
+                    ..Default::default()
+                });
+            }
+            let vendor = oui::vendor(mac).unwrap_or_default().to_string();
+            vec![
+                ip.to_string(),
+                mac.to_string(),
+                vendor,
+                host.unwrap_or_default(),
+            ]
+        })
+        .collect();
+    print!("{}", table::format(&["IP", "MAC", "VENDOR", "HOST"], &rows));
+    if !suggested.hosts.is_empty() {
+        println!(
+            "\n# entries for the registry, named as you like:\n{}",
+            suggested
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+/// ARP-scans a subnet, or those of the local interfaces, and prints the hosts that answered along
+/// with their NetBIOS names and the names of those already in the registry.
+pub fn scan(subnet: Option<Subnet>, timeout: Duration, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let subnets = match subnet {
+        Some(subnet) => vec![subnet],
+        None => match local_subnets() {
+            Ok(subnets) => subnets,
+            Err(err) => {
+                eprintln!("unable to list the network interfaces: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    if subnets.is_empty() {
+        eprintln!("no interfaces to scan, give a subnet like 192.168.1.0/24");
+        return ExitCode::FAILURE;
+    }
+
+    let mut found = Vec::new();
+    let mut failed = false;
+    for subnet in subnets {
+        if subnet.size() > MAX_SCAN {
+            eprintln!(
+                "{} has too many addresses to scan, give a /16 or smaller subnet",
+                subnet
+            );
+            failed = true;
+            continue;
+        }
+        match probe::arp_scan(&subnet, timeout) {
+            Ok(hosts) => found.extend(hosts),
+            Err(err) => {
+                eprintln!("unable to scan {}: {}", subnet, err);
+                failed = true;
+            }
+        }
+    }
+
+    // Windows hosts rarely announce themselves otherwise, but answer NetBIOS with their name
+    let names =
+        netbios::query_all(found.iter().map(|(ip, _)| *ip), timeout).unwrap_or_else(|err| {
+            eprintln!("unable to query NetBIOS names: {}", err);
+            Default::default()
+        });
+    let rows: Vec<Vec<String>> = found
+        .into_iter()
+        .map(|(ip, mac)| {
+            let host = config.host_with_mac(mac);
+            let vendor = oui::vendor(mac).unwrap_or_default().to_string();
+            let name = names.get(&ip).map(|status| status.name.clone());
+            vec![
+                ip.to_string(),
+                mac.to_string(),
+                vendor,
+                name.unwrap_or_default(),
+                host.map(|host| host.name.clone()).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    if !rows.is_empty() {
+        print!(
+            "{}",
+            table::format(&["IP", "MAC", "VENDOR", "NAME", "HOST"], &rows)
+        );
+    } else if !failed {
+        eprintln!("no hosts answered");
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// A host found by `discover`.
+#[derive(Debug, Default)]
+struct Discovered {
+    name: Option<String>,
+    mac: Option<MacAddr>,
+
+    /// How the host was found: the mDNS services it advertises, wsd or netbios.
+    services: Vec<String>,
+}
+
+/// Lists the hosts announcing themselves over mDNS, answering WS-Discovery probes or NetBIOS
+/// queries and asks whether to add those with a known MAC address that are not in the registry
+/// yet. Without any of the methods given, all of them are used.
+pub fn discover(
+    (mut mdns, mut netbios, mut wsd): (bool, bool, bool),
+    timeout: Duration,
+    config_path: &Path,
+) -> ExitCode {
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    if !(mdns || netbios || wsd) {
+        (mdns, netbios, wsd) = (true, true, true);
+    }
+
+    let mut hosts: BTreeMap<IpAddr, Discovered> = BTreeMap::new();
+    let mut failed = false;
+    if mdns {
+        match mdns::discover(timeout) {
+            Ok(devices) => {
+                for device in devices {
+                    // prefer the IPv4 address, which is what most hosts are woken and probed on
+                    let mut addrs = device.addrs;
+                    addrs.sort_by_key(IpAddr::is_ipv6);
+                    let host = hosts.entry(addrs[0]).or_default();
+                    host.name.get_or_insert(device.name);
+                    host.services.extend(device.services);
+                }
+            }
+            Err(err) => {
+                eprintln!("unable to browse mDNS: {}", err);
+                failed = true;
+            }
+        }
+    }
+    if wsd {
+        match wsd::discover(timeout) {
+            Ok(ips) => {
+                for ip in ips {
+                    hosts.entry(ip).or_default().services.push("wsd".into());
+                }
+            }
+            Err(err) => {
+                eprintln!("unable to send WS-Discovery probes: {}", err);
+                failed = true;
+            }
+        }
+    }
+
+    // WS-Discovery does not tell the names of hosts, but NetBIOS does
+    let mut ips: Vec<Ipv4Addr> = hosts
+        .keys()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ip) => Some(*ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    if netbios {
+        match local_subnets() {
+            Ok(subnets) => ips.extend(
+                subnets
+                    .iter()
+                    .filter(|subnet| subnet.size() <= MAX_SCAN)
+                    .flat_map(Subnet::hosts),
+            ),
+            Err(err) => {
+                eprintln!("unable to list the network interfaces: {}", err);
+                failed = true;
+            }
+        }
+    }
+    ips.sort();
+    ips.dedup();
+    match netbios::query_all(ips.into_iter(), timeout) {
+        Ok(statuses) => {
+            for (ip, status) in statuses {
+                let host = hosts.entry(ip.into()).or_default();
+                host.name.get_or_insert(status.name);
+                host.mac = host.mac.take().or(status.mac);
+                if netbios {
+                    host.services.push("netbios".into());
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("unable to query NetBIOS names: {}", err);
+            failed = true;
+        }
+    }
+
+    if hosts.is_empty() {
+        if !failed {
+            eprintln!("no hosts answered");
+        }
+        return if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        };
+    }
+
+    let mut found = Vec::new();
+    let mut rows = Vec::new();
+    for (ip, discovered) in hosts {
+        let mac = discovered
+            .mac
+            .or_else(|| neighbors::lookup(ip).ok().flatten());
+        let host = mac.and_then(|mac| config.host_with_mac(mac));
+        rows.push(vec![
+            discovered.name.clone().unwrap_or_default(),
+            ip.to_string(),
+            mac.map(|mac| mac.to_string()).unwrap_or_default(),
+            discovered.services.join(","),
+            host.map(|host| host.name.clone()).unwrap_or_default(),
+        ]);
+        if let (Some(name), Some(mac), None) = (discovered.name, mac, host) {
+            found.push((name.to_lowercase(), mac, ip));
+        }
+    }
+    print!(
+        "{}",
+        table::format(&["NAME", "IP", "MAC", "SERVICES", "HOST"], &rows)
+    );
+
+    let mut added = Vec::new();
+    for (name, mac, ip) in found {
+        if config.host(&name).is_some() {
+            continue;
+        }
+        if !confirm(&format!("add {} ({}) to the registry", name, mac)) {
+            continue;
+        }
+        let host = Host {
+            name,
+            mac,
+            ip: Some(ip),
+            ..Default::default()
+        };
+        if let Err(err) = host.validate() {
+            eprintln!("unable to add {}: {}", host.name, err);
+            continue;
+        }
+        added.push(format!("added {} ({})", host.name, host.mac));
+        config.hosts.push(host);
+    }
+    if !added.is_empty() {
+        if save_config(&config, config_path) != ExitCode::SUCCESS {
+            return ExitCode::FAILURE;
+        }
+        for added in added {
+            println!("{}", added);
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
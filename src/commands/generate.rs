@@ -0,0 +1,68 @@
+//! `completions` and `man`, generated from the command line as clap describes it.
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::CommandFactory;
+
+use crate::cli::Args;
+
+/// Prints the man page, or writes it and those of the subcommands into `dir`.
+pub fn man(dir: Option<&Path>) -> ExitCode {
+    let mut command = Args::command();
+    command.build();
+    let Some(dir) = dir else {
+        let mut page = Vec::new();
+        if let Err(err) = clap_mangen::Man::new(command)
+            .render(&mut page)
+            .and_then(|()| io::stdout().write_all(&page))
+        {
+            eprintln!("unable to write the man page: {}", err);
+            return ExitCode::FAILURE;
+        }
+        return ExitCode::SUCCESS;
+    };
+    match write_man_pages(dir, &command, command.get_name()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!(
+                "unable to write the man pages to {}: {}",
+                dir.display(),
+                err
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes the page of `command` as NAME.1, and those of its subcommands as NAME-SUBCOMMAND.1.
+fn write_man_pages(dir: &Path, command: &clap::Command, name: &str) -> io::Result<()> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(command.clone().name(name.to_string())).render(&mut page)?;
+    fs::write(dir.join(format!("{}.1", name)), page)?;
+    for subcommand in command.get_subcommands() {
+        // help is a subcommand too, but has nothing to document
+        if subcommand.get_name() != "help" {
+            let name = format!("{}-{}", name, subcommand.get_name());
+            write_man_pages(dir, subcommand, &name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the completion script of `shell`.
+pub fn completions(shell: clap_complete::Shell) -> ExitCode {
+    let mut command = Args::command();
+    let name = command.get_name().to_string();
+    // generated into memory, as the generators panic on a closed pipe
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, name, &mut script);
+    match io::stdout().write_all(&script) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("unable to write the completions: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! The gRPC service of `serve --grpc`, answered from the registry as the HTTP API is.
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::grpc;
+use crate::guard::Refusal;
+use crate::http;
+
+use super::hosts::{probe_hosts, STATUS_TIMEOUT};
+use super::serve::{check_served, wake_served, Served};
+use super::wake::Outcome;
+use super::{load_history, select_hosts};
+
+/// How often the hosts are probed for the Status method of the gRPC service, unless the call
+/// asks otherwise.
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+impl Served {
+    /// Loads the registry for a gRPC call, if the call carries a token of it where it has any.
+    /// The registry is read for every call, so that changes to it take effect right away.
+    fn authorize<T>(&self, call: &grpc::Call<T>) -> Result<Config, grpc::Status> {
+        let config = Config::load(&self.config_path).map_err(|err| {
+            let message = format!("unable to load {}: {}", self.config_path.display(), err);
+            grpc::Status::new(grpc::Code::Internal, message)
+        })?;
+        let tokens = &config.serve.tokens;
+        if !tokens.is_empty() && !http::is_authorized(call.token.as_deref(), tokens) {
+            return Err(grpc::Status::new(
+                grpc::Code::Unauthenticated,
+                "a valid bearer token is required",
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Answers the calls of the gRPC service, with the messages of wol.proto.
+impl grpc::Handler for Served {
+    fn wake(
+        &self,
+        call: grpc::Call<grpc::proto::WakeRequest>,
+    ) -> Result<grpc::proto::WakeReply, grpc::Status> {
+        let config = self.authorize(&call)?;
+        let name = &call.message.name;
+        let Some(host) = config.host(name) else {
+            let message = format!("no host named '{}'", name);
+            return Err(grpc::Status::new(grpc::Code::NotFound, message));
+        };
+        match check_served(&self.guard, call.client, host, &config) {
+            Err(refusal @ Refusal::NotAllowed) => {
+                let message = refusal.to_string();
+                return Err(grpc::Status::new(grpc::Code::PermissionDenied, message));
+            }
+            Err(refusal @ Refusal::RateLimited) => {
+                let message = refusal.to_string();
+                return Err(grpc::Status::new(grpc::Code::ResourceExhausted, message));
+            }
+            Ok(()) => {}
+        }
+        let outcome = wake_served(host, &self.send, &config, &self.waking, &self.metrics);
+        let outcome = match outcome {
+            Outcome::Woken => grpc::proto::Outcome::Woken,
+            Outcome::Invalid => grpc::proto::Outcome::Invalid,
+            Outcome::SendFailed => grpc::proto::Outcome::SendFailed,
+            Outcome::Failed => grpc::proto::Outcome::Failed,
+            Outcome::TimedOut => grpc::proto::Outcome::TimedOut,
+        };
+        Ok(grpc::proto::WakeReply {
+            host: host.name.clone(),
+            mac: host.mac.to_string(),
+            outcome: outcome.into(),
+        })
+    }
+
+    fn list_hosts(
+        &self,
+        call: grpc::Call<grpc::proto::ListHostsRequest>,
+    ) -> Result<grpc::proto::ListHostsReply, grpc::Status> {
+        let config = self.authorize(&call)?;
+        let history = load_history();
+        let hosts = config
+            .hosts
+            .iter()
+            .map(|host| grpc::proto::Host {
+                name: host.name.clone(),
+                mac: host.mac.to_string(),
+                ip: host.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                broadcast: host
+                    .broadcast
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                port: host.port.unwrap_or_default().into(),
+                groups: host.groups.clone(),
+                note: host.note.clone().unwrap_or_default(),
+                last_woken: history.get(&host.name).copied().unwrap_or_default(),
+            })
+            .collect();
+        Ok(grpc::proto::ListHostsReply { hosts })
+    }
+
+    /// Probes hosts every interval, sending the status of each once and then whenever it
+    /// changes, until the client cancels the call.
+    fn status(
+        &self,
+        call: grpc::Call<grpc::proto::StatusRequest>,
+        statuses: &grpc::Statuses,
+    ) -> Result<(), grpc::Status> {
+        let config = self.authorize(&call)?;
+        let names = &call.message.names;
+        let interval = match call.message.interval {
+            0 => STATUS_INTERVAL,
+            seconds => Duration::from_secs(seconds.into()),
+        };
+        let hosts = if names.is_empty() {
+            config.hosts.iter().collect()
+        } else {
+            select_hosts(&config, names).ok_or_else(|| {
+                let message = format!("no configured hosts match {}", names.join(", "));
+                grpc::Status::new(grpc::Code::NotFound, message)
+            })?
+        };
+
+        let send = &self.send;
+        let mut sent = HashMap::new();
+        loop {
+            for (host, ip, up, _) in probe_hosts(&hosts, send.wait_port, send.probe, STATUS_TIMEOUT)
+            {
+                let state = match up {
+                    Some(Ok(true)) => grpc::proto::State::Up,
+                    Some(Ok(false)) => grpc::proto::State::Down,
+                    _ => grpc::proto::State::Unknown,
+                };
+                if sent.get(&host.name) == Some(&state) {
+                    continue;
+                }
+                let status = grpc::proto::HostStatus {
+                    name: host.name.clone(),
+                    ip: ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                    state: state.into(),
+                };
+                // failing to send means the client went away, and there is no one left to tell
+                if statuses.send(status).is_err() {
+                    return Ok(());
+                }
+                sent.insert(&host.name, state);
+            }
+            let next = Instant::now() + interval;
+            while let Some(remaining) = next.checked_duration_since(Instant::now()) {
+                if !statuses.is_open() {
+                    return Ok(());
+                }
+                thread::sleep(remaining.min(Duration::from_secs(1)));
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! The subcommands telling about the hosts of the registry: `list`, `status` and `offline`.
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cli::Probe;
+use crate::config::{Config, Host};
+use crate::{color, history, json, oui, table};
+
+use super::wake::probe_host;
+use super::{host_ip, load_config, load_history, select_hosts};
+
+/// How long the hosts are probed for the status shown in the web UI.
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Prints the hosts of the registry along with when they were last woken.
+pub fn list(json: bool, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let history = load_history();
+
+    if json {
+        println!("{}", hosts_json(&config, &history));
+        return ExitCode::SUCCESS;
+    }
+
+    if config.hosts.is_empty() {
+        eprintln!("no hosts are configured");
+        return ExitCode::SUCCESS;
+    }
+    let now = history::now();
+    let rows: Vec<Vec<String>> = config
+        .hosts
+        .iter()
+        .map(|host| {
+            vec![
+                host.name.clone(),
+                host.mac.to_string(),
+                oui::vendor(host.mac).unwrap_or_default().to_string(),
+                host.broadcast
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                host.groups.join(","),
+                history
+                    .get(&host.name)
+                    .map(|&timestamp| history::ago(timestamp, now))
+                    .unwrap_or_else(|| "never".to_string()),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        table::format(
+            &["HOST", "MAC", "VENDOR", "BROADCAST", "GROUPS", "LAST WOKEN"],
+            &rows
+        )
+    );
+    ExitCode::SUCCESS
+}
+
+/// The hosts of the registry as a JSON array, as printed by `list --json` and served by `serve`.
+pub fn hosts_json(config: &Config, history: &HashMap<String, u64>) -> json::Value {
+    let hosts = config
+        .hosts
+        .iter()
+        .map(|host| {
+            json::Value::object([
+                ("name", host.name.as_str().into()),
+                ("mac", host.mac.to_string().into()),
+                ("vendor", oui::vendor(host.mac).into()),
+                (
+                    "broadcast",
+                    host.broadcast
+                        .map(|addr| addr.to_string())
+                        .as_deref()
+                        .into(),
+                ),
+                ("ip", host.ip.map(|ip| ip.to_string()).as_deref().into()),
+                (
+                    "port",
+                    host.port
+                        .map(|port| json::Value::Number(port.into()))
+                        .into(),
+                ),
+                (
+                    "groups",
+                    json::Value::Array(
+                        host.groups
+                            .iter()
+                            .map(|group| group.as_str().into())
+                            .collect(),
+                    ),
+                ),
+                ("note", host.note.as_deref().into()),
+                (
+                    "last_woken",
+                    history
+                        .get(&host.name)
+                        .map(|&timestamp| json::Value::Number(timestamp as i64))
+                        .into(),
+                ),
+            ])
+        })
+        .collect();
+    json::Value::Array(hosts)
+}
+
+/// A probed host of the registry, the address it was probed at, if it has one, whether it is up
+/// and how long probing it took.
+type Probed<'a> = (&'a Host, Option<IpAddr>, Option<io::Result<bool>>, Duration);
+
+/// Probes hosts of the registry in parallel.
+pub fn probe_hosts<'a>(
+    hosts: &[&'a Host],
+    port: Option<u16>,
+    method: Probe,
+    timeout: Duration,
+) -> Vec<Probed<'a>> {
+    thread::scope(|scope| {
+        let probes: Vec<_> = hosts
+            .iter()
+            .map(|&host| {
+                scope.spawn(move || {
+                    let start = Instant::now();
+                    let ip = host_ip(host);
+                    let up = ip.map(|ip| probe_host(ip, port, method, timeout));
+                    (host, ip, up, start.elapsed())
+                })
+            })
+            .collect();
+        probes
+            .into_iter()
+            .map(|probe| probe.join().unwrap())
+            .collect()
+    })
+}
+
+/// Probes the given hosts of the registry, or all of them, in parallel and prints which are up.
+pub fn status(
+    names: &[String],
+    port: Option<u16>,
+    method: Probe,
+    timeout: Duration,
+    config_path: &Path,
+) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let Some(hosts) = select_hosts(&config, names) else {
+        return ExitCode::FAILURE;
+    };
+
+    let rows: Vec<Vec<String>> = probe_hosts(&hosts, port, method, timeout)
+        .into_iter()
+        .map(|(host, ip, up, _)| {
+            vec![
+                host.name.clone(),
+                ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                status_cell(&up),
+            ]
+        })
+        .collect();
+    print!("{}", table::format(&["HOST", "IP", "STATUS"], &rows));
+    ExitCode::SUCCESS
+}
+
+/// Whether a host is up as shown by `status` and `tui`, from probing it.
+fn status_cell(up: &Option<io::Result<bool>>) -> String {
+    match up {
+        Some(Ok(true)) => color::green("up"),
+        Some(Ok(false)) => color::red("down"),
+        Some(Err(err)) => color::yellow(&format!("unknown: {}", err)),
+        None => color::yellow("unknown: no ip"),
+    }
+}
+
+/// Probes the hosts of the registry and prints the names of those that are down, one per line, so
+/// that they can be piped back into `wol-cli -`.
+pub fn offline(
+    names: &[String],
+    port: Option<u16>,
+    method: Probe,
+    timeout: Duration,
+    config_path: &Path,
+) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let Some(hosts) = select_hosts(&config, names) else {
+        return ExitCode::FAILURE;
+    };
+
+    for (host, _, up, _) in probe_hosts(&hosts, port, method, timeout) {
+        match up {
+            Some(Ok(true)) => {}
+            Some(Ok(false)) => println!("{}", host.name),
+            Some(Err(err)) => eprintln!("unable to probe {}: {}", host.name, err),
+            None => eprintln!(
+                "unable to probe {}, its IP address is unknown: set ip in the registry",
+                host.name
+            ),
+        }
+    }
+    ExitCode::SUCCESS
+}
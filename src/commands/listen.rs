@@ -0,0 +1,91 @@
+//! `listen`: prints the magic packets received on the given ports, and the hosts they wake.
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+
+use wol_cli::{wol, MacAddr};
+
+use crate::config::Config;
+use crate::history;
+
+use super::load_config;
+
+/// Prints the magic packets received on the given ports until interrupted.
+pub fn listen(ports: &[u16], bind: IpAddr, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let mut sockets = Vec::new();
+    for &port in ports {
+        match UdpSocket::bind((bind, port)) {
+            Ok(socket) => sockets.push(socket),
+            Err(err) => {
+                eprintln!(
+                    "unable to listen on {}: {}",
+                    SocketAddr::new(bind, port),
+                    err
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    for port in ports {
+        eprintln!("listening on {}", SocketAddr::new(bind, *port));
+    }
+
+    let config = &config;
+    let failed = thread::scope(|scope| {
+        let listeners: Vec<_> = sockets
+            .into_iter()
+            .map(|socket| scope.spawn(move || receive_packets(&socket, config)))
+            .collect();
+        listeners
+            .into_iter()
+            .any(|listener| !matches!(listener.join(), Ok(Ok(()))))
+    });
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn receive_packets(socket: &UdpSocket, config: &Config) -> io::Result<()> {
+    let local = socket.local_addr()?;
+    let mut buf = [0; 1500];
+    loop {
+        let (len, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                eprintln!("unable to receive on {}: {}", local, err);
+                return Err(err);
+            }
+        };
+        let time = history::format_time(history::now());
+        let packet = match wol::MagicPacket::parse(&buf[..len]) {
+            Ok(packet) => packet,
+            Err(err) => {
+                println!(
+                    "{} {} -> :{} not a magic packet: {}",
+                    time,
+                    source,
+                    local.port(),
+                    err
+                );
+                continue;
+            }
+        };
+        let mac = packet.mac();
+        let mut line = format!("{} {} -> :{} {}", time, source, local.port(), mac);
+        if let Some(host) = config.host_with_mac(mac) {
+            line += &format!(" ({})", host.name);
+        }
+        if let Some(password) = packet.password() {
+            line += &format!(" with password {}", MacAddr(password));
+        }
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,162 @@
+//! The subcommands, each in a module of its own, and what several of them share: loading and
+//! saving the registry, selecting its hosts and the prompts of those that change it.
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::Path;
+use std::process::ExitCode;
+
+use crate::config::{Config, Host};
+use crate::subnet::Subnet;
+use crate::{config, history, platform};
+
+pub mod dbus;
+pub mod discover;
+pub mod generate;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hosts;
+pub mod listen;
+pub mod mqtt;
+pub mod proxy;
+pub mod registry;
+pub mod relay;
+pub mod schedule;
+pub mod serve;
+pub mod tui;
+pub mod wake;
+
+/// Loads the registry, treating a missing one as empty.
+pub fn load_config(path: &Path) -> Option<Config> {
+    match Config::load(path) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            eprintln!("unable to load {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Writes the registry back to its file.
+pub fn save_config(config: &Config, path: &Path) -> ExitCode {
+    match config.save(path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("unable to write {}: {}", path.display(), err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads the wake history, treating an unreadable one as empty.
+pub fn load_history() -> HashMap<String, u64> {
+    match history::default_path().map(|path| history::load(&path)) {
+        Some(Ok(history)) => history,
+        Some(Err(err)) => {
+            eprintln!("unable to read the wake history: {}", err);
+            Default::default()
+        }
+        None => Default::default(),
+    }
+}
+
+/// The address to probe a host of the registry at: its configured ip, or else its name resolved
+/// in DNS.
+pub fn host_ip(host: &Host) -> Option<IpAddr> {
+    host.ip.or_else(|| {
+        (host.name.as_str(), 0)
+            .to_socket_addrs()
+            .ok()?
+            .next()
+            .map(|addr| addr.ip())
+    })
+}
+
+/// The hosts of the registry with the given names, in the given groups or matching the given
+/// patterns, or all of them when none are given, printing why when there are none.
+pub fn select_hosts<'a>(config: &'a Config, names: &[String]) -> Option<Vec<&'a Host>> {
+    let mut hosts: Vec<&Host> = Vec::new();
+    if names.is_empty() {
+        hosts.extend(&config.hosts);
+    }
+    for name in names {
+        let matching = match config.host(name) {
+            Some(host) => vec![host],
+            None if config::is_pattern(name) => config.matching(name),
+            None => config.group(name),
+        };
+        if matching.is_empty() {
+            eprintln!("no configured hosts match '{}'", name);
+        }
+        for host in matching {
+            if !hosts.contains(&host) {
+                hosts.push(host);
+            }
+        }
+    }
+    if hosts.is_empty() {
+        if names.is_empty() {
+            eprintln!("no hosts are configured");
+        }
+        return None;
+    }
+    Some(hosts)
+}
+
+/// The subnets of the local interfaces.
+pub fn local_subnets() -> io::Result<Vec<Subnet>> {
+    let mut subnets: Vec<Subnet> = Vec::new();
+    for interface in platform::interfaces()? {
+        let subnet = Subnet::with_netmask(interface.addr, interface.netmask);
+        if !subnets.contains(&subnet) {
+            subnets.push(subnet);
+        }
+    }
+    Ok(subnets)
+}
+
+/// Asks the user to confirm an action on standard input, refusing when it is not a terminal.
+pub fn confirm(question: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        return false;
+    }
+
+    print!("{}? [y/N] ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// Asks a question and reads the answer from standard input, trimmed. Returns None once
+/// standard input ends.
+pub fn prompt(question: &str) -> Option<String> {
+    print!("{}: ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
+}
+
+/// Asks until the answer parses, or is left blank for the default. Returns None once standard
+/// input ends.
+pub fn prompt_parsed<T>(question: &str) -> Option<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    loop {
+        let answer = prompt(question)?;
+        if answer.is_empty() {
+            return Some(None);
+        }
+        match answer.parse() {
+            Ok(value) => return Some(Some(value)),
+            Err(err) => eprintln!("invalid value '{}': {}", answer, err),
+        }
+    }
+}
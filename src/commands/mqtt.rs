@@ -0,0 +1,175 @@
+//! `mqtt`: wakes the hosts named on an MQTT topic, publishing whether they are up and, with
+//! --homeassistant, the entities of Home Assistant discovery.
+use std::io;
+use std::path::Path;
+use std::process::{self, ExitCode};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::SendArgs;
+use crate::config::Host;
+use crate::homeassistant::Discovery;
+use crate::targets::Target;
+use crate::{duration, history, mqtt};
+
+use super::hosts::{probe_hosts, STATUS_TIMEOUT};
+use super::load_config;
+use super::wake::wake_all;
+
+/// How long to wait before reconnecting to a broker that closed the connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How often the hosts are probed for the sensors published for Home Assistant.
+const STATE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Wakes the targets named in the messages published on an MQTT topic, until interrupted. Once
+/// connected, the broker is reconnected to whenever the connection is lost.
+pub fn subscribe(
+    broker: &mqtt::Broker,
+    topic: &str,
+    homeassistant: Option<&str>,
+    send: &SendArgs,
+    config_path: &Path,
+) -> ExitCode {
+    let discovery = match homeassistant.map(|prefix| Discovery::new(prefix, topic)) {
+        Some(None) => {
+            eprintln!(
+                "--homeassistant requires a topic ending in /# or /+, not {}",
+                topic
+            );
+            return ExitCode::FAILURE;
+        }
+        discovery => discovery.flatten(),
+    };
+    let will = discovery
+        .as_ref()
+        .map(|discovery| mqtt::Message::new(&discovery.availability_topic(), "offline"));
+
+    let client_id = format!("wol-cli-{}", process::id());
+    let mut connected = false;
+    loop {
+        let client =
+            mqtt::Client::connect(broker, &client_id, will.as_ref()).and_then(|mut client| {
+                client.subscribe(topic)?;
+                if let Some(discovery) = &discovery {
+                    announce(&client.publisher(), discovery, config_path)?;
+                }
+                Ok(client)
+            });
+        let err = match client {
+            Ok(client) => {
+                connected = true;
+                eprintln!("subscribed to {} on {}", topic, broker);
+                receive_messages(client, discovery.as_ref(), send, config_path)
+            }
+            Err(err) => err,
+        };
+        if !connected {
+            eprintln!("unable to subscribe to {} on {}: {}", topic, broker, err);
+            return ExitCode::FAILURE;
+        }
+        eprintln!(
+            "lost the connection to {}: {}, reconnecting in {}",
+            broker,
+            err,
+            duration::format(RECONNECT_DELAY)
+        );
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// Publishes the entities of the hosts of the registry for Home Assistant, and that they are
+/// available.
+fn announce(
+    publisher: &mqtt::Publisher,
+    discovery: &Discovery,
+    config_path: &Path,
+) -> io::Result<()> {
+    if let Some(config) = load_config(config_path) {
+        for host in &config.hosts {
+            for (topic, payload) in discovery.configs(host) {
+                publisher.publish(&mqtt::Message::new(&topic, payload), true)?;
+            }
+        }
+    }
+    let online = mqtt::Message::new(&discovery.availability_topic(), "online");
+    publisher.publish(&online, true)
+}
+
+/// Probes the hosts of the registry every `STATE_INTERVAL` and publishes whether they are up,
+/// until `stop` is dropped.
+fn publish_states(
+    publisher: &mqtt::Publisher,
+    discovery: &Discovery,
+    send: &SendArgs,
+    config_path: &Path,
+    stop: &mpsc::Receiver<()>,
+) {
+    loop {
+        if let Some(config) = load_config(config_path) {
+            let hosts: Vec<&Host> = config.hosts.iter().collect();
+            for (host, _, up, _) in probe_hosts(&hosts, send.wait_port, send.probe, STATUS_TIMEOUT)
+            {
+                // hosts that cannot be probed are left unknown
+                let state = match up {
+                    Some(Ok(true)) => "ON",
+                    Some(Ok(false)) => "OFF",
+                    _ => continue,
+                };
+                let message = mqtt::Message::new(&discovery.state_topic(host), state);
+                if publisher.publish(&message, true).is_err() {
+                    return;
+                }
+            }
+        }
+        if stop.recv_timeout(STATE_INTERVAL) != Err(mpsc::RecvTimeoutError::Timeout) {
+            return;
+        }
+    }
+}
+
+/// Wakes the target named in each message received, returning why the connection was lost.
+fn receive_messages(
+    mut client: mqtt::Client,
+    discovery: Option<&Discovery>,
+    send: &SendArgs,
+    config_path: &Path,
+) -> io::Error {
+    let publisher = client.publisher();
+    let (stop, stopped) = mpsc::channel();
+    thread::scope(|scope| {
+        if let Some(discovery) = discovery {
+            scope.spawn(move || publish_states(&publisher, discovery, send, config_path, &stopped));
+        }
+        let err = loop {
+            let message = match client.next_message() {
+                Ok(message) => message,
+                Err(err) => break err,
+            };
+            if discovery.is_some_and(|discovery| discovery.is_own(&message.topic)) {
+                continue;
+            }
+            let payload = String::from_utf8_lossy(&message.payload);
+            let name = match payload.trim() {
+                "" => message.topic.rsplit('/').next().unwrap_or_default(),
+                name => name,
+            };
+            if name.is_empty() {
+                continue;
+            }
+            eprintln!(
+                "{} {}: waking {}",
+                history::format_time(history::now()),
+                message.topic,
+                name
+            );
+            // the registry is read for every message, so that changes to it take effect right away
+            if let Some(config) = load_config(config_path) {
+                wake_all(vec![Target::new(name)], send, &config);
+            }
+        };
+        drop(stop);
+        err
+    })
+}
@@ -0,0 +1,173 @@
+//! `proxy`: answers ARP for sleeping hosts, waking them when a connection to them is attempted.
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use crate::cli::SendArgs;
+use crate::config::Host;
+use crate::{history, platform, probe, proxy};
+
+use super::hosts::{probe_hosts, STATUS_TIMEOUT};
+use super::wake::wake_all;
+use super::{load_config, select_hosts};
+
+/// How often the hosts that are awake are probed by the sleep proxy, to see whether they fell
+/// asleep.
+const PROXY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What the sleep proxy knows of a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sleep {
+    Awake,
+
+    /// Asleep, with ARP for its address answered by us.
+    Asleep,
+
+    /// Woken at the given instant, and not seen since.
+    Waking(Instant),
+}
+
+/// A host the sleep proxy answers for.
+struct Proxied<'a> {
+    host: &'a Host,
+    ip: Ipv4Addr,
+    mac: [u8; 6],
+    state: Sleep,
+}
+
+/// Answers ARP for the given hosts while they are asleep and wakes them when a connection to
+/// them is attempted, until interrupted.
+pub fn sleep_proxy(
+    names: &[String],
+    wake_on: &[u16],
+    send: &SendArgs,
+    config_path: &Path,
+) -> ExitCode {
+    let Some(interface) = &send.interface else {
+        eprintln!("proxy requires the --interface the hosts are on");
+        return ExitCode::FAILURE;
+    };
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let Some(hosts) = select_hosts(&config, names) else {
+        return ExitCode::FAILURE;
+    };
+    let mut proxied = Vec::new();
+    for host in hosts {
+        let Some(IpAddr::V4(ip)) = host.ip else {
+            eprintln!(
+                "{} has no IPv4 ip in the registry to answer ARP for",
+                host.name
+            );
+            return ExitCode::FAILURE;
+        };
+        proxied.push(Proxied {
+            host,
+            ip,
+            mac: host.mac.octets(),
+            state: Sleep::Awake,
+        });
+    }
+    let socket = platform::hardware_addr(interface).and_then(|own| {
+        let socket = platform::packet_socket(interface, proxy::ETHERTYPE_ALL)?;
+        Ok((own, socket))
+    });
+    let (own, socket) = match socket {
+        Ok(socket) => socket,
+        Err(err) => {
+            eprintln!("unable to listen on {}: {}", interface, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("proxying on {} for {}", interface, names.join(", "));
+
+    let mut next_probe = Instant::now();
+    let mut buf = [0; 65536];
+    loop {
+        if Instant::now() >= next_probe {
+            probe_proxied(&mut proxied, own, &socket, send);
+            next_probe = Instant::now() + PROXY_INTERVAL;
+        }
+        let remaining = next_probe.saturating_duration_since(Instant::now());
+        let len = match socket
+            .set_read_timeout(Some(remaining.max(Duration::from_millis(1))))
+            .and_then(|()| socket.recv(&mut buf))
+        {
+            Ok(len) => len,
+            Err(err) if probe::is_timeout(&err) => continue,
+            Err(err) => {
+                eprintln!("unable to receive on {}: {}", interface, err);
+                return ExitCode::FAILURE;
+            }
+        };
+        let Some((from, frame)) = proxy::Frame::parse(&buf[..len]) else {
+            continue;
+        };
+        for proxied in &mut proxied {
+            let name = &proxied.host.name;
+            let time = history::format_time(history::now());
+            if from == proxied.mac {
+                if proxied.state != Sleep::Awake {
+                    eprintln!("{} {} is up, stepping aside", time, name);
+                    // unanswered, the hosts we claimed its address for would retry for a while
+                    let _ = socket.send(&proxy::hand_back(own, proxied.mac, proxied.ip));
+                    proxied.state = Sleep::Awake;
+                }
+                continue;
+            }
+            if proxied.state == Sleep::Awake || from == own {
+                continue;
+            }
+            match frame {
+                proxy::Frame::WhoHas { ip, target } if target == proxied.ip && ip != target => {
+                    let _ = socket.send(&proxy::claim(own, target, (from, ip)));
+                }
+                proxy::Frame::Connect { source, ip, port }
+                    if ip == proxied.ip
+                        && proxied.state == Sleep::Asleep
+                        && (wake_on.is_empty() || wake_on.contains(&port)) =>
+                {
+                    eprintln!(
+                        "{} {} connecting to {}:{}, waking it",
+                        time, source, name, port
+                    );
+                    wake_all(vec![proxied.host.to_target()], send, &config);
+                    proxied.state = Sleep::Waking(Instant::now());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Probes the proxied hosts that are awake, answering ARP for those that fell asleep, and gives
+/// up on those that did not wake up after all.
+fn probe_proxied(proxied: &mut [Proxied], own: [u8; 6], socket: &UdpSocket, send: &SendArgs) {
+    let awake: Vec<&Host> = proxied
+        .iter()
+        .filter(|proxied| proxied.state == Sleep::Awake)
+        .map(|proxied| proxied.host)
+        .collect();
+    let asleep: Vec<String> = probe_hosts(&awake, send.wait_port, send.probe, STATUS_TIMEOUT)
+        .into_iter()
+        .filter(|(_, _, up, _)| matches!(up, Some(Ok(false))))
+        .map(|(host, _, _, _)| host.name.clone())
+        .collect();
+    let time = history::format_time(history::now());
+    for proxied in proxied {
+        let name = &proxied.host.name;
+        match proxied.state {
+            Sleep::Awake if asleep.contains(name) => {
+                eprintln!("{} {} is asleep, answering for {}", time, name, proxied.ip);
+            }
+            Sleep::Waking(since) if since.elapsed() > probe::WAIT_TIMEOUT => {
+                eprintln!("{} {} did not wake up, answering for it again", time, name);
+            }
+            _ => continue,
+        }
+        proxied.state = Sleep::Asleep;
+        let _ = socket.send(&proxy::announce(own, proxied.ip));
+    }
+}
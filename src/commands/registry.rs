@@ -0,0 +1,382 @@
+//! The subcommands changing the registry: `add`, `remove`, `rename`, `edit`, `config` and `import`.
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+
+use crate::cli::ExportFormat;
+use crate::config::{Config, Host};
+use crate::{history, leases, snmp};
+
+use super::{confirm, load_config, save_config};
+
+/// Adds a single host to the registry.
+pub fn add(host: Host, config_path: &Path) -> ExitCode {
+    if let Err(err) = host.validate() {
+        eprintln!("unable to add {}: {}", host.name, err);
+        return ExitCode::FAILURE;
+    }
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    if config.host(&host.name).is_some() {
+        eprintln!("{} is already in the registry", host.name);
+        return ExitCode::FAILURE;
+    }
+
+    let added = format!("added {} ({})", host.name, host.mac);
+    config.hosts.push(host);
+    if save_config(&config, config_path) != ExitCode::SUCCESS {
+        return ExitCode::FAILURE;
+    }
+    println!("{}", added);
+    ExitCode::SUCCESS
+}
+
+/// Removes a host, or a group from all of its hosts, from the registry.
+pub fn remove(name: &str, force: bool, config_path: &Path) -> ExitCode {
+    if !force && !io::stdin().is_terminal() {
+        eprintln!(
+            "refusing to remove {} without confirmation, use --force",
+            name
+        );
+        return ExitCode::FAILURE;
+    }
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+
+    if let Some(host) = config.host(name) {
+        let question = format!("remove host {} ({})", host.name, host.mac);
+        if !force && !confirm(&question) {
+            return ExitCode::FAILURE;
+        }
+        config.remove_host(name);
+        println!("removed host {}", name);
+    } else {
+        let members = config.group(name).len();
+        if members == 0 {
+            eprintln!("no host or group named {} in the registry", name);
+            return ExitCode::FAILURE;
+        }
+        let question = format!("remove group {} from {} hosts", name, members);
+        if !force && !confirm(&question) {
+            return ExitCode::FAILURE;
+        }
+        config.remove_group(name);
+        println!("removed group {}", name);
+    }
+
+    save_config(&config, config_path)
+}
+
+/// Renames a host, or a group on all of its hosts.
+pub fn rename(old: &str, new: &str, config_path: &Path) -> ExitCode {
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    if config.host(new).is_some() {
+        eprintln!("{} is already in the registry", new);
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(host) = config.hosts.iter_mut().find(|host| host.name == old) {
+        let renamed = Host {
+            name: new.to_string(),
+            ..host.clone()
+        };
+        if let Err(err) = renamed.validate() {
+            eprintln!("unable to rename {}: {}", old, err);
+            return ExitCode::FAILURE;
+        }
+        *host = renamed;
+        if save_config(&config, config_path) != ExitCode::SUCCESS {
+            return ExitCode::FAILURE;
+        }
+        if let Some(path) = history::default_path() {
+            if let Err(err) = history::rename(&path, old, new) {
+                eprintln!("unable to update the wake history: {}", err);
+            }
+        }
+        println!("renamed host {} to {}", old, new);
+        ExitCode::SUCCESS
+    } else if !config.group(old).is_empty() {
+        config.rename_group(old, new);
+        if save_config(&config, config_path) != ExitCode::SUCCESS {
+            return ExitCode::FAILURE;
+        }
+        println!("renamed group {} to {}", old, new);
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("no host or group named {} in the registry", old);
+        ExitCode::FAILURE
+    }
+}
+
+/// Lets the user edit a copy of the registry, which only replaces the registry once it is valid.
+pub fn edit(path: &Path) -> ExitCode {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(".edit");
+    let copy = PathBuf::from(copy);
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            eprintln!("unable to read {}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(err) = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&copy, contents))
+    {
+        eprintln!("unable to write {}: {}", copy.display(), err);
+        return ExitCode::FAILURE;
+    }
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+    // the editor may come with arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+
+    loop {
+        match process::Command::new(program)
+            .args(words.clone())
+            .arg(&copy)
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                eprintln!("{} exited with {}, discarding changes", editor, status);
+                break;
+            }
+            Err(err) => {
+                eprintln!("unable to run {}: {}", editor, err);
+                break;
+            }
+        }
+
+        // as thoroughly as config validate, so that no host that could never be woken by name,
+        // or that shares its MAC address, is saved
+        let problems = match fs::read_to_string(&copy) {
+            Ok(contents) => Config::check(&contents),
+            Err(err) => vec![err.into()],
+        };
+        if problems.is_empty() {
+            if let Err(err) = fs::rename(&copy, path) {
+                eprintln!("unable to write {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+            return ExitCode::SUCCESS;
+        }
+        for problem in &problems {
+            eprintln!("{}: {}", path.display(), problem);
+        }
+        if !confirm("edit again") {
+            eprintln!("discarding changes");
+            break;
+        }
+    }
+
+    let _ = fs::remove_file(&copy);
+    ExitCode::FAILURE
+}
+
+/// Prints the registry in a machine-readable format.
+pub fn export(format: ExportFormat, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    match format {
+        ExportFormat::Json => println!("{:#}", config.to_json()),
+        ExportFormat::Toml => print!("{}", config),
+    }
+    ExitCode::SUCCESS
+}
+
+/// Reports every problem in a registry, failing if there are any.
+pub fn validate(path: &Path) -> ExitCode {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("unable to read {}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let problems = Config::check(&contents);
+    for problem in &problems {
+        eprintln!("{}: {}", path.display(), problem);
+    }
+    if !problems.is_empty() {
+        return ExitCode::FAILURE;
+    }
+    println!("{}: no problems found", path.display());
+    ExitCode::SUCCESS
+}
+
+/// Adds the hosts of an exported registry to the registry.
+pub fn import(file: &Path, replace: bool, config_path: &Path) -> ExitCode {
+    let contents = if file == Path::new("-") {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(file)
+    };
+    let contents = match contents {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("unable to read {}: {}", file.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let is_json =
+        file.extension().is_some_and(|ext| ext == "json") || contents.trim_start().starts_with('{');
+    let imported = if is_json {
+        Config::from_json(&contents)
+    } else {
+        Config::parse(&contents)
+    };
+    let imported = match imported {
+        Ok(imported) => imported,
+        Err(err) => {
+            eprintln!("{}: {}", file.display(), err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let mut changed = false;
+    let mut skipped = 0;
+    for found in imported.hosts {
+        match config.hosts.iter_mut().find(|host| host.name == found.name) {
+            Some(host) if *host == found => {}
+            Some(host) if replace => {
+                println!("replaced {} ({})", found.name, found.mac);
+                *host = found;
+                changed = true;
+            }
+            Some(_) => skipped += 1,
+            None => {
+                println!("added {} ({})", found.name, found.mac);
+                config.hosts.push(found);
+                changed = true;
+            }
+        }
+    }
+
+    if skipped > 0 {
+        println!(
+            "skipped {} hosts already configured differently, use --replace to overwrite them",
+            skipped
+        );
+    }
+    if !changed {
+        return ExitCode::SUCCESS;
+    }
+    save_config(&config, config_path)
+}
+
+/// Adds the hosts found in DHCP lease files to the registry.
+pub fn import_leases(files: &[PathBuf], replace: bool, config_path: &Path) -> ExitCode {
+    let mut found = Vec::new();
+    for path in files {
+        match leases::read_file(path) {
+            Ok(leases) => found.extend(leases.into_iter().map(|lease| Host {
+                name: lease.hostname,
+                mac: lease.mac,
+                ..Default::default()
+            })),
+            Err(err) => {
+                eprintln!("unable to read {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    import_hosts(found, replace, config_path)
+}
+
+/// Adds the MAC addresses learned by a switch to the registry.
+pub fn import_snmp(
+    switch: &str,
+    community: &str,
+    prefix: &str,
+    replace: bool,
+    config_path: &Path,
+) -> ExitCode {
+    let entries = match snmp::walk_fdb(switch, community) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("unable to walk the forwarding table of {}: {}", switch, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    if entries.is_empty() {
+        eprintln!("{} has not learned any MAC addresses", switch);
+        return ExitCode::FAILURE;
+    }
+
+    let found = entries
+        .into_iter()
+        .map(|entry| Host {
+            name: format!("{}{}", prefix, entry.mac.to_string().replace(':', "")),
+            note: Some(format!("seen on {} port {}", switch, entry.port)),
+            mac: entry.mac,
+            ..Default::default()
+        })
+        .collect();
+    import_hosts(found, replace, config_path)
+}
+
+/// Adds hosts to the registry, skipping those already configured under the same name unless
+/// `replace` is set, in which case their MAC address and note are updated.
+fn import_hosts(found: Vec<Host>, replace: bool, config_path: &Path) -> ExitCode {
+    let Some(mut config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+
+    let mut changes = Vec::new();
+    let mut skipped = 0;
+    for found in found {
+        // a host that does not validate would keep the whole registry from loading
+        if let Err(err) = found.validate() {
+            eprintln!("skipped {}: {}", found.name, err);
+            continue;
+        }
+        match config.hosts.iter_mut().find(|host| host.name == found.name) {
+            Some(host) if host.mac == found.mac => {}
+            Some(host) if replace => {
+                changes.push(format!("updated {} ({})", host.name, found.mac));
+                host.mac = found.mac;
+                host.note = found.note.or(host.note.take());
+            }
+            Some(_) => skipped += 1,
+            None => {
+                changes.push(format!("added {} ({})", found.name, found.mac));
+                config.hosts.push(found);
+            }
+        }
+    }
+
+    if !changes.is_empty() && save_config(&config, config_path) != ExitCode::SUCCESS {
+        return ExitCode::FAILURE;
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    if skipped > 0 {
+        println!(
+            "skipped {} hosts already configured with another MAC address, use --replace to update them",
+            skipped
+        );
+    }
+    ExitCode::SUCCESS
+}
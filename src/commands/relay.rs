@@ -0,0 +1,233 @@
+//! `relay`: broadcasts the magic packets and requests received from other networks.
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpListener, UdpSocket};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+
+use wol_cli::{wol, MacAddr};
+
+use crate::cli::GuardArgs;
+use crate::config::Config;
+use crate::guard::Guard;
+use crate::platform::InheritedSocket;
+use crate::{history, platform, relay};
+
+use super::load_config;
+use super::serve::inherited_sockets;
+
+/// Broadcasts the magic packets received on the `listen` endpoints and the requests received on
+/// `tcp` on the `emit` interfaces, until interrupted.
+pub fn run_relay(
+    listen: &[relay::Endpoint],
+    tcp: Option<SocketAddr>,
+    tls: Option<(&Path, &Path)>,
+    emit: &[String],
+    emit_port: u16,
+    guard: &GuardArgs,
+    config_path: &Path,
+) -> ExitCode {
+    let interfaces = match platform::interfaces() {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            eprintln!("unable to list interfaces: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut outputs = Vec::new();
+    for name in emit {
+        let found: Vec<_> = interfaces.iter().filter(|i| &i.name == name).collect();
+        if found.is_empty() {
+            eprintln!("{} has no IPv4 address to broadcast from", name);
+            return ExitCode::FAILURE;
+        }
+        outputs.extend(found);
+    }
+
+    let mut sockets = Vec::new();
+    for endpoint in listen {
+        let socket = UdpSocket::bind(endpoint.addr).and_then(|socket| {
+            if let Some(interface) = &endpoint.interface {
+                platform::bind_to_device(&socket, interface)?;
+            }
+            Ok(socket)
+        });
+        match socket {
+            Ok(socket) => sockets.push(socket),
+            Err(err) => {
+                eprintln!("unable to listen on {}: {}", endpoint_name(endpoint), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let mut listener = None;
+    if let Some(tcp) = tcp {
+        match TcpListener::bind(tcp) {
+            Ok(bound) => listener = Some(bound),
+            Err(err) => {
+                eprintln!("unable to listen on {}: {}", tcp, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(inherited) = inherited_sockets() else {
+        return ExitCode::FAILURE;
+    };
+    let passed = inherited.len();
+    for socket in inherited {
+        match socket {
+            InheritedSocket::Udp(socket) => sockets.push(socket),
+            InheritedSocket::Tcp(inherited) if listener.is_none() => listener = Some(inherited),
+            InheritedSocket::Tcp(_) => {
+                eprintln!("relay accepts relay requests on a single stream socket only");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if sockets.is_empty() && listener.is_none() {
+        eprintln!(
+            "nothing to listen on: give --listen or --tcp, or start relay from a socket unit"
+        );
+        return ExitCode::FAILURE;
+    }
+    if tls.is_some() && listener.is_none() {
+        eprintln!("--tls-cert is for the requests of --relay, which need --tcp or a stream socket");
+        return ExitCode::FAILURE;
+    }
+    let tls = match tls {
+        Some((cert, key)) => match relay::server_config(cert, key) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                eprintln!("unable to load {}: {}", cert.display(), err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    for endpoint in listen {
+        eprintln!("listening on {}", endpoint_name(endpoint));
+    }
+    if let Some(tcp) = tcp {
+        let tls = if tls.is_some() { " over TLS" } else { "" };
+        eprintln!("listening on {} for relay requests{}", tcp, tls);
+    }
+    if passed > 0 {
+        eprintln!("listening on the {} sockets passed by systemd", passed);
+    }
+
+    let guard = &Guard::new(&guard.allow, guard.rate_limit);
+    // the relay needs no registry otherwise, so the names of the allowlist are looked up once
+    let config = if guard.needs_registry() {
+        match load_config(config_path) {
+            Some(config) => config,
+            None => return ExitCode::FAILURE,
+        }
+    } else {
+        Config::default()
+    };
+    let config = &config;
+    let outputs = &outputs;
+    let relay_wake = move |source: SocketAddr, mac: MacAddr, password: Option<MacAddr>| {
+        let time = history::format_time(history::now());
+        if let Err(refusal) = guard.check(source.ip(), mac, config) {
+            eprintln!("{} {} -> {} refused: {}", time, source, mac, refusal);
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, refusal));
+        }
+        let result = broadcast(outputs, emit_port, mac, password);
+        let names: Vec<_> = emit.iter().map(String::as_str).collect();
+        match &result {
+            Ok(()) => println!(
+                "{} {} -> {} relayed to {}",
+                time,
+                source,
+                mac,
+                names.join(", ")
+            ),
+            Err(err) => eprintln!("{} {} -> {} unable to relay: {}", time, source, mac, err),
+        }
+        result.map_err(io::Error::other)
+    };
+    let failed = thread::scope(|scope| {
+        let mut relays = Vec::new();
+        for socket in sockets {
+            relays.push(scope.spawn(move || relay_packets(&socket, outputs, relay_wake)));
+        }
+        if let Some(listener) = listener {
+            relays.push(scope.spawn(move || {
+                let result = relay::serve(&listener, tls, relay_wake);
+                if let Err(err) = &result {
+                    eprintln!("unable to accept relay requests: {}", err);
+                }
+                result
+            }));
+        }
+        relays
+            .into_iter()
+            .any(|relay| !matches!(relay.join(), Ok(Ok(()))))
+    });
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn endpoint_name(endpoint: &relay::Endpoint) -> String {
+    match &endpoint.interface {
+        Some(interface) => format!("{} on {}", endpoint.addr, interface),
+        None => endpoint.addr.to_string(),
+    }
+}
+
+/// Broadcasts a magic packet on each of the given interfaces.
+fn broadcast(
+    interfaces: &[&platform::Interface],
+    port: u16,
+    mac: MacAddr,
+    password: Option<MacAddr>,
+) -> Result<(), wol::WolError> {
+    let mut packet = wol::MagicPacket::builder(mac).port(port);
+    if let Some(password) = password {
+        packet = packet.password(password.octets());
+    }
+    for interface in interfaces {
+        // as with --all-interfaces, the interface's own address picks the way out
+        packet
+            .clone()
+            .destination(interface.broadcast().into())
+            .bind(interface.addr.into())
+            .send()?;
+    }
+    Ok(())
+}
+
+/// Relays the magic packets received on a socket, leaving out those broadcast by the relay
+/// itself.
+fn relay_packets(
+    socket: &UdpSocket,
+    outputs: &[&platform::Interface],
+    relay_wake: impl Fn(SocketAddr, MacAddr, Option<MacAddr>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut buf = [0; 1500];
+    loop {
+        let (len, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                eprintln!("unable to receive magic packets: {}", err);
+                return Err(err);
+            }
+        };
+        if outputs
+            .iter()
+            .any(|output| IpAddr::from(output.addr) == source.ip())
+        {
+            continue;
+        }
+        let Ok(packet) = wol::MagicPacket::parse(&buf[..len]) else {
+            continue;
+        };
+        // the failure is reported, and the next packet may well be relayed
+        let _ = relay_wake(source, packet.mac(), packet.password().map(MacAddr));
+    }
+}
@@ -0,0 +1,88 @@
+//! `schedule`: wakes the hosts of the registry at the times of their schedules.
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::SendArgs;
+use crate::config::Host;
+use crate::{duration, history, schedule};
+
+use super::load_config;
+use super::wake::wake_all;
+
+/// How long to sleep at most before looking at the clock again, which keeps scheduled wakes on
+/// time even when the system was suspended meanwhile.
+const CLOCK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sleeps until the given Unix timestamp.
+pub fn sleep_until(timestamp: u64) {
+    let now = history::now();
+    eprintln!(
+        "waking at {}, in {}",
+        history::format_time(timestamp),
+        duration::format(Duration::from_secs(timestamp.saturating_sub(now)))
+    );
+    loop {
+        let remaining = timestamp.saturating_sub(history::now());
+        if remaining == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_secs(remaining).min(CLOCK_INTERVAL));
+    }
+}
+
+/// How many minutes missed, e.g. while waiting for hosts to come up, are caught up on.
+const MAX_CATCH_UP: u64 = 10;
+
+/// Wakes the hosts of the registry on their schedules until interrupted.
+pub fn run_schedule(send: &SendArgs, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let scheduled: Vec<&str> = config
+        .hosts
+        .iter()
+        .filter(|host| !host.schedule.is_empty())
+        .map(|host| host.name.as_str())
+        .collect();
+    if scheduled.is_empty() {
+        eprintln!("no hosts have a schedule yet");
+    } else {
+        eprintln!("scheduled hosts: {}", scheduled.join(", "));
+    }
+
+    let mut last = history::now() / 60;
+    loop {
+        let now = history::now();
+        if now / 60 == last {
+            thread::sleep(Duration::from_secs(60 - now % 60).min(CLOCK_INTERVAL));
+            continue;
+        }
+        let minutes = (last + 1).max(now / 60 - MAX_CATCH_UP)..=now / 60;
+        last = now / 60;
+        // the registry is read every minute, so that changes to it take effect right away
+        let Some(config) = load_config(config_path) else {
+            continue;
+        };
+        for minute in minutes {
+            let time = schedule::Time::local(minute * 60);
+            let hosts: Vec<&Host> = config
+                .hosts
+                .iter()
+                .filter(|host| host.schedule.iter().any(|schedule| schedule.matches(&time)))
+                .collect();
+            if hosts.is_empty() {
+                continue;
+            }
+            let names: Vec<&str> = hosts.iter().map(|host| host.name.as_str()).collect();
+            eprintln!(
+                "{} waking {} on schedule",
+                history::format_time(minute * 60),
+                names.join(", ")
+            );
+            let targets = hosts.into_iter().map(Host::to_target).collect();
+            wake_all(targets, send, &config);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! `serve`: the HTTP API and web UI waking and listing the hosts of the registry, along with the
+//! gRPC service of --grpc.
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "grpc")]
+use std::thread;
+
+use crate::cli::{GuardArgs, SendArgs};
+use crate::config::{Config, Host};
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::guard::{Guard, Refusal};
+use crate::metrics::Metrics;
+use crate::platform::InheritedSocket;
+use crate::{activation, history, http, json, metrics};
+
+use super::hosts::{hosts_json, probe_hosts, STATUS_TIMEOUT};
+use super::load_history;
+use super::wake::{wake_all, Outcome};
+
+/// The page served at /, listing the hosts of the registry with a button to wake each.
+const UI: &str = include_str!("../ui.html");
+
+/// Takes the sockets passed by systemd socket activation, if any.
+pub fn inherited_sockets() -> Option<Vec<InheritedSocket>> {
+    match activation::sockets() {
+        Ok(sockets) => Some(sockets),
+        Err(err) => {
+            eprintln!("unable to take the sockets passed by systemd: {}", err);
+            None
+        }
+    }
+}
+
+/// Answers HTTP requests to list and wake the hosts of the registry until interrupted.
+pub fn serve(
+    listen: SocketAddr,
+    grpc: Option<SocketAddr>,
+    guard: &GuardArgs,
+    send: &SendArgs,
+    config_path: &Path,
+) -> ExitCode {
+    let Some(mut inherited) = inherited_sockets() else {
+        return ExitCode::FAILURE;
+    };
+    let listener = match (inherited.pop(), inherited.is_empty()) {
+        (None, _) => match TcpListener::bind(listen) {
+            Ok(listener) => {
+                eprintln!("listening on http://{}", listen);
+                listener
+            }
+            Err(err) => {
+                eprintln!("unable to listen on {}: {}", listen, err);
+                return ExitCode::FAILURE;
+            }
+        },
+        (Some(InheritedSocket::Tcp(listener)), true) => {
+            eprintln!("listening on the socket passed by systemd");
+            listener
+        }
+        _ => {
+            eprintln!("serve requires a single stream socket from systemd");
+            return ExitCode::FAILURE;
+        }
+    };
+    let grpc = match grpc.map(|addr| (addr, TcpListener::bind(addr))) {
+        None => None,
+        Some((_, Ok(grpc))) => Some(grpc),
+        Some((addr, Err(err))) => {
+            eprintln!("unable to listen on {}: {}", addr, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let local = listener.local_addr().map_or(listen.ip(), |addr| addr.ip());
+    let open = Config::load(config_path).is_ok_and(|config| config.serve.tokens.is_empty());
+    let exposed = grpc
+        .iter()
+        .filter_map(|grpc| grpc.local_addr().ok())
+        .any(|addr| !addr.ip().is_loopback());
+    if open && (exposed || !local.is_loopback()) {
+        eprintln!("anyone who can connect can wake the hosts, unless tokens are set in [serve]");
+    }
+    let served = Arc::new(Served {
+        guard: Guard::new(&guard.allow, guard.rate_limit),
+        send: send.clone(),
+        config_path: config_path.to_path_buf(),
+        waking: Mutex::new(()),
+        metrics: Mutex::new(Metrics::default()),
+    });
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = grpc {
+        if let Ok(addr) = grpc.local_addr() {
+            eprintln!("answering gRPC calls on {}", addr);
+        }
+        let served = served.clone();
+        thread::spawn(move || {
+            if let Err(err) = grpc::serve(grpc, served) {
+                eprintln!("unable to accept gRPC connections: {}", err);
+            }
+            process::exit(1);
+        });
+    }
+    let result = http::serve(&listener, |request| handle_request(request, &served));
+    if let Err(err) = result {
+        eprintln!("unable to accept connections: {}", err);
+    }
+    // returning would leave the gRPC server behind, which never ends by itself
+    process::exit(1);
+}
+
+/// What the HTTP and gRPC servers of `serve` share.
+pub struct Served {
+    pub guard: Guard,
+    pub send: SendArgs,
+    pub config_path: PathBuf,
+
+    /// Held while waking a host, as hooks and the wake history are not made for hosts being
+    /// woken concurrently.
+    pub waking: Mutex<()>,
+
+    pub metrics: Mutex<Metrics>,
+}
+
+/// Decides whether a client of `serve` may have a host woken, logging the refusal if not.
+pub fn check_served(
+    guard: &Guard,
+    client: Option<IpAddr>,
+    host: &Host,
+    config: &Config,
+) -> Result<(), Refusal> {
+    // clients are always known, but were one not, all such would share a limit
+    let client = client.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+    let result = guard.check(client, host.mac, config);
+    if let Err(refusal) = &result {
+        let time = history::format_time(history::now());
+        eprintln!("{} {} -> {} refused: {}", time, client, host.name, refusal);
+    }
+    result
+}
+
+/// Wakes a host for `serve`, one at a time, counting the wake in the metrics.
+pub fn wake_served(
+    host: &Host,
+    send: &SendArgs,
+    config: &Config,
+    waking: &Mutex<()>,
+    metrics: &Mutex<Metrics>,
+) -> Outcome {
+    let results = {
+        let _waking = waking
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        wake_all(vec![host.to_target()], send, config)
+    };
+    let outcome = results
+        .first()
+        .map_or(Outcome::Failed, |(_, outcome)| *outcome);
+    metrics
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .count_wake(&host.name, outcome.name());
+    outcome
+}
+
+fn handle_request(request: &http::Request, served: &Served) -> http::Response {
+    let Served {
+        guard,
+        send,
+        config_path,
+        waking,
+        metrics,
+    } = served;
+    let segments: Vec<&str> = request.segments.iter().map(String::as_str).collect();
+    // the registry is read for every request, so that changes to it take effect right away
+    let config = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", []) => return http::Response::html(UI),
+        ("GET", ["hosts" | "status" | "metrics"]) | ("POST", ["wake", _]) => {
+            match Config::load(config_path) {
+                Ok(config) => config,
+                Err(err) => {
+                    let message = format!("unable to load {}: {}", config_path.display(), err);
+                    return http::Response::error(500, &message);
+                }
+            }
+        }
+        (_, [] | ["hosts" | "status" | "metrics"] | ["wake", _]) => {
+            return http::Response::error(405, "method not allowed")
+        }
+        _ => return http::Response::error(404, "not found"),
+    };
+    if !config.serve.tokens.is_empty() && !request.is_authorized(&config.serve.tokens) {
+        return http::Response::error(401, "a valid bearer token is required");
+    }
+
+    let name = match segments.as_slice() {
+        ["wake", name] => name,
+        ["status"] => {
+            let hosts: Vec<&Host> = config.hosts.iter().collect();
+            let probed = probe_hosts(&hosts, send.wait_port, send.probe, STATUS_TIMEOUT);
+            let statuses = probed
+                .into_iter()
+                .map(|(host, ip, up, _)| {
+                    let status = match up {
+                        Some(Ok(true)) => "up",
+                        Some(Ok(false)) => "down",
+                        _ => "unknown",
+                    };
+                    json::Value::object([
+                        ("name", host.name.as_str().into()),
+                        ("ip", ip.map(|ip| ip.to_string()).as_deref().into()),
+                        ("status", status.into()),
+                    ])
+                })
+                .collect();
+            return http::Response::json(200, &json::Value::Array(statuses));
+        }
+        ["metrics"] => {
+            let hosts: Vec<&Host> = config.hosts.iter().collect();
+            let probed = probe_hosts(&hosts, send.wait_port, send.probe, STATUS_TIMEOUT);
+            let probes: Vec<metrics::ProbeResult> = probed
+                .iter()
+                .map(|(host, _, up, duration)| metrics::ProbeResult {
+                    host: &host.name,
+                    up: up.as_ref().and_then(|up| up.as_ref().ok()).copied(),
+                    duration: *duration,
+                })
+                .collect();
+            let metrics = metrics
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            return http::Response::text(&metrics.render(&probes));
+        }
+        _ => return http::Response::json(200, &hosts_json(&config, &load_history())),
+    };
+    let Some(host) = config.host(name) else {
+        return http::Response::error(404, &format!("no host named '{}'", name));
+    };
+    match check_served(guard, request.client, host, &config) {
+        Err(refusal @ Refusal::NotAllowed) => {
+            return http::Response::error(403, &refusal.to_string())
+        }
+        Err(refusal @ Refusal::RateLimited) => {
+            return http::Response::error(429, &refusal.to_string())
+        }
+        Ok(()) => {}
+    }
+    let outcome = wake_served(host, send, &config, waking, metrics);
+    let status = if outcome.is_woken() { 200 } else { 500 };
+    let body = json::Value::object([
+        ("host", host.name.as_str().into()),
+        ("mac", host.mac.to_string().into()),
+        ("result", outcome.name().into()),
+    ]);
+    http::Response::json(status, &body)
+}
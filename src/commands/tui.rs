@@ -0,0 +1,177 @@
+//! `tui`: the host picker, a table of the hosts of the registry to wake from.
+use std::io::{self, IsTerminal};
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::cli::SendArgs;
+use crate::{cli, tui};
+
+use super::hosts::{probe_hosts, STATUS_TIMEOUT};
+use super::wake::{wake_all, Outcome};
+use super::{load_config, select_hosts};
+
+/// What the host picker of `tui` waits for.
+enum PickerEvent {
+    Key(tui::Key),
+
+    /// The terminal was resized, and the screen needs to be drawn again.
+    Resize,
+
+    /// The IP address and status of each host, from a round of probing them.
+    Probed(Vec<(Option<IpAddr>, tui::Status)>),
+
+    /// A host was woken, or failed to be, with the last error of waking it.
+    Woken(String, Outcome, Option<String>),
+}
+
+/// Shows the hosts of the registry on a full screen with whether each is up, probing them every
+/// `refresh`, and wakes the one selected when Enter is pressed.
+pub fn pick_hosts(
+    names: &[String],
+    refresh: Duration,
+    send: &SendArgs,
+    config_path: &Path,
+) -> ExitCode {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        eprintln!("tui needs a terminal, use status and wake the hosts by name instead");
+        return ExitCode::FAILURE;
+    }
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let Some(hosts) = select_hosts(&config, names) else {
+        return ExitCode::FAILURE;
+    };
+    // what is printed of sending would only garble the screen, and the status column shows
+    // the hosts coming up. Errors are shown under the table instead, see report
+    let mut send = send.clone();
+    send.quiet = true;
+    send.output = cli::Output::Text;
+    send.format = None;
+    send.wait = false;
+    send.until_awake = false;
+    send.then = None;
+
+    let mut terminal = match tui::Terminal::enter() {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            eprintln!("unable to set up the terminal: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let (events, received) = mpsc::channel();
+    let (probe_now, probe_requests) = mpsc::channel::<()>();
+    tui::read_events(events.clone(), PickerEvent::Key, || PickerEvent::Resize);
+
+    let failed = thread::scope(|scope| {
+        let (hosts, send, config) = (&hosts, &send, &config);
+        let probed = events.clone();
+        scope.spawn(move || loop {
+            let states = probe_hosts(hosts, send.wait_port, send.probe, STATUS_TIMEOUT)
+                .into_iter()
+                .map(|(_, ip, up, _)| {
+                    let status = match up {
+                        Some(Ok(true)) => tui::Status::Up,
+                        Some(Ok(false)) => tui::Status::Down,
+                        Some(Err(err)) => tui::Status::Unknown(err.to_string()),
+                        None => tui::Status::Unknown("no ip".to_string()),
+                    };
+                    (ip, status)
+                })
+                .collect();
+            if probed.send(PickerEvent::Probed(states)).is_err() {
+                return;
+            }
+            // another round once refresh has passed, or right away when asked for
+            if let Err(mpsc::RecvTimeoutError::Disconnected) = probe_requests.recv_timeout(refresh)
+            {
+                return;
+            }
+        });
+
+        let mut states: Vec<(Option<IpAddr>, tui::Status)> = hosts
+            .iter()
+            .map(|host| (host.ip, tui::Status::Probing))
+            .collect();
+        let mut selected = 0;
+        let mut message = tui::Message::None;
+        let mut failed = None;
+        loop {
+            let rows: Vec<tui::Host> = hosts
+                .iter()
+                .zip(&states)
+                .map(|(host, (ip, status))| tui::Host {
+                    cells: [
+                        host.name.clone(),
+                        host.mac.to_string(),
+                        ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                    ],
+                    status: status.clone(),
+                })
+                .collect();
+            if let Err(err) = terminal.draw(&rows, selected, &message) {
+                failed = Some(err);
+                break;
+            }
+
+            let Ok(event) = received.recv() else {
+                break;
+            };
+            match event {
+                PickerEvent::Key(tui::Key::Up) => selected = selected.saturating_sub(1),
+                PickerEvent::Key(tui::Key::Down) => {
+                    selected = (selected + 1).min(hosts.len() - 1);
+                }
+                PickerEvent::Key(tui::Key::Home) => selected = 0,
+                PickerEvent::Key(tui::Key::End) => selected = hosts.len() - 1,
+                PickerEvent::Key(tui::Key::Refresh) => {
+                    let _ = probe_now.send(());
+                }
+                PickerEvent::Key(tui::Key::Quit) => break,
+                PickerEvent::Key(tui::Key::Enter) => {
+                    let host = hosts[selected];
+                    message = tui::Message::Waking(format!("waking {}", host.name));
+                    let woken = events.clone();
+                    scope.spawn(move || {
+                        let errors = Arc::new(Mutex::new(Vec::new()));
+                        let send = SendArgs {
+                            errors: Some(errors.clone()),
+                            ..send.clone()
+                        };
+                        let outcome = wake_all(vec![host.to_target()], &send, config)
+                            .first()
+                            .map_or(Outcome::Failed, |(_, outcome)| *outcome);
+                        let error = errors
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .pop();
+                        let _ = woken.send(PickerEvent::Woken(host.name.clone(), outcome, error));
+                    });
+                }
+                PickerEvent::Resize => {}
+                PickerEvent::Probed(probed) => states = probed,
+                PickerEvent::Woken(name, outcome, error) => {
+                    message = if outcome.is_woken() {
+                        tui::Message::Woken(format!("woke {}", name))
+                    } else {
+                        let reason = error.unwrap_or_else(|| outcome.name().to_string());
+                        tui::Message::Failed(format!("unable to wake {}: {}", name, reason))
+                    };
+                }
+            }
+        }
+        // stops probing
+        drop(probe_now);
+        failed
+    });
+    drop(terminal);
+    if let Some(err) = failed {
+        eprintln!("unable to draw on the terminal: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,1008 @@
+//! Waking targets, as without any subcommand and by `group` and `all`: resolving them, sending
+//! their packets, waiting for them and running their hooks.
+use std::io::{self, IsTerminal};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::process::{self, ExitCode};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::error::ErrorKind;
+use clap::CommandFactory;
+use wol_cli::wol;
+
+use crate::cli::{Args, Probe, SendArgs};
+use crate::config::{Config, Hook};
+use crate::targets::Target;
+use crate::{
+    color, config, duration, ethers, hexdump, history, json, metrics, neighbors, notify, oui,
+    platform, probe, progress, relay, table, targets, template,
+};
+
+use super::schedule::sleep_until;
+use super::{load_config, prompt, prompt_parsed};
+
+/// Exit status when a target is not a valid MAC address or could not be resolved.
+const EXIT_INVALID: u8 = 3;
+
+/// Exit status when sending the packets failed.
+const EXIT_SEND_FAILED: u8 = 4;
+
+/// Exit status when only some of the targets were woken.
+const EXIT_PARTIAL: u8 = 5;
+
+/// Exit status when waiting for a host timed out, as for timeout(1).
+const EXIT_TIMEOUT: u8 = 124;
+
+/// What became of a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The target was woken, and has come up when waiting for it.
+    Woken,
+
+    /// The target is not a valid MAC address, could not be resolved, or has an invalid password.
+    Invalid,
+
+    /// Sending a packet to the target failed.
+    SendFailed,
+
+    /// The target was woken, but probing it or running the --then command failed.
+    Failed,
+
+    /// The target did not come up before the --timeout elapsed.
+    TimedOut,
+}
+
+impl Outcome {
+    pub fn is_woken(self) -> bool {
+        self == Outcome::Woken
+    }
+
+    /// The outcome as reported to webhooks.
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Woken => "woken",
+            Outcome::Invalid => "invalid",
+            Outcome::SendFailed => "send-failed",
+            Outcome::Failed => "failed",
+            Outcome::TimedOut => "timed-out",
+        }
+    }
+}
+
+/// Where a target's packets are sent.
+struct Destination {
+    /// How it is printed, e.g. "192.168.1.255:9 via eth0".
+    name: String,
+
+    /// The address, relay or interface the packets go to, as in --output json.
+    addr: String,
+
+    /// The UDP port, unless they are sent as raw ethernet or through a relay.
+    port: Option<u16>,
+}
+
+impl Destination {
+    /// A UDP destination, optionally reached through a given interface.
+    fn udp(addr: IpAddr, port: u16, via: Option<&str>) -> Destination {
+        let socket_addr = SocketAddr::new(addr, port);
+        Destination {
+            name: match via {
+                Some(interface) => format!("{} via {}", socket_addr, interface),
+                None => socket_addr.to_string(),
+            },
+            addr: addr.to_string(),
+            port: Some(port),
+        }
+    }
+}
+
+/// Calls `send` as many times as requested for the target and prints the outcome for the
+/// destination, returning whether every send succeeded.
+fn send_repeated(
+    args: &SendArgs,
+    target: &Target,
+    destination: &Destination,
+    mut send: impl FnMut() -> Result<(), wol::WolError>,
+) -> bool {
+    let start = Instant::now();
+    let count = target.count.or(args.count).unwrap_or(1);
+    let mut sent = 0;
+    let mut error = None;
+    for i in 0..count {
+        if args.dry_run {
+            break;
+        }
+        if i > 0 {
+            thread::sleep(Duration::from_millis(args.interval.unwrap_or(100)));
+        }
+        let number = format!("packet {}/{} to {}", i + 1, count, destination.name);
+        match send() {
+            Ok(()) => {
+                tracing::debug!("{} sent", number);
+                sent += 1;
+                metrics::count_packet();
+            }
+            Err(err) => {
+                tracing::debug!("{} failed", number);
+                if !args.records() {
+                    report(args, &err);
+                }
+                error = Some(err.to_string());
+            }
+        }
+    }
+
+    if args.records() {
+        let result = if sent == count { "ok" } else { "failed" };
+        print_record(
+            args,
+            target,
+            Some(destination),
+            result,
+            error.as_deref(),
+            start,
+        );
+        return sent == count;
+    }
+    if args.quiet || progress::active() {
+        return sent == count;
+    }
+
+    let alias = match &target.alias {
+        Some(alias) => format!(" ({})", alias),
+        None => String::new(),
+    };
+    let mac = match target.mac.and_then(oui::vendor) {
+        Some(vendor) if args.verbose > 0 => format!("{} [{}]", target.name(), vendor),
+        _ => target.name(),
+    };
+    if args.dry_run {
+        let packets = if count == 1 { "packet" } else { "packets" };
+        println!(
+            "would send {} {} to {} with MAC {}{}",
+            count, packets, destination.name, mac, alias
+        );
+        return true;
+    }
+    if count == 1 && sent == 1 {
+        println!(
+            "{} to {} with MAC {}{}",
+            color::green("packet sent"),
+            destination.name,
+            mac,
+            alias
+        );
+    } else {
+        let packets = format!("{}/{} packets sent", sent, count);
+        let packets = match sent {
+            _ if sent == count => color::green(&packets),
+            0 => color::red(&packets),
+            _ => color::yellow(&packets),
+        };
+        println!(
+            "{} to {} with MAC {}{}",
+            packets, destination.name, mac, alias
+        );
+    }
+
+    sent == count
+}
+
+/// Prints an error of waking a target to standard error, or keeps it for the host picker to show
+/// when it is on the screen.
+fn report(args: &SendArgs, error: impl std::fmt::Display) {
+    match &args.errors {
+        Some(errors) => errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(error.to_string()),
+        None => eprintln!("{}", error),
+    }
+}
+
+/// Prints the record of sending a target's packets to a destination, or of not sending them
+/// anywhere, as JSON or in the template of --format.
+fn print_record(
+    args: &SendArgs,
+    target: &Target,
+    destination: Option<&Destination>,
+    result: &str,
+    error: Option<&str>,
+    start: Instant,
+) {
+    let port = destination.and_then(|destination| destination.port);
+    let addr = destination.map(|destination| destination.addr.as_str());
+    // targets that could not be resolved are still named as given
+    let mac = target.mac.map(|mac| mac.to_string());
+    let (mac, host) = (mac.as_deref(), target.alias.as_deref());
+    let duration = start.elapsed().as_millis();
+
+    if let Some(template) = &args.format {
+        let line = template.render(|field| match field {
+            template::Field::Alias => host.unwrap_or_default().to_string(),
+            template::Field::Mac => mac.unwrap_or_default().to_string(),
+            template::Field::Destination => addr.unwrap_or_default().to_string(),
+            template::Field::Port => port.map(|port| port.to_string()).unwrap_or_default(),
+            template::Field::Status => result.to_string(),
+            template::Field::Error => error.unwrap_or_default().to_string(),
+            template::Field::Duration => duration.to_string(),
+        });
+        println!("{}", line);
+        return;
+    }
+    let record = json::Value::object([
+        ("host", host.into()),
+        ("mac", mac.into()),
+        ("destination", addr.into()),
+        (
+            "port",
+            port.map(|port| json::Value::Number(port.into())).into(),
+        ),
+        ("result", result.into()),
+        ("error", error.into()),
+        ("duration_ms", json::Value::Number(duration as i64)),
+    ]);
+    println!("{}", record);
+}
+
+/// Sends the magic packet for a single target to every requested destination.
+pub fn wake(target: &Target, args: &SendArgs, ports: &[u16]) -> Outcome {
+    let _span = tracing::debug_span!("wake", target = %target).entered();
+    let start = Instant::now();
+    let invalid = |error: String| {
+        if args.records() {
+            print_record(args, target, None, "invalid", Some(&error), start);
+        } else {
+            report(args, &error);
+        }
+        Outcome::Invalid
+    };
+    let Some(mac) = target.mac else {
+        return invalid(format!("unable to create magic packet for {}", target));
+    };
+    let mut packet = wol::MagicPacket::new(mac);
+    if let Some(password) = target.password.as_ref().or(args.password.as_ref()) {
+        packet = match packet.with_password(password) {
+            Ok(packet) => packet,
+            Err(err) => return invalid(format!("invalid SecureOn password: {}", err)),
+        };
+    }
+    if args.dry_run {
+        let size = packet.as_bytes().len();
+        println!("packet for {}, {} bytes:", target, size);
+        print!("{}", hexdump::format(packet.as_bytes()));
+    }
+
+    match send(&packet, target, args, ports) {
+        true => Outcome::Woken,
+        false => Outcome::SendFailed,
+    }
+}
+
+/// Sends a target's magic packet to every requested destination, returning whether all of them
+/// succeeded.
+fn send(packet: &wol::MagicPacket, target: &Target, args: &SendArgs, ports: &[u16]) -> bool {
+    let mac = packet.mac();
+    let interface = target.interface.as_ref().or(args.interface.as_ref());
+    let password = target.password.as_ref().or(args.password.as_ref());
+    let ports = match target.port {
+        Some(port) => &[port][..],
+        None => ports,
+    };
+
+    if let Some(relay) = &args.relay {
+        let password = password.map(String::as_str);
+        let destination = Destination {
+            name: format!("relay {}", relay),
+            addr: relay.clone(),
+            port: None,
+        };
+        return send_repeated(args, target, &destination, || {
+            tracing::debug!("asking relay {} to wake {}", relay, mac);
+            relay::request_wake(relay, mac, password, args.relay_ca.as_deref())
+        });
+    }
+
+    if args.raw {
+        let interface = interface.map(String::as_str).unwrap_or_default();
+        let destination = Destination {
+            name: match args.vlan {
+                Some(vlan) => format!("{} as raw ethernet on VLAN {}", interface, vlan),
+                None => format!("{} as raw ethernet", interface),
+            },
+            addr: interface.to_string(),
+            port: None,
+        };
+        return send_repeated(args, target, &destination, || {
+            packet.send_raw(interface, args.vlan)
+        });
+    }
+
+    let mut ok = true;
+    if args.all_interfaces && target.addr.is_none() && interface.is_none() {
+        let interfaces = match platform::interfaces() {
+            Ok(interfaces) => interfaces,
+            Err(err) => {
+                report(args, format!("unable to list interfaces: {}", err));
+                return false;
+            }
+        };
+        if interfaces.is_empty() {
+            report(args, "no active broadcast-capable interfaces found");
+            return false;
+        }
+
+        for interface in interfaces {
+            tracing::debug!(
+                "interface {} has {} with netmask {}",
+                interface.name,
+                interface.addr,
+                interface.netmask
+            );
+            // binding to the interface's own address makes the directed broadcast leave through
+            // that interface without requiring the privileges of SO_BINDTODEVICE
+            let options = wol::SendOptions {
+                bind: Some(interface.addr.into()),
+                source_port: args.source_port,
+                ttl: args.ttl,
+                dscp: args.dscp,
+                ..Default::default()
+            };
+            let addr = interface.broadcast().into();
+            for &port in ports {
+                let destination = Destination::udp(addr, port, Some(&interface.name));
+                ok &= send_repeated(args, target, &destination, || {
+                    packet.send_to(addr, port, &options)
+                });
+            }
+        }
+        return ok;
+    }
+
+    let options = wol::SendOptions {
+        interface: interface.cloned(),
+        bind: args.bind,
+        source_port: args.source_port,
+        unicast: target.addr.is_none() && args.ip.is_some(),
+        ttl: args.ttl,
+        dscp: args.dscp,
+    };
+    let addrs = match (target.addr.or(args.ip), args.subnet) {
+        (Some(ip), _) => vec![ip],
+        (None, Some(subnet)) => vec![subnet.broadcast().into()],
+        (None, None) if !args.addr.is_empty() => args.addr.clone(),
+        (None, None) if args.ipv6 => vec![wol::IPV6_ALL_NODES.into()],
+        (None, None) => vec![Ipv4Addr::BROADCAST.into()],
+    };
+    for &addr in &addrs {
+        for &port in ports {
+            let destination = Destination::udp(addr, port, interface.map(String::as_str));
+            ok &= send_repeated(args, target, &destination, || {
+                packet.send_to(addr, port, &options)
+            });
+        }
+    }
+
+    ok
+}
+
+/// Wakes every target, resolving those that are not MAC addresses as aliases from the registry, IP
+/// addresses from the neighbor table or hostnames from /etc/ethers, and prints a summary when
+/// there is more than one. Returns each target, resolved where possible, along with what became
+/// of it.
+pub fn wake_all(targets: Vec<Target>, args: &SendArgs, config: &Config) -> Vec<(Target, Outcome)> {
+    let start = Instant::now();
+    let deadline = start + args.timeout.unwrap_or(probe::WAIT_TIMEOUT);
+    let ports = if args.all_ports {
+        wol::COMMON_PORTS.to_vec()
+    } else if args.port.is_empty() {
+        vec![wol::DEFAULT_PORT]
+    } else {
+        args.port.clone()
+    };
+
+    let mut resolved = Vec::new();
+    let mut unresolved = Vec::new();
+    for target in targets {
+        let name = match (target.mac, &target.alias) {
+            (None, Some(alias)) => alias.clone(),
+            _ => {
+                resolved.push(target);
+                continue;
+            }
+        };
+        let hosts = match (config.host(&name), name.parse::<IpAddr>()) {
+            (Some(host), _) => vec![host],
+            (None, Ok(ip)) => {
+                match neighbors::discover(ip) {
+                    Ok(Some(mac)) => resolved.push(Target {
+                        mac: Some(mac),
+                        ip: Some(ip),
+                        ..target
+                    }),
+                    Ok(None) => {
+                        report(
+                            args,
+                            format!(
+                                "no MAC address known for {}, it must be awake once for its MAC address to be learned",
+                                target
+                            ),
+                        );
+                        unresolved.push(target);
+                    }
+                    Err(err) => {
+                        report(args, format!("unable to read the neighbor table: {}", err));
+                        unresolved.push(target);
+                    }
+                }
+                continue;
+            }
+            (None, _) if config::is_pattern(&name) => config.matching(&name),
+            (None, _) => {
+                match ethers::lookup(&name) {
+                    Ok(Some(mac)) => resolved.push(Target {
+                        mac: Some(mac),
+                        ..target
+                    }),
+                    Ok(None) => {
+                        report(
+                            args,
+                            format!(
+                                "{} is neither a configured host, in {}, nor a valid MAC address",
+                                target,
+                                ethers::PATH
+                            ),
+                        );
+                        unresolved.push(target);
+                    }
+                    Err(err) => {
+                        report(args, format!("unable to read {}: {}", ethers::PATH, err));
+                        unresolved.push(target);
+                    }
+                }
+                continue;
+            }
+        };
+        if hosts.is_empty() {
+            report(args, format!("no configured hosts match {}", target));
+            unresolved.push(target);
+            continue;
+        }
+
+        resolved.extend(hosts.into_iter().map(|host| Target {
+            origin: target.origin.clone(),
+            ..host.to_target()
+        }));
+    }
+
+    let mut results = Vec::new();
+    let shows_progress = !args.records() && !args.quiet && !args.dry_run && args.verbose == 0;
+    let mut progress = shows_progress
+        .then(|| progress::Progress::start(resolved.len()))
+        .flatten();
+    for target in resolved {
+        if let Some(progress) = &progress {
+            progress.clear();
+        }
+        let outcome = if args.dry_run {
+            wake(&target, args, &ports)
+        } else if !run_hook(config, Hook::PreSend, &target, args, None) {
+            Outcome::Failed
+        } else {
+            let outcome = wake(&target, args, &ports);
+            let result = if outcome.is_woken() { "ok" } else { "failed" };
+            run_hook(config, Hook::PostSend, &target, args, Some(result));
+            outcome
+        };
+        if let Some(progress) = &mut progress {
+            progress.advance(&target.name());
+        }
+        results.push((target, outcome));
+    }
+    let showed_progress = progress.take().is_some();
+    if args.records() {
+        for target in &unresolved {
+            print_record(args, target, None, "invalid", Some("unknown host"), start);
+        }
+    }
+    results.extend(
+        unresolved
+            .into_iter()
+            .map(|target| (target, Outcome::Invalid)),
+    );
+
+    let total = results.len();
+    if args.dry_run {
+        return results;
+    }
+    if showed_progress {
+        // the bar left out the packets, so at least what went wrong is listed
+        for (target, outcome) in &results {
+            if !outcome.is_woken() {
+                println!("{} {}", target, color::red(outcome.name()));
+            }
+        }
+    }
+    if total > 1 && !args.records() && !args.quiet {
+        let woken = results
+            .iter()
+            .filter(|(_, outcome)| outcome.is_woken())
+            .count();
+        let summary = format!("{}/{} targets woken", woken, total);
+        match woken {
+            _ if woken == total => println!("{}", color::green(&summary)),
+            0 => println!("{}", color::red(&summary)),
+            _ => println!("{}", color::yellow(&summary)),
+        }
+    }
+
+    let names: Vec<&str> = results
+        .iter()
+        .filter(|(_, outcome)| outcome.is_woken())
+        .filter_map(|(target, _)| target.alias.as_deref())
+        .filter(|alias| config.host(alias).is_some())
+        .collect();
+    match history::default_path() {
+        Some(path) if !names.is_empty() => {
+            if let Err(err) = history::record(&path, &names) {
+                report(args, format!("unable to record the wake history: {}", err));
+            }
+        }
+        _ => {}
+    }
+
+    if args.waiting() {
+        wait_all(&mut results, args, config, &ports, deadline);
+    }
+    if let Some(command) = &args.then {
+        for (target, outcome) in results.iter_mut() {
+            if outcome.is_woken() && !run_command(command, "command", target, args, &[]) {
+                *outcome = Outcome::Failed;
+            }
+        }
+    }
+    for (target, outcome) in &results {
+        notify(target, *outcome, args, config, start);
+    }
+    results
+}
+
+/// Posts the outcome of waking a target to the webhook from --notify-url or the registry, if any.
+fn notify(target: &Target, outcome: Outcome, args: &SendArgs, config: &Config, start: Instant) {
+    let Some(url) = args.notify_url.as_deref().or(config.notify_url(target)) else {
+        return;
+    };
+    let ip = target.ip.or(args.ip).map(|ip| ip.to_string());
+    let body = json::Value::object([
+        ("host", target.name().into()),
+        ("mac", target.mac.map(|mac| mac.to_string()).into()),
+        ("ip", ip.as_deref().into()),
+        ("result", outcome.name().into()),
+        (
+            "elapsed_ms",
+            json::Value::Number(start.elapsed().as_millis() as i64),
+        ),
+    ]);
+    if let Err(err) = notify::post(url, &body) {
+        report(
+            args,
+            format!("unable to notify {} for {}: {}", url, target, err),
+        );
+    }
+}
+
+/// Runs the registry's command for a hook, if there is one, returning whether it succeeded. Post-send
+/// hooks are told whether sending worked in WOL_RESULT.
+fn run_hook(
+    config: &Config,
+    hook: Hook,
+    target: &Target,
+    args: &SendArgs,
+    result: Option<&str>,
+) -> bool {
+    let Some(command) = config.hook(target, hook) else {
+        return true;
+    };
+    let mut env = vec![("WOL_EVENT", hook.key())];
+    env.extend(result.map(|result| ("WOL_RESULT", result)));
+    run_command(command, &format!("{} hook", hook.key()), target, args, &env)
+}
+
+/// Runs a shell command for a target, describing it in WOL_HOST, WOL_MAC and WOL_IP, and returns
+/// whether it succeeded. `what` names the command in error messages.
+fn run_command(
+    command: &str,
+    what: &str,
+    target: &Target,
+    args: &SendArgs,
+    env: &[(&str, &str)],
+) -> bool {
+    let mut shell = if cfg!(windows) {
+        let mut shell = process::Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    if args.errors.is_some() {
+        // the picker is on the screen, which the command's output would garble
+        shell
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null());
+    }
+    let ip = target.ip.or(args.ip).map(|ip| ip.to_string());
+    let mac = target.mac.map(|mac| mac.to_string());
+    let status = shell
+        .arg(command)
+        .env("WOL_HOST", target.name())
+        .env("WOL_MAC", mac.unwrap_or_default())
+        .env("WOL_IP", ip.unwrap_or_default())
+        .envs(env.iter().copied())
+        .status();
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            report(
+                args,
+                format!("{} for {} exited with {}", what, target, status),
+            );
+            false
+        }
+        Err(err) => {
+            report(
+                args,
+                format!("unable to run {} for {}: {}", what, target, err),
+            );
+            false
+        }
+    }
+}
+
+/// Checks once whether the host at `ip` is up, with a TCP connection when given a port.
+pub fn probe_host(
+    ip: IpAddr,
+    port: Option<u16>,
+    method: Probe,
+    timeout: Duration,
+) -> io::Result<bool> {
+    match (port, method) {
+        (Some(port), _) => probe::connect(SocketAddr::new(ip, port), timeout),
+        (None, Probe::Ping) => probe::ping(ip, timeout),
+        (None, Probe::Arp) => probe::arp(ip, timeout),
+    }
+}
+
+/// Probes the woken targets in parallel until they come up or the deadline passes, marking those
+/// that did not and running their on-awake or on-timeout hooks. With --until-awake, the packets
+/// are resent to targets that are not up yet.
+fn wait_all(
+    results: &mut [(Target, Outcome)],
+    args: &SendArgs,
+    config: &Config,
+    ports: &[u16],
+    deadline: Instant,
+) {
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    thread::scope(|scope| {
+        for (target, outcome) in results.iter_mut() {
+            if !outcome.is_woken() {
+                continue;
+            }
+            let Some(ip) = target.ip.or(args.ip) else {
+                let error = format!(
+                    "cannot wait for {}, its IP address is unknown: use --ip or set ip in the registry",
+                    target
+                );
+                if args.records() {
+                    print_record(args, target, None, "failed", Some(&error), Instant::now());
+                } else {
+                    eprintln!("{}", error);
+                }
+                *outcome = Outcome::Failed;
+                continue;
+            };
+            scope.spawn(move || {
+                // a record for the target as soon as it is up or given up on, after those of its
+                // destinations
+                let start = Instant::now();
+                let probed = Destination {
+                    name: ip.to_string(),
+                    addr: ip.to_string(),
+                    port: args.wait_port,
+                };
+                let record = |result: &str, error: Option<&str>| {
+                    if args.records() {
+                        print_record(args, target, Some(&probed), result, error, start);
+                    }
+                };
+                let probe = |timeout| probe_host(ip, args.wait_port, args.probe, timeout);
+                let up = if args.until_awake {
+                    let resend = || {
+                        wake(target, args, ports);
+                    };
+                    probe::wait_resending(timeout, probe::FIRST_BACKOFF, resend, probe)
+                } else {
+                    probe::wait(timeout, probe)
+                };
+                match up {
+                    Ok(Some(elapsed)) => {
+                        record("up", None);
+                        // standard output is left to the records of --output json and --format
+                        if args.records() {
+                            eprintln!("{} is up after {}s", target, elapsed.as_secs());
+                        } else if !args.quiet {
+                            println!("{} is up after {}s", target, elapsed.as_secs());
+                        }
+                        if !run_hook(config, Hook::OnAwake, target, args, None) {
+                            *outcome = Outcome::Failed;
+                        }
+                    }
+                    Ok(None) => {
+                        record("timed-out", None);
+                        eprintln!(
+                            "{} did not come up within {}",
+                            target,
+                            duration::format(args.timeout.unwrap_or(probe::WAIT_TIMEOUT))
+                        );
+                        *outcome = Outcome::TimedOut;
+                        run_hook(config, Hook::OnTimeout, target, args, None);
+                    }
+                    Err(err) => {
+                        record("failed", Some(&err.to_string()));
+                        eprintln!("unable to probe {}: {}", target, err);
+                        *outcome = Outcome::Failed;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// The exit status for the outcomes of waking targets: success only when every target was
+/// woken, and came up when waiting for it. Failing to send or resolve some of the targets while
+/// waking others is a partial failure, and targets that only timed out are told apart from other
+/// failures.
+fn exit_code(outcomes: impl IntoIterator<Item = Outcome>) -> ExitCode {
+    let (mut woken, mut invalid, mut send_failed, mut failed, mut timed_out) =
+        (false, false, false, false, false);
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Woken => woken = true,
+            Outcome::Invalid => invalid = true,
+            Outcome::SendFailed => send_failed = true,
+            Outcome::Failed => failed = true,
+            Outcome::TimedOut => timed_out = true,
+        }
+    }
+    // targets that failed or timed out while waiting were woken nonetheless
+    let woken = woken || failed || timed_out;
+
+    if (invalid || send_failed) && woken {
+        ExitCode::from(EXIT_PARTIAL)
+    } else if send_failed {
+        ExitCode::from(EXIT_SEND_FAILED)
+    } else if invalid {
+        ExitCode::from(EXIT_INVALID)
+    } else if failed {
+        ExitCode::FAILURE
+    } else if timed_out {
+        ExitCode::from(EXIT_TIMEOUT)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Asks for the host to wake, picked from the registry by number or name or given by MAC
+/// address, and optionally the port and broadcast address to send its packets to. Returns None
+/// once standard input ends.
+fn ask_target(config: &Config, args: &SendArgs) -> Option<Target> {
+    let question = if config.hosts.is_empty() {
+        "MAC address to wake"
+    } else {
+        let rows: Vec<Vec<String>> = (1..)
+            .zip(&config.hosts)
+            .map(|(i, host)| vec![i.to_string(), host.name.clone(), host.mac.to_string()])
+            .collect();
+        print!("{}", table::format(&["#", "HOST", "MAC"], &rows));
+        "Number or name of the host to wake, or a MAC address"
+    };
+    let mut target = loop {
+        let answer = prompt(question)?;
+        let numbered = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| config.hosts.get(i.checked_sub(1)?));
+        if let Some(host) = numbered.or_else(|| config.host(&answer)) {
+            break host.to_target();
+        }
+        if let Ok(mac) = answer.parse() {
+            break Target {
+                mac: Some(mac),
+                ..Default::default()
+            };
+        }
+        if !answer.is_empty() {
+            eprintln!(
+                "'{}' is neither a configured host nor a valid MAC address",
+                answer
+            );
+        }
+    };
+
+    // blank answers keep what the registry or the options would have used
+    let port = target
+        .port
+        .or(args.port.first().copied())
+        .unwrap_or(wol::DEFAULT_PORT);
+    if let Some(port) = prompt_parsed(&format!("UDP port [{}]", port))? {
+        target.port = Some(port);
+    }
+    let addr = target
+        .addr
+        .or(args.addr.first().copied())
+        .unwrap_or(Ipv4Addr::BROADCAST.into());
+    if let Some(addr) = prompt_parsed(&format!("Broadcast address [{}]", addr))? {
+        target.addr = Some(addr);
+    }
+    Some(target)
+}
+
+/// Wakes the targets given on the command line, on standard input and in files.
+pub fn wake_targets(args: &Args, config_path: &Path) -> ExitCode {
+    let no_targets = args.macs.is_empty()
+        && args.mac.is_empty()
+        && !args.stdin
+        && args.file.is_empty()
+        && args.csv.is_empty()
+        && args.host.is_empty();
+    if no_targets {
+        if !io::stdin().is_terminal() {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "no targets were given: pass MAC addresses or host aliases, or run it in a terminal to be asked for one",
+                )
+                .exit();
+        }
+        let Some(config) = load_config(config_path) else {
+            return ExitCode::FAILURE;
+        };
+        let Some(target) = ask_target(&config, &args.send) else {
+            println!();
+            return ExitCode::FAILURE;
+        };
+        let results = wake_all(vec![target], &args.send, &config);
+        return exit_code(results.iter().map(|(_, outcome)| *outcome));
+    }
+
+    let mut targets: Vec<Target> = args.mac.iter().map(|mac| Target::new(mac)).collect();
+    let mut read_stdin = args.stdin;
+    for mac in &args.macs {
+        if mac == "-" {
+            read_stdin = true;
+        } else {
+            targets.push(Target::new(mac));
+        }
+    }
+    if read_stdin {
+        match targets::read_lines(io::stdin().lock(), "<stdin>") {
+            Ok(lines) => targets.extend(lines),
+            Err(err) => {
+                eprintln!("unable to read standard input: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    for path in &args.file {
+        match targets::read_file(path) {
+            Ok(lines) => targets.extend(lines),
+            Err(err) => {
+                eprintln!("unable to read {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    for path in &args.csv {
+        match targets::read_csv_file(path) {
+            Ok(rows) => targets.extend(rows),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                eprintln!("unable to read {}: {}", path.display(), err);
+                return ExitCode::from(EXIT_INVALID);
+            }
+            Err(err) => {
+                eprintln!("unable to read {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    for host in &args.host {
+        match neighbors::resolve_host(host) {
+            Ok((ip, mac)) => targets.push(Target {
+                mac: Some(mac),
+                alias: Some(host.clone()),
+                ip: Some(ip),
+                ..Default::default()
+            }),
+            Err(err) => {
+                eprintln!("unable to resolve {}: {}", host, err);
+                return ExitCode::from(EXIT_INVALID);
+            }
+        }
+    }
+
+    // only read the registry when there are aliases to resolve
+    let config = if targets.iter().any(|target| target.mac.is_none()) {
+        match load_config(config_path) {
+            Some(config) => config,
+            None => return ExitCode::FAILURE,
+        }
+    } else {
+        Config::default()
+    };
+
+    if let Some(at) = args.at {
+        sleep_until(at.next(history::now()));
+    }
+    let results = wake_all(targets, &args.send, &config);
+    exit_code(results.iter().map(|(_, outcome)| *outcome))
+}
+
+/// Wakes the hosts of the given groups of the registry.
+pub fn wake_groups(groups: &[String], send: &SendArgs, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    let mut targets = Vec::new();
+    let mut missing = false;
+    for group in groups {
+        let hosts = config.group(group);
+        if hosts.is_empty() {
+            eprintln!("no hosts belong to group '{}'", group);
+            missing = true;
+        }
+        targets.extend(hosts.into_iter().map(|host| host.to_target()));
+    }
+    let results = wake_all(targets, send, &config);
+    // an empty group counts as a target that could not be resolved
+    let outcomes = results.iter().map(|(_, outcome)| *outcome);
+    exit_code(outcomes.chain(missing.then_some(Outcome::Invalid)))
+}
+
+/// Wakes every host of the registry, listing what became of each in a table.
+pub fn wake_registry(send: &SendArgs, config_path: &Path) -> ExitCode {
+    let Some(config) = load_config(config_path) else {
+        return ExitCode::FAILURE;
+    };
+    if config.hosts.is_empty() {
+        eprintln!("no hosts are configured");
+        return ExitCode::SUCCESS;
+    }
+    let targets = config.hosts.iter().map(|host| host.to_target()).collect();
+    let results = wake_all(targets, send, &config);
+    // the table would end up among the records, and is part of the summary -q leaves out
+    if send.records() || send.quiet {
+        return exit_code(results.iter().map(|(_, outcome)| *outcome));
+    }
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|(target, outcome)| {
+            let result = match outcome {
+                Outcome::Woken => color::green("ok"),
+                Outcome::Invalid => color::yellow("invalid"),
+                Outcome::SendFailed | Outcome::Failed => color::red("failed"),
+                Outcome::TimedOut => color::red("timed out"),
+            };
+            vec![
+                target.alias.clone().unwrap_or_default(),
+                target.mac.map(|mac| mac.to_string()).unwrap_or_default(),
+                result,
+            ]
+        })
+        .collect();
+    print!("\n{}", table::format(&["HOST", "MAC", "RESULT"], &rows));
+    exit_code(results.iter().map(|(_, outcome)| *outcome))
+}
@@ -0,0 +1,1150 @@
+//! The host registry, a TOML file mapping friendly names to MAC addresses and the options used
+//! to wake them:
+//!
+//! ```toml
+//! [hosts.nas]
+//! mac = "aa:bb:cc:dd:ee:ff"
+//! broadcast = "192.168.1.255"
+//! ip = "192.168.1.20"
+//! port = 7
+//! password = "01-02-03-04-05-06"
+//! interface = "eth1"
+//! count = 3
+//! groups = ["lab"]
+//! note = "rack 2"
+//! schedule = ["45 6 * * mon-fri"]
+//! on-awake = "mount /mnt/nas"
+//! ```
+//!
+//! Hooks that apply to every host are set in a `[hooks]` table, see [`Hook`], and the settings
+//! of `serve` in a `[serve]` table, see [`Serve`].
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use wol_cli::MacAddr;
+
+use crate::dirs;
+use crate::json;
+use crate::schedule::Schedule;
+use crate::targets::Target;
+use crate::toml::{self, Value};
+
+const FILE_NAME: &str = "hosts.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Host {
+    pub name: String,
+    pub mac: MacAddr,
+    pub broadcast: Option<IpAddr>,
+
+    /// Address the host answers on once it is awake, probed by `--wait`.
+    pub ip: Option<IpAddr>,
+
+    pub port: Option<u16>,
+    pub password: Option<String>,
+    pub interface: Option<String>,
+
+    /// Number of packets to send when waking the host.
+    pub count: Option<u32>,
+
+    pub groups: Vec<String>,
+
+    /// Free-form text describing the host, not used when waking it.
+    pub note: Option<String>,
+
+    /// When `schedule` wakes the host.
+    pub schedule: Vec<Schedule>,
+
+    /// Hooks run for this host instead of the global ones.
+    pub hooks: Hooks,
+}
+
+/// A point in waking a host at which a command can be run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Before the packets are sent. A failing command skips the host.
+    PreSend,
+
+    /// After the packets were sent, successfully or not.
+    PostSend,
+
+    /// When the host has come up, with `--wait`.
+    OnAwake,
+
+    /// When the host did not come up before the `--timeout`.
+    OnTimeout,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 4] = [
+        Hook::PreSend,
+        Hook::PostSend,
+        Hook::OnAwake,
+        Hook::OnTimeout,
+    ];
+
+    /// The key setting the hook in the registry.
+    pub fn key(self) -> &'static str {
+        match self {
+            Hook::PreSend => "pre-send",
+            Hook::PostSend => "post-send",
+            Hook::OnAwake => "on-awake",
+            Hook::OnTimeout => "on-timeout",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Hook> {
+        Hook::ALL.into_iter().find(|hook| hook.key() == key)
+    }
+}
+
+/// The shell commands to run at each [`Hook`], and the URL to post the results to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    commands: Vec<(Hook, String)>,
+
+    /// Webhook to POST a JSON document describing the outcome to, like `--notify-url`.
+    pub notify_url: Option<String>,
+}
+
+impl Hooks {
+    pub fn get(&self, hook: Hook) -> Option<&str> {
+        self.commands
+            .iter()
+            .find(|(other, _)| *other == hook)
+            .map(|(_, command)| command.as_str())
+    }
+
+    pub fn set(&mut self, hook: Hook, command: String) {
+        self.commands.retain(|(other, _)| *other != hook);
+        self.commands.push((hook, command));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty() && self.notify_url.is_none()
+    }
+
+    /// Reads a hook from a table entry, returning `None` when the key is not a hook.
+    fn read(&mut self, key: &str, value: &Value) -> Option<Result<(), String>> {
+        let hook = Hook::from_key(key);
+        if hook.is_none() && key != "notify-url" {
+            return None;
+        }
+        Some(match (hook, value) {
+            (Some(hook), Value::String(command)) => {
+                self.set(hook, command.clone());
+                Ok(())
+            }
+            (None, Value::String(url)) => {
+                self.notify_url = Some(url.clone());
+                Ok(())
+            }
+            _ => Err(format!("'{}' has the wrong type", key)),
+        })
+    }
+
+    /// Reads the global `[hooks]` table.
+    fn from_table(table: &toml::Table) -> Result<Hooks, ConfigError> {
+        let mut hooks = Hooks::default();
+        for (key, value) in &table.entries {
+            match hooks.read(key, value) {
+                Some(Ok(())) => {}
+                Some(Err(message)) => return invalid(table.line, format!("hooks: {}", message)),
+                None => return invalid(table.line, format!("hooks: unknown hook '{}'", key)),
+            }
+        }
+        Ok(hooks)
+    }
+
+    fn write(&self, table: &mut toml::Table) {
+        for hook in Hook::ALL {
+            if let Some(command) = self.get(hook) {
+                table.set(hook.key(), Value::String(command.to_string()));
+            }
+        }
+        if let Some(url) = &self.notify_url {
+            table.set("notify-url", Value::String(url.clone()));
+        }
+    }
+}
+
+/// The settings of `serve`:
+///
+/// ```toml
+/// [serve]
+/// tokens = ["s3cret", "another for the phone"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Serve {
+    /// Bearer tokens of which requests to the HTTP API have to carry one. Without any, anyone who
+    /// can connect may wake the hosts.
+    pub tokens: Vec<String>,
+}
+
+impl Serve {
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Reads the `[serve]` table.
+    fn from_table(table: &toml::Table) -> Result<Serve, ConfigError> {
+        let mut serve = Serve::default();
+        for (key, value) in &table.entries {
+            match (key.as_str(), value) {
+                ("tokens", Value::Array(tokens)) => {
+                    for token in tokens {
+                        match token {
+                            Value::String(token) if !token.is_empty() => {
+                                serve.tokens.push(token.clone())
+                            }
+                            _ => {
+                                return invalid(
+                                    table.line,
+                                    "serve: tokens must be non-empty strings".into(),
+                                )
+                            }
+                        }
+                    }
+                }
+                ("tokens", _) => {
+                    return invalid(table.line, "serve: 'tokens' has the wrong type".into())
+                }
+                _ => return invalid(table.line, format!("serve: unknown key '{}'", key)),
+            }
+        }
+        Ok(serve)
+    }
+
+    fn write(&self, table: &mut toml::Table) {
+        if !self.tokens.is_empty() {
+            let tokens = self.tokens.iter().cloned().map(Value::String).collect();
+            table.set("tokens", Value::Array(tokens));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub hosts: Vec<Host>,
+
+    /// Hooks run for every host that does not set its own.
+    pub hooks: Hooks,
+
+    pub serve: Serve,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// The file could not be read
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The file is not valid TOML
+    #[error(transparent)]
+    Syntax(#[from] toml::SyntaxError),
+
+    /// The file is valid TOML but does not describe a valid registry
+    #[error("line {line}: {message}")]
+    Invalid { line: usize, message: String },
+
+    /// An exported registry is not valid JSON or does not describe a valid registry
+    #[error("{0}")]
+    Json(String),
+}
+
+fn invalid<T>(line: usize, message: String) -> Result<T, ConfigError> {
+    Err(ConfigError::Invalid { line, message })
+}
+
+impl Host {
+    fn from_table(name: &str, table: &toml::Table) -> Result<Host, ConfigError> {
+        let line = table.line;
+        let mut host = Host {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut mac = None;
+
+        for (key, value) in &table.entries {
+            match host.hooks.read(key, value) {
+                Some(Ok(())) => continue,
+                Some(Err(message)) => {
+                    return invalid(line, format!("host '{}': {}", name, message))
+                }
+                None => {}
+            }
+            match (key.as_str(), value) {
+                ("mac", Value::String(value)) => match value.parse() {
+                    Ok(value) => mac = Some(value),
+                    Err(err) => {
+                        return invalid(line, format!("host '{}': invalid MAC: {}", name, err))
+                    }
+                },
+                ("broadcast", Value::String(addr)) => match addr.parse() {
+                    Ok(addr) => host.broadcast = Some(addr),
+                    Err(_) => {
+                        return invalid(
+                            line,
+                            format!("host '{}': invalid broadcast address '{}'", name, addr),
+                        )
+                    }
+                },
+                ("ip", Value::String(addr)) => match addr.parse() {
+                    Ok(addr) => host.ip = Some(addr),
+                    Err(_) => {
+                        return invalid(
+                            line,
+                            format!("host '{}': invalid IP address '{}'", name, addr),
+                        )
+                    }
+                },
+                ("port", Value::Integer(port)) => match u16::try_from(*port) {
+                    Ok(port) => host.port = Some(port),
+                    Err(_) => {
+                        return invalid(line, format!("host '{}': invalid port {}", name, port))
+                    }
+                },
+                ("password", Value::String(password)) => {
+                    if let Err(err) = password.parse::<MacAddr>() {
+                        return invalid(
+                            line,
+                            format!("host '{}': invalid SecureOn password: {}", name, err),
+                        );
+                    }
+                    host.password = Some(password.clone());
+                }
+                ("interface", Value::String(interface)) => host.interface = Some(interface.clone()),
+                ("count", Value::Integer(count)) => match u32::try_from(*count) {
+                    Ok(count) if count > 0 => host.count = Some(count),
+                    _ => return invalid(line, format!("host '{}': invalid count {}", name, count)),
+                },
+                ("groups", Value::Array(groups)) => {
+                    for group in groups {
+                        let Value::String(group) = group else {
+                            return invalid(
+                                line,
+                                format!("host '{}': group names must be strings", name),
+                            );
+                        };
+                        host.groups.push(group.clone());
+                    }
+                }
+                ("note", Value::String(note)) => host.note = Some(note.clone()),
+                ("schedule", Value::Array(schedules)) => {
+                    for schedule in schedules {
+                        let Value::String(schedule) = schedule else {
+                            return invalid(
+                                line,
+                                format!("host '{}': schedules must be strings", name),
+                            );
+                        };
+                        match schedule.parse() {
+                            Ok(schedule) => host.schedule.push(schedule),
+                            Err(err) => {
+                                return invalid(
+                                    line,
+                                    format!("host '{}': schedule '{}': {}", name, schedule, err),
+                                )
+                            }
+                        }
+                    }
+                }
+                (
+                    "mac" | "broadcast" | "ip" | "port" | "password" | "interface" | "count"
+                    | "groups" | "note" | "schedule",
+                    _,
+                ) => {
+                    return invalid(
+                        line,
+                        format!("host '{}': '{}' has the wrong type", name, key),
+                    )
+                }
+                _ => return invalid(line, format!("host '{}': unknown key '{}'", name, key)),
+            }
+        }
+
+        let Some(mac) = mac else {
+            return invalid(line, format!("host '{}' is missing a MAC address", name));
+        };
+        host.mac = mac;
+
+        Ok(host)
+    }
+
+    /// Checks a host about to be added to the registry, making sure that its addresses are valid
+    /// and that it can be woken by name: names that are MAC or IP addresses or glob patterns are
+    /// never looked up as aliases.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("the name must not be empty".into());
+        }
+        if self.name.parse::<MacAddr>().is_ok() || self.name.parse::<IpAddr>().is_ok() {
+            return Err(format!(
+                "'{}' is an address and cannot be used as a name",
+                self.name
+            ));
+        }
+        if is_pattern(&self.name) {
+            return Err(format!(
+                "'{}' contains glob characters and cannot be used as a name",
+                self.name
+            ));
+        }
+        if let Some(password) = &self.password {
+            if let Err(err) = password.parse::<MacAddr>() {
+                return Err(format!("invalid SecureOn password: {}", err));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_table(&self) -> toml::Table {
+        let mut table = toml::Table::new(&["hosts", &self.name]);
+        table.set("mac", Value::String(self.mac.to_string()));
+        if let Some(broadcast) = self.broadcast {
+            table.set("broadcast", Value::String(broadcast.to_string()));
+        }
+        if let Some(ip) = self.ip {
+            table.set("ip", Value::String(ip.to_string()));
+        }
+        if let Some(port) = self.port {
+            table.set("port", Value::Integer(port.into()));
+        }
+        if let Some(password) = &self.password {
+            table.set("password", Value::String(password.clone()));
+        }
+        if let Some(interface) = &self.interface {
+            table.set("interface", Value::String(interface.clone()));
+        }
+        if let Some(count) = self.count {
+            table.set("count", Value::Integer(count.into()));
+        }
+        if !self.groups.is_empty() {
+            let groups = self.groups.iter().cloned().map(Value::String).collect();
+            table.set("groups", Value::Array(groups));
+        }
+        if let Some(note) = &self.note {
+            table.set("note", Value::String(note.clone()));
+        }
+        if !self.schedule.is_empty() {
+            let schedule = self
+                .schedule
+                .iter()
+                .map(|schedule| Value::String(schedule.to_string()))
+                .collect();
+            table.set("schedule", Value::Array(schedule));
+        }
+        self.hooks.write(&mut table);
+
+        table
+    }
+
+    fn to_json(&self) -> json::Value {
+        let table = self.to_table();
+        let mut members = vec![("name".to_string(), json::Value::String(self.name.clone()))];
+        members.extend(
+            table
+                .entries
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value))),
+        );
+        json::Value::Object(members)
+    }
+
+    /// Reads a host from its exported JSON object, validating it like a table of the registry.
+    fn from_json(value: &json::Value) -> Result<Host, ConfigError> {
+        let json::Value::Object(members) = value else {
+            return Err(ConfigError::Json("hosts must be objects".into()));
+        };
+        let Some(json::Value::String(name)) = value.get("name") else {
+            return Err(ConfigError::Json("every host must have a name".into()));
+        };
+
+        let mut table = toml::Table::new(&["hosts", name]);
+        for (key, value) in members {
+            if key == "name" || *value == json::Value::Null {
+                continue;
+            }
+            match json_to_toml(value) {
+                Some(value) => table.set(key, value),
+                None => {
+                    return Err(ConfigError::Json(format!(
+                        "host '{}': '{}' has the wrong type",
+                        name, key
+                    )))
+                }
+            }
+        }
+
+        Host::from_table(name, &table).map_err(|err| match err {
+            ConfigError::Invalid { message, .. } => ConfigError::Json(message),
+            err => err,
+        })
+    }
+
+    /// The target to wake this host with, carrying its options.
+    pub fn to_target(&self) -> Target {
+        Target {
+            mac: Some(self.mac),
+            alias: Some(self.name.clone()),
+            addr: self.broadcast,
+            port: self.port,
+            password: self.password.clone(),
+            interface: self.interface.clone(),
+            count: self.count,
+            ip: self.ip,
+            ..Default::default()
+        }
+    }
+}
+
+impl Config {
+    /// The location of the registry: `explicit` when given, then the file named by the
+    /// `WOL_CONFIG` environment variable, then `$XDG_CONFIG_HOME/wol/hosts.toml` (by default
+    /// `~/.config/wol/hosts.toml`, or `%APPDATA%\wol\hosts.toml` on Windows). When the user has
+    /// no registry of their own, the first one found in `$XDG_CONFIG_DIRS` (by default
+    /// `/etc/xdg/wol/hosts.toml`) is used instead.
+    pub fn locate(explicit: Option<&Path>) -> Option<PathBuf> {
+        if let Some(path) = explicit {
+            return Some(path.to_path_buf());
+        }
+        if let Some(path) = std::env::var_os("WOL_CONFIG").filter(|path| !path.is_empty()) {
+            return Some(path.into());
+        }
+
+        let user = dirs::config().map(|dir| dir.join(FILE_NAME));
+        if user.as_ref().is_some_and(|path| path.exists()) {
+            return user;
+        }
+        dirs::system_config()
+            .into_iter()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.exists())
+            .or(user)
+    }
+
+    /// Loads the registry from a file. A missing file is treated as an empty registry.
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Config::parse(&contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the registry to a file, creating its directory if needed. The file is replaced as a
+    /// whole, so comments in it are not kept.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        // write to a temporary file first so that a failed write never leaves half a registry
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, self.to_string())?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn parse(input: &str) -> Result<Config, ConfigError> {
+        let document = toml::parse(input)?;
+        let hosts = hosts(&document)
+            .into_iter()
+            .map(|(_, host)| host)
+            .collect::<Result<_, _>>()?;
+        let hooks = match global_table(&document, "hooks") {
+            Some(table) => Hooks::from_table(table)?,
+            None => Hooks::default(),
+        };
+        let serve = match global_table(&document, "serve") {
+            Some(table) => Serve::from_table(table)?,
+            None => Serve::default(),
+        };
+        Ok(Config {
+            hosts,
+            hooks,
+            serve,
+        })
+    }
+
+    /// The hook to run for a target: the target's own when it is a host that sets one, or else
+    /// the global one.
+    pub fn hook(&self, target: &Target, hook: Hook) -> Option<&str> {
+        let host = target.alias.as_deref().and_then(|alias| self.host(alias));
+        host.and_then(|host| host.hooks.get(hook))
+            .or_else(|| self.hooks.get(hook))
+    }
+
+    /// The webhook to notify of a target's outcome, the target's own or else the global one.
+    pub fn notify_url(&self, target: &Target) -> Option<&str> {
+        let host = target.alias.as_deref().and_then(|alias| self.host(alias));
+        host.and_then(|host| host.hooks.notify_url.as_deref())
+            .or(self.hooks.notify_url.as_deref())
+    }
+
+    /// Checks a registry more thoroughly than [`Config::parse`], returning every problem found
+    /// instead of only the first. Besides invalid entries, this flags hosts that could never be
+    /// woken by name, and hosts sharing a MAC address.
+    pub fn check(input: &str) -> Vec<ConfigError> {
+        let document = match toml::parse(input) {
+            Ok(document) => document,
+            Err(err) => return vec![err.into()],
+        };
+
+        let mut problems = Vec::new();
+        if let Some(Err(err)) = global_table(&document, "hooks").map(Hooks::from_table) {
+            problems.push(err);
+        }
+        if let Some(Err(err)) = global_table(&document, "serve").map(Serve::from_table) {
+            problems.push(err);
+        }
+        let mut valid: Vec<Host> = Vec::new();
+        for (table, result) in hosts(&document) {
+            let host = match result {
+                Ok(host) => host,
+                Err(err) => {
+                    problems.push(err);
+                    continue;
+                }
+            };
+            if let Err(message) = host.validate() {
+                problems.push(ConfigError::Invalid {
+                    line: table.line,
+                    message: format!("host '{}': {}", host.name, message),
+                });
+            }
+            if let Some(other) = valid.iter().find(|other| other.mac == host.mac) {
+                problems.push(ConfigError::Invalid {
+                    line: table.line,
+                    message: format!(
+                        "host '{}' has the same MAC address as '{}'",
+                        host.name, other.name
+                    ),
+                });
+            }
+            valid.push(host);
+        }
+
+        problems
+    }
+
+    /// The registry as JSON, `{"hosts": [{"name": ..., "mac": ..., ...}]}`, with the same keys
+    /// as the TOML file.
+    pub fn to_json(&self) -> json::Value {
+        let hosts = self.hosts.iter().map(Host::to_json).collect();
+        let mut members = vec![("hosts".to_string(), json::Value::Array(hosts))];
+        if !self.hooks.is_empty() {
+            let mut table = toml::Table::new(&["hooks"]);
+            self.hooks.write(&mut table);
+            let hooks = table
+                .entries
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect();
+            members.push(("hooks".to_string(), json::Value::Object(hooks)));
+        }
+        if !self.serve.is_empty() {
+            let mut table = toml::Table::new(&["serve"]);
+            self.serve.write(&mut table);
+            let serve = table
+                .entries
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect();
+            members.push(("serve".to_string(), json::Value::Object(serve)));
+        }
+        json::Value::Object(members)
+    }
+
+    /// Reads a registry exported by [`Config::to_json`].
+    pub fn from_json(input: &str) -> Result<Config, ConfigError> {
+        let document = json::parse(input).map_err(|err| ConfigError::Json(err.to_string()))?;
+        let Some(json::Value::Array(hosts)) = document.get("hosts") else {
+            return Err(ConfigError::Json(
+                "expected an object with a list of hosts".into(),
+            ));
+        };
+
+        let mut config = Config::default();
+        if let Some(hooks) = document.get("hooks") {
+            let json::Value::Object(members) = hooks else {
+                return Err(ConfigError::Json("hooks must be an object".into()));
+            };
+            let mut table = toml::Table::new(&["hooks"]);
+            for (key, value) in members {
+                let json::Value::String(command) = value else {
+                    return Err(ConfigError::Json(format!(
+                        "hooks: '{}' has the wrong type",
+                        key
+                    )));
+                };
+                table.set(key, Value::String(command.clone()));
+            }
+            config.hooks = Hooks::from_table(&table).map_err(|err| match err {
+                ConfigError::Invalid { message, .. } => ConfigError::Json(message),
+                err => err,
+            })?;
+        }
+        if let Some(serve) = document.get("serve") {
+            let json::Value::Object(members) = serve else {
+                return Err(ConfigError::Json("serve must be an object".into()));
+            };
+            let mut table = toml::Table::new(&["serve"]);
+            for (key, value) in members {
+                let Some(value) = json_to_toml(value) else {
+                    return Err(ConfigError::Json(format!(
+                        "serve: '{}' has the wrong type",
+                        key
+                    )));
+                };
+                table.set(key, value);
+            }
+            config.serve = Serve::from_table(&table).map_err(|err| match err {
+                ConfigError::Invalid { message, .. } => ConfigError::Json(message),
+                err => err,
+            })?;
+        }
+        for host in hosts {
+            let host = Host::from_json(host)?;
+            if config.host(&host.name).is_some() {
+                return Err(ConfigError::Json(format!(
+                    "host '{}' is defined twice",
+                    host.name
+                )));
+            }
+            config.hosts.push(host);
+        }
+
+        Ok(config)
+    }
+
+    pub fn host(&self, name: &str) -> Option<&Host> {
+        self.hosts.iter().find(|host| host.name == name)
+    }
+
+    /// Removes the named host, returning it.
+    pub fn remove_host(&mut self, name: &str) -> Option<Host> {
+        let index = self.hosts.iter().position(|host| host.name == name)?;
+        Some(self.hosts.remove(index))
+    }
+
+    /// Removes the named group from every host belonging to it, returning how many did.
+    pub fn remove_group(&mut self, name: &str) -> usize {
+        let mut removed = 0;
+        for host in &mut self.hosts {
+            let len = host.groups.len();
+            host.groups.retain(|group| group != name);
+            removed += len - host.groups.len();
+        }
+        removed
+    }
+
+    /// Renames the named group on every host belonging to it, keeping its position among the
+    /// host's groups and merging it into the new group where a host already belongs to both.
+    pub fn rename_group(&mut self, old: &str, new: &str) {
+        for host in &mut self.hosts {
+            if host.groups.iter().any(|group| group == new) {
+                host.groups.retain(|group| group != old);
+            } else if let Some(group) = host.groups.iter_mut().find(|group| *group == old) {
+                *group = new.to_string();
+            }
+        }
+    }
+
+    /// The hosts whose names match a glob pattern, where `*` matches any run of characters, `?`
+    /// any single character and `[abc]` or `[a-z]` any character of a set.
+    pub fn matching(&self, pattern: &str) -> Vec<&Host> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        self.hosts
+            .iter()
+            .filter(|host| glob(&pattern, &host.name.chars().collect::<Vec<_>>()))
+            .collect()
+    }
+
+    /// The host with the given MAC address.
+    pub fn host_with_mac(&self, mac: MacAddr) -> Option<&Host> {
+        self.hosts.iter().find(|host| host.mac == mac)
+    }
+
+    /// The hosts belonging to the named group.
+    pub fn group(&self, name: &str) -> Vec<&Host> {
+        self.hosts
+            .iter()
+            .filter(|host| host.groups.iter().any(|group| group == name))
+            .collect()
+    }
+}
+
+fn toml_to_json(value: Value) -> json::Value {
+    match value {
+        Value::String(s) => json::Value::String(s),
+        Value::Integer(n) => json::Value::Number(n),
+        Value::Boolean(b) => json::Value::Bool(b),
+        Value::Array(values) => json::Value::Array(values.into_iter().map(toml_to_json).collect()),
+    }
+}
+
+fn json_to_toml(value: &json::Value) -> Option<Value> {
+    match value {
+        json::Value::String(s) => Some(Value::String(s.clone())),
+        json::Value::Number(n) => Some(Value::Integer(*n)),
+        json::Value::Bool(b) => Some(Value::Boolean(*b)),
+        json::Value::Array(values) => Some(Value::Array(
+            values.iter().map(json_to_toml).collect::<Option<_>>()?,
+        )),
+        json::Value::Null | json::Value::Object(_) => None,
+    }
+}
+
+/// A global table of a document, `[hooks]` or `[serve]`.
+fn global_table<'a>(document: &'a toml::Document, name: &str) -> Option<&'a toml::Table> {
+    document
+        .tables
+        .iter()
+        .find(|table| table.path.len() == 1 && table.path[0] == name)
+}
+
+/// Reads the hosts of a document, with one result for every table in it except the empty ones
+/// and the global ones.
+fn hosts(document: &toml::Document) -> Vec<(&toml::Table, Result<Host, ConfigError>)> {
+    let mut hosts = Vec::new();
+    for table in &document.tables {
+        let host = match table.path.as_slice() {
+            [] if table.entries.is_empty() => continue,
+            [global] if global == "hooks" || global == "serve" => continue,
+            [hosts] if hosts == "hosts" && table.entries.is_empty() => continue,
+            [hosts, name] if hosts == "hosts" => Host::from_table(name, table),
+            [] => {
+                let (key, _) = &table.entries[0];
+                invalid(table.line, format!("unknown key '{}'", key))
+            }
+            path => invalid(table.line, format!("unknown table '{}'", path.join("."))),
+        };
+        hosts.push((table, host));
+    }
+
+    hosts
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tables = Vec::new();
+        if !self.hooks.is_empty() {
+            let mut table = toml::Table::new(&["hooks"]);
+            self.hooks.write(&mut table);
+            tables.push(table);
+        }
+        if !self.serve.is_empty() {
+            let mut table = toml::Table::new(&["serve"]);
+            self.serve.write(&mut table);
+            tables.push(table);
+        }
+        tables.extend(self.hosts.iter().map(Host::to_table));
+        let document = toml::Document { tables };
+        write!(f, "{}", document)
+    }
+}
+
+/// Whether `pattern` contains any glob metacharacters.
+pub fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(1).position(|&c| c == ']') else {
+                // an unterminated set is matched literally
+                return name.first() == Some(&'[') && glob(&pattern[1..], &name[1..]);
+            };
+            let set = &pattern[1..end + 1];
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let mut matched = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    matched |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    matched |= set[i] == c;
+                    i += 1;
+                }
+            }
+            matched && glob(&pattern[end + 2..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && glob(&pattern[1..], &name[1..]),
+    }
+}
+
+#[test]
+fn test_glob() {
+    let matches = |pattern: &str, name: &str| {
+        glob(
+            &pattern.chars().collect::<Vec<_>>(),
+            &name.chars().collect::<Vec<_>>(),
+        )
+    };
+
+    assert!(matches("render-*", "render-01"));
+    assert!(matches("render-*", "render-"));
+    assert!(!matches("render-*", "renderer"));
+    assert!(matches("*-01", "render-01"));
+    assert!(matches("render-0?", "render-07"));
+    assert!(!matches("render-0?", "render-007"));
+    assert!(matches("render-[0-4]1", "render-31"));
+    assert!(!matches("render-[0-4]1", "render-51"));
+    assert!(matches("[ab]*", "build"));
+    assert!(matches("nas[", "nas["));
+}
+
+#[test]
+fn test_parse() {
+    let config = Config::parse(
+        r#"
+[hosts.nas]
+mac = "aa:bb:cc:dd:ee:ff"
+broadcast = "192.168.1.255"
+port = 7
+
+[hosts.desktop]
+mac = "11-22-33-44-55-66"
+password = "01-02-03-04-05-06"
+groups = ["lab", "render-farm"]
+
+[hosts.laptop]
+mac = "11-22-33-44-55-77"
+groups = ["lab"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config.host("nas"),
+        Some(&Host {
+            name: "nas".into(),
+            mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+            broadcast: Some("192.168.1.255".parse().unwrap()),
+            ip: None,
+            port: Some(7),
+            password: None,
+            interface: None,
+            count: None,
+            groups: vec![],
+            note: None,
+            schedule: vec![],
+            hooks: Hooks::default(),
+        })
+    );
+    assert_eq!(
+        config.host("desktop").unwrap().password.as_deref(),
+        Some("01-02-03-04-05-06")
+    );
+    assert!(config.host("printer").is_none());
+
+    let lab: Vec<_> = config.group("lab").iter().map(|host| &host.name).collect();
+    assert_eq!(lab, ["desktop", "laptop"]);
+    assert_eq!(config.group("render-farm").len(), 1);
+    assert!(config.group("media").is_empty());
+
+    let mut config = config;
+    config.rename_group("render-farm", "lab");
+    assert_eq!(config.host("desktop").unwrap().groups, ["lab"]);
+    config.rename_group("lab", "render-farm");
+    assert_eq!(config.group("render-farm").len(), 2);
+    config.rename_group("render-farm", "lab");
+    assert_eq!(config.remove_group("lab"), 2);
+    assert!(config.group("lab").is_empty());
+    assert!(config.host("desktop").unwrap().groups.is_empty());
+    assert_eq!(config.remove_host("nas").unwrap().name, "nas");
+    assert!(config.remove_host("nas").is_none());
+    assert_eq!(config.hosts.len(), 2);
+}
+
+#[test]
+fn test_parse_invalid() {
+    let err = Config::parse("[hosts.nas]\nmac = \"nope\"\n").unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { line: 1, .. }));
+
+    assert!(Config::parse("[hosts.nas]\nport = 9\n").is_err());
+    assert!(Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\nport = 70000\n").is_err());
+    assert!(Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\nvlan = 2\n").is_err());
+    assert!(Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\ncount = 0\n").is_err());
+    assert!(Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\nip = \"nas\"\n").is_err());
+    assert!(Config::parse("[printers.hp]\nmac = \"aa:bb:cc:dd:ee:ff\"\n").is_err());
+    assert!(Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\ngroups = [1]\n").is_err());
+    let err = Config::parse("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\nschedule = [\"45 6\"]\n")
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("schedule '45 6': expected 5 fields"));
+}
+
+#[test]
+fn test_roundtrip() {
+    let config = Config {
+        hosts: vec![
+            Host {
+                name: "nas".into(),
+                mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+                broadcast: Some("192.168.1.255".parse().unwrap()),
+                ip: Some("192.168.1.20".parse().unwrap()),
+                port: Some(7),
+                password: Some("01-02-03-04-05-06".into()),
+                interface: Some("eth1".into()),
+                count: Some(3),
+                groups: vec!["lab".into()],
+                note: Some("rack 2".into()),
+                schedule: vec!["45 6 * * mon-fri".parse().unwrap()],
+                hooks: {
+                    let mut hooks = Hooks::default();
+                    hooks.set(Hook::OnAwake, "mount /mnt/nas".into());
+                    hooks
+                },
+            },
+            Host {
+                name: "living room".into(),
+                mac: "11:22:33:44:55:66".parse().unwrap(),
+                ..Default::default()
+            },
+        ],
+        hooks: {
+            let mut hooks = Hooks::default();
+            hooks.set(Hook::PostSend, "notify-send \"$WOL_HOST\"".into());
+            hooks.notify_url = Some("https://ntfy.sh/wol".into());
+            hooks
+        },
+        serve: Serve {
+            tokens: vec!["s3cret".into(), "phone".into()],
+        },
+    };
+
+    assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+    assert_eq!(
+        Config::from_json(&config.to_json().to_string()).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn test_from_json_invalid() {
+    assert!(Config::from_json("[]").is_err());
+    assert!(Config::from_json(r#"{"hosts": [{"mac": "aa:bb:cc:dd:ee:ff"}]}"#).is_err());
+    assert!(Config::from_json(r#"{"hosts": [{"name": "nas", "mac": "nope"}]}"#).is_err());
+    assert!(Config::from_json(r#"{"hosts": [{"name": "nas", "mac": {}}]}"#).is_err());
+    let twice = r#"{"hosts": [
+        {"name": "nas", "mac": "aa:bb:cc:dd:ee:ff"},
+        {"name": "nas", "mac": "aa:bb:cc:dd:ee:ff"}
+    ]}"#;
+    assert!(Config::from_json(twice).is_err());
+}
+
+#[test]
+fn test_check() {
+    let problems = Config::check(
+        r#"
+[hosts.nas]
+mac = "aa:bb:cc:dd:ee:ff"
+
+[hosts.desktop]
+mac = "nope"
+
+[hosts."10.0.0.1"]
+mac = "11:22:33:44:55:66"
+
+[hosts.storage]
+mac = "AA-BB-CC-DD-EE-FF"
+port = 70000
+"#,
+    );
+    let lines: Vec<usize> = problems
+        .iter()
+        .map(|problem| match problem {
+            ConfigError::Invalid { line, .. } => *line,
+            _ => 0,
+        })
+        .collect();
+    assert_eq!(lines, [5, 8, 11]);
+
+    assert_eq!(Config::check("[hosts.nas]\n[hosts.nas]\n").len(), 1);
+    assert!(Config::check("[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\n").is_empty());
+}
+
+#[test]
+fn test_validate() {
+    let host = |name: &str| Host {
+        name: name.into(),
+        mac: MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]),
+        ..Default::default()
+    };
+
+    assert!(host("nas").validate().is_ok());
+    assert!(host("").validate().is_err());
+    assert!(host("aabbccddeeff").validate().is_err());
+    assert!(host("10.0.0.1").validate().is_err());
+    assert!(host("nas*").validate().is_err());
+}
+
+#[test]
+fn test_hooks() {
+    let config = Config::parse(
+        r#"
+[hooks]
+on-awake = "echo global"
+on-timeout = "echo timeout"
+
+[hosts.nas]
+mac = "aa:bb:cc:dd:ee:ff"
+on-awake = "mount /mnt/nas"
+notify-url = "http://dashboard.local/wol"
+"#,
+    )
+    .unwrap();
+
+    let nas = config.host("nas").unwrap().to_target();
+    assert_eq!(config.hook(&nas, Hook::OnAwake), Some("mount /mnt/nas"));
+    assert_eq!(config.hook(&nas, Hook::OnTimeout), Some("echo timeout"));
+    assert_eq!(config.hook(&nas, Hook::PreSend), None);
+    let other = Target::new("11:22:33:44:55:66");
+    assert_eq!(config.hook(&other, Hook::OnAwake), Some("echo global"));
+    assert_eq!(config.notify_url(&nas), Some("http://dashboard.local/wol"));
+    assert_eq!(config.notify_url(&other), None);
+
+    assert!(Config::parse("[hooks]\non-sleep = \"true\"\n").is_err());
+    assert!(Config::parse("[hooks]\non-awake = 1\n").is_err());
+    assert_eq!(Config::check("[hooks]\npre-wake = \"true\"\n").len(), 1);
+}
+
+#[test]
+fn test_serve() {
+    let config = Config::parse("[serve]\ntokens = [\"s3cret\"]\n").unwrap();
+    assert_eq!(config.serve.tokens, ["s3cret"]);
+    assert!(config.hosts.is_empty());
+
+    assert!(Config::parse("[serve]\ntokens = \"s3cret\"\n").is_err());
+    assert!(Config::parse("[serve]\ntokens = [\"\"]\n").is_err());
+    assert_eq!(Config::check("[serve]\ntoken = [\"s3cret\"]\n").len(), 1);
+}
+
+#[test]
+fn test_host_with_mac() {
+    let config = Config::parse("[hosts.nas]\nmac = \"AA-BB-CC-DD-EE-FF\"\n").unwrap();
+    assert_eq!(
+        config
+            .host_with_mac("aa:bb:cc:dd:ee:ff".parse().unwrap())
+            .map(|host| host.name.as_str()),
+        Some("nas")
+    );
+    assert!(config
+        .host_with_mac("11:22:33:44:55:66".parse().unwrap())
+        .is_none());
+}
@@ -0,0 +1,776 @@
+//! A small D-Bus client, enough to own a name on the system or session bus and answer the
+//! method calls made to it.
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const SYSTEM_BUS: &str = "unix:path=/var/run/dbus/system_bus_socket";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest message the specification allows.
+const MAX_MESSAGE: usize = 1 << 27;
+
+pub const METHOD_CALL: u8 = 1;
+pub const METHOD_RETURN: u8 = 2;
+pub const ERROR: u8 = 3;
+
+/// Set on calls whose caller does not want an answer.
+const NO_REPLY_EXPECTED: u8 = 0x1;
+
+const FIELD_PATH: u8 = 1;
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_ERROR_NAME: u8 = 4;
+const FIELD_REPLY_SERIAL: u8 = 5;
+const FIELD_DESTINATION: u8 = 6;
+const FIELD_SENDER: u8 = 7;
+const FIELD_SIGNATURE: u8 = 8;
+
+/// Asks RequestName to fail rather than wait in line when the name is owned already.
+const DO_NOT_QUEUE: u32 = 0x4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    System,
+    Session,
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Bus::System => "system",
+            Bus::Session => "session",
+        })
+    }
+}
+
+impl Bus {
+    /// The address of the bus, from the environment or where it is usually found.
+    fn address(self) -> Option<String> {
+        match self {
+            Bus::System => {
+                Some(env::var("DBUS_SYSTEM_BUS_ADDRESS").unwrap_or_else(|_| SYSTEM_BUS.to_string()))
+            }
+            Bus::Session => env::var("DBUS_SESSION_BUS_ADDRESS").ok().or_else(|| {
+                // systemd starts the session bus here, without every session setting the variable
+                let path = Path::new(&env::var_os("XDG_RUNTIME_DIR")?).join("bus");
+                path.exists()
+                    .then(|| format!("unix:path={}", path.display()))
+            }),
+        }
+    }
+}
+
+/// A value of an argument or header field, of the types used here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    U32(u32),
+    Str(String),
+    ObjectPath(String),
+    Signature(String),
+
+    /// The signature of the elements, needed for empty arrays, and the elements.
+    Array(String, Vec<Value>),
+
+    Struct(Vec<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    /// Builds an array of strings.
+    pub fn strings(strings: impl IntoIterator<Item = String>) -> Value {
+        Value::Array("s".into(), strings.into_iter().map(Value::Str).collect())
+    }
+
+    pub fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".into(),
+            Value::Bool(_) => "b".into(),
+            Value::U32(_) => "u".into(),
+            Value::Str(_) => "s".into(),
+            Value::ObjectPath(_) => "o".into(),
+            Value::Signature(_) => "g".into(),
+            Value::Array(element, _) => format!("a{}", element),
+            Value::Struct(fields) => {
+                let fields: String = fields.iter().map(Value::signature).collect();
+                format!("({})", fields)
+            }
+            Value::Variant(_) => "v".into(),
+        }
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            Value::Byte(byte) => buf.push(*byte),
+            Value::Bool(value) => write_u32(buf, u32::from(*value)),
+            Value::U32(value) => write_u32(buf, *value),
+            Value::Str(s) | Value::ObjectPath(s) => {
+                write_u32(buf, s.len() as u32);
+                buf.extend_from_slice(s.as_bytes());
+                buf.push(0);
+            }
+            Value::Signature(s) => {
+                buf.push(s.len() as u8);
+                buf.extend_from_slice(s.as_bytes());
+                buf.push(0);
+            }
+            Value::Array(element, values) => {
+                write_u32(buf, 0);
+                let len_at = buf.len() - 4;
+                // the length leaves out the padding before the first element
+                pad(buf, alignment(element));
+                let start = buf.len();
+                for value in values {
+                    value.write(buf);
+                }
+                let len = (buf.len() - start) as u32;
+                buf[len_at..len_at + 4].copy_from_slice(&len.to_le_bytes());
+            }
+            Value::Struct(fields) => {
+                pad(buf, 8);
+                for field in fields {
+                    field.write(buf);
+                }
+            }
+            Value::Variant(value) => {
+                Value::Signature(value.signature()).write(buf);
+                value.write(buf);
+            }
+        }
+    }
+}
+
+/// The alignment of the values of a signature, given by its first type.
+fn alignment(signature: &str) -> usize {
+    match signature.as_bytes().first() {
+        Some(b'y' | b'g' | b'v') => 1,
+        Some(b'n' | b'q') => 2,
+        Some(b'x' | b't' | b'd' | b'(' | b'{') => 8,
+        _ => 4,
+    }
+}
+
+fn pad(buf: &mut Vec<u8>, align: usize) {
+    while !buf.len().is_multiple_of(align) {
+        buf.push(0);
+    }
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    pad(buf, 4);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Splits the first complete type off a signature, e.g. "a(su)" off "a(su)b".
+fn split_type(signature: &str) -> io::Result<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in signature.char_indices() {
+        match c {
+            'a' => continue,
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return Ok(signature.split_at(i + 1));
+        }
+    }
+    Err(invalid(format!("incomplete signature '{}'", signature)))
+}
+
+/// Reads values out of a message, where values are aligned relative to its start.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or_else(|| invalid("truncated message"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn align(&mut self, align: usize) -> io::Result<()> {
+        let padding = (align - self.pos % align) % align;
+        self.take(padding).map(drop)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4)?;
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn string(&mut self, len: usize) -> io::Result<String> {
+        let bytes = self.take(len + 1)?;
+        if bytes[len] != 0 {
+            return Err(invalid("string without a terminating nul"));
+        }
+        String::from_utf8(bytes[..len].to_vec()).map_err(|_| invalid("string not in UTF-8"))
+    }
+
+    /// Reads the values of a signature, which may hold several types.
+    fn values(&mut self, mut signature: &str) -> io::Result<Vec<Value>> {
+        let mut values = Vec::new();
+        while !signature.is_empty() {
+            let (ty, rest) = split_type(signature)?;
+            values.push(self.value(ty)?);
+            signature = rest;
+        }
+        Ok(values)
+    }
+
+    /// Reads a value of a single complete type.
+    fn value(&mut self, ty: &str) -> io::Result<Value> {
+        Ok(match ty.as_bytes()[0] {
+            b'y' => Value::Byte(self.take(1)?[0]),
+            b'b' => Value::Bool(self.u32()? != 0),
+            b'u' => Value::U32(self.u32()?),
+            b's' => {
+                let len = self.u32()? as usize;
+                Value::Str(self.string(len)?)
+            }
+            b'o' => {
+                let len = self.u32()? as usize;
+                Value::ObjectPath(self.string(len)?)
+            }
+            b'g' => {
+                let len = self.take(1)?[0] as usize;
+                Value::Signature(self.string(len)?)
+            }
+            b'a' => {
+                let len = self.u32()? as usize;
+                let element = &ty[1..];
+                self.align(alignment(element))?;
+                let end = self.pos.saturating_add(len);
+                let mut values = Vec::new();
+                while self.pos < end {
+                    let before = self.pos;
+                    values.push(self.value(element)?);
+                    if self.pos == before {
+                        return Err(invalid("array of empty elements"));
+                    }
+                }
+                if self.pos != end {
+                    return Err(invalid("array elements overrun its length"));
+                }
+                Value::Array(element.to_string(), values)
+            }
+            // dict entries are read as structs, which they are laid out like
+            b'(' | b'{' => {
+                self.align(8)?;
+                Value::Struct(self.values(&ty[1..ty.len() - 1])?)
+            }
+            b'v' => {
+                let len = self.take(1)?[0] as usize;
+                let signature = self.string(len)?;
+                let (ty, rest) = split_type(&signature)?;
+                if !rest.is_empty() {
+                    return Err(invalid("variant of more than one type"));
+                }
+                Value::Variant(Box::new(self.value(ty)?))
+            }
+            _ => return Err(invalid(format!("unsupported type '{}'", ty))),
+        })
+    }
+}
+
+/// A message sent or received on the bus.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub kind: u8,
+    pub flags: u8,
+    pub serial: u32,
+    pub path: Option<String>,
+    pub interface: Option<String>,
+    pub member: Option<String>,
+    pub error_name: Option<String>,
+    pub reply_serial: Option<u32>,
+    pub destination: Option<String>,
+    pub sender: Option<String>,
+    signature: String,
+    body: Vec<u8>,
+    big_endian: bool,
+}
+
+impl Message {
+    pub fn method_call(
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        args: &[Value],
+    ) -> Message {
+        Message {
+            kind: METHOD_CALL,
+            path: Some(path.to_string()),
+            interface: Some(interface.to_string()),
+            member: Some(member.to_string()),
+            destination: Some(destination.to_string()),
+            ..Default::default()
+        }
+        .with_args(args)
+    }
+
+    /// The answer to a method call.
+    pub fn method_return(call: &Message, args: &[Value]) -> Message {
+        Message {
+            kind: METHOD_RETURN,
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            ..Default::default()
+        }
+        .with_args(args)
+    }
+
+    /// The error answering a method call, e.g. org.freedesktop.DBus.Error.UnknownMethod.
+    pub fn error(call: &Message, name: &str, text: &str) -> Message {
+        Message {
+            kind: ERROR,
+            error_name: Some(name.to_string()),
+            reply_serial: Some(call.serial),
+            destination: call.sender.clone(),
+            ..Default::default()
+        }
+        .with_args(&[Value::Str(text.to_string())])
+    }
+
+    fn with_args(mut self, args: &[Value]) -> Message {
+        for arg in args {
+            self.signature.push_str(&arg.signature());
+            arg.write(&mut self.body);
+        }
+        self
+    }
+
+    /// Whether the caller asked for an answer to this call.
+    pub fn expects_reply(&self) -> bool {
+        self.flags & NO_REPLY_EXPECTED == 0
+    }
+
+    pub fn args(&self) -> io::Result<Vec<Value>> {
+        let mut reader = Reader {
+            buf: &self.body,
+            pos: 0,
+            big_endian: self.big_endian,
+        };
+        let args = reader.values(&self.signature)?;
+        if reader.pos != self.body.len() {
+            return Err(invalid("body longer than its signature"));
+        }
+        Ok(args)
+    }
+
+    /// The message as sent, in little-endian byte order.
+    fn encode(&self) -> Vec<u8> {
+        let mut fields = Vec::new();
+        let mut field = |code: u8, value: Value| {
+            fields.push(Value::Struct(vec![
+                Value::Byte(code),
+                Value::Variant(Box::new(value)),
+            ]));
+        };
+        let strings = [
+            (FIELD_PATH, &self.path),
+            (FIELD_INTERFACE, &self.interface),
+            (FIELD_MEMBER, &self.member),
+            (FIELD_ERROR_NAME, &self.error_name),
+            (FIELD_DESTINATION, &self.destination),
+            (FIELD_SENDER, &self.sender),
+        ];
+        for (code, value) in strings {
+            if let Some(value) = value {
+                let value = match code {
+                    FIELD_PATH => Value::ObjectPath(value.clone()),
+                    _ => Value::Str(value.clone()),
+                };
+                field(code, value);
+            }
+        }
+        if let Some(reply_serial) = self.reply_serial {
+            field(FIELD_REPLY_SERIAL, Value::U32(reply_serial));
+        }
+        if !self.signature.is_empty() {
+            field(FIELD_SIGNATURE, Value::Signature(self.signature.clone()));
+        }
+
+        let mut buf = vec![b'l', self.kind, self.flags, 1];
+        buf.extend_from_slice(&(self.body.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.serial.to_le_bytes());
+        Value::Array("(yv)".into(), fields).write(&mut buf);
+        pad(&mut buf, 8);
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    /// The length of a message from the first 16 bytes of its header.
+    fn length(start: &[u8; 16]) -> io::Result<usize> {
+        let read = |bytes: &[u8]| {
+            let bytes = bytes.try_into().unwrap();
+            match start[0] {
+                b'l' => Ok(u32::from_le_bytes(bytes) as usize),
+                b'B' => Ok(u32::from_be_bytes(bytes) as usize),
+                _ => Err(invalid("unknown byte order")),
+            }
+        };
+        let (body, fields) = (read(&start[4..8])?, read(&start[12..16])?);
+        let header = (16 + fields).next_multiple_of(8);
+        match header.checked_add(body) {
+            Some(len) if len <= MAX_MESSAGE => Ok(len),
+            _ => Err(invalid("message too long")),
+        }
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Message> {
+        let mut reader = Reader {
+            buf,
+            pos: 0,
+            big_endian: buf.first() == Some(&b'B'),
+        };
+        let start = reader.take(4)?;
+        let mut message = Message {
+            kind: start[1],
+            flags: start[2],
+            big_endian: reader.big_endian,
+            ..Default::default()
+        };
+        let body_len = reader.u32()? as usize;
+        message.serial = reader.u32()?;
+        let Value::Array(_, fields) = reader.value("a(yv)")? else {
+            unreachable!()
+        };
+        for field in fields {
+            let Value::Struct(field) = field else {
+                unreachable!()
+            };
+            let (Value::Byte(code), Value::Variant(value)) = (&field[0], &field[1]) else {
+                unreachable!()
+            };
+            // fields of unknown codes are to be ignored
+            match (*code, *value.clone()) {
+                (FIELD_PATH, Value::ObjectPath(path)) => message.path = Some(path),
+                (FIELD_INTERFACE, Value::Str(s)) => message.interface = Some(s),
+                (FIELD_MEMBER, Value::Str(s)) => message.member = Some(s),
+                (FIELD_ERROR_NAME, Value::Str(s)) => message.error_name = Some(s),
+                (FIELD_REPLY_SERIAL, Value::U32(serial)) => message.reply_serial = Some(serial),
+                (FIELD_DESTINATION, Value::Str(s)) => message.destination = Some(s),
+                (FIELD_SENDER, Value::Str(s)) => message.sender = Some(s),
+                (FIELD_SIGNATURE, Value::Signature(s)) => message.signature = s,
+                (FIELD_PATH..=FIELD_SIGNATURE, _) => {
+                    return Err(invalid(format!("header field {} of the wrong type", code)))
+                }
+                _ => {}
+            }
+        }
+        reader.align(8)?;
+        message.body = reader.take(body_len)?.to_vec();
+        Ok(message)
+    }
+}
+
+/// The stream to the bus, a Unix socket on the platforms that have them.
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// Decodes the percent escapes of a value in a bus address.
+fn unescape(value: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (byte, escaped) {
+            (b'%', Some(escaped)) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Connects to the first address of a list, e.g. "unix:path=/run/user/1000/bus", that can be
+/// connected to.
+#[cfg(unix)]
+fn open(addresses: &str) -> io::Result<Box<dyn Stream>> {
+    use std::os::unix::net::UnixStream;
+
+    let mut last_err = io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("no supported address in '{}'", addresses),
+    );
+    for address in addresses.split(';') {
+        let Some(params) = address.strip_prefix("unix:") else {
+            continue;
+        };
+        for (key, value) in params.split(',').filter_map(|param| param.split_once('=')) {
+            let value = unescape(value);
+            let stream = match key {
+                "path" => UnixStream::connect(&value),
+                #[cfg(target_os = "linux")]
+                "abstract" => {
+                    use std::os::linux::net::SocketAddrExt;
+                    std::os::unix::net::SocketAddr::from_abstract_name(&value)
+                        .and_then(|addr| UnixStream::connect_addr(&addr))
+                }
+                _ => continue,
+            };
+            match stream {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(TIMEOUT))?;
+                    stream.set_write_timeout(Some(TIMEOUT))?;
+                    return Ok(Box::new(stream));
+                }
+                Err(err) => last_err = err,
+            }
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(not(unix))]
+fn open(_addresses: &str) -> io::Result<Box<dyn Stream>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "D-Bus is not supported on this platform",
+    ))
+}
+
+/// Authenticates as the user we run as, which the bus checks against the credentials of the
+/// socket.
+#[cfg(unix)]
+fn authenticate(stream: &mut dyn Stream) -> io::Result<()> {
+    let uid: String = crate::platform::euid()
+        .to_string()
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    write!(stream, "\0AUTH EXTERNAL {}\r\n", uid)?;
+    let mut line = Vec::new();
+    while !line.ends_with(b"\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the bus closed the connection",
+            ));
+        }
+        line.push(byte[0]);
+    }
+    if !line.starts_with(b"OK ") {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the bus refused to authenticate us",
+        ));
+    }
+    stream.write_all(b"BEGIN\r\n")
+}
+
+#[cfg(not(unix))]
+fn authenticate(_stream: &mut dyn Stream) -> io::Result<()> {
+    Ok(())
+}
+
+pub struct Connection {
+    stream: Box<dyn Stream>,
+    serial: u32,
+
+    /// Messages received while waiting for the answer to a call.
+    received: VecDeque<Message>,
+}
+
+impl Connection {
+    /// Connects to the bus and says hello, as the bus expects before anything else.
+    pub fn connect(bus: Bus) -> io::Result<Connection> {
+        let address = bus.address().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "DBUS_SESSION_BUS_ADDRESS is not set",
+            )
+        })?;
+        let mut stream = open(&address)?;
+        authenticate(&mut *stream)?;
+        let mut connection = Connection {
+            stream,
+            serial: 0,
+            received: VecDeque::new(),
+        };
+        connection.call_bus("Hello", &[])?;
+        Ok(connection)
+    }
+
+    /// Asks for a well-known name, e.g. org.wolcli.Wol, failing if another process owns it.
+    pub fn request_name(&mut self, name: &str) -> io::Result<()> {
+        let reply = self.call_bus(
+            "RequestName",
+            &[Value::Str(name.to_string()), Value::U32(DO_NOT_QUEUE)],
+        )?;
+        match reply[..] {
+            // the primary owner, or the owner already
+            [Value::U32(1 | 4)] => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is owned by another process", name),
+            )),
+        }
+    }
+
+    /// Calls a method of the bus itself and waits for its answer.
+    fn call_bus(&mut self, member: &str, args: &[Value]) -> io::Result<Vec<Value>> {
+        let call = Message::method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            member,
+            args,
+        );
+        let serial = self.send(call)?;
+        loop {
+            let message = self.read_message()?;
+            if message.reply_serial != Some(serial) {
+                self.received.push_back(message);
+                continue;
+            }
+            if message.kind == ERROR {
+                let text = match message.args()?.first() {
+                    Some(Value::Str(text)) => text.clone(),
+                    _ => String::new(),
+                };
+                let name = message.error_name.unwrap_or_default();
+                return Err(io::Error::other(format!("{}: {}", name, text)));
+            }
+            return message.args();
+        }
+    }
+
+    /// Sends a message, returning the serial it was given.
+    pub fn send(&mut self, mut message: Message) -> io::Result<u32> {
+        self.serial += 1;
+        message.serial = self.serial;
+        self.stream.write_all(&message.encode())?;
+        Ok(self.serial)
+    }
+
+    /// Waits for the next message sent to us.
+    pub fn next_message(&mut self) -> io::Result<Message> {
+        match self.received.pop_front() {
+            Some(message) => Ok(message),
+            None => self.read_message(),
+        }
+    }
+
+    fn read_message(&mut self) -> io::Result<Message> {
+        let mut start = [0; 16];
+        // timing out is only expected between messages
+        loop {
+            match self.stream.read_exact(&mut start[..1]) {
+                Ok(()) => break,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        self.stream.read_exact(&mut start[1..])?;
+        let mut buf = vec![0; Message::length(&start)?];
+        buf[..16].copy_from_slice(&start);
+        self.stream.read_exact(&mut buf[16..])?;
+        Message::decode(&buf)
+    }
+}
+
+#[test]
+fn test_message() {
+    let hello = Message {
+        serial: 1,
+        ..Message::method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+            &[],
+        )
+    };
+    let encoded = hello.encode();
+    assert_eq!(&encoded[..16], b"l\x01\x00\x01\0\0\0\0\x01\0\0\0\x6d\0\0\0");
+    assert_eq!(
+        &encoded[16..48],
+        b"\x01\x01o\0\x15\0\0\0/org/freedesktop/DBus\0\0\0"
+    );
+    assert_eq!(encoded.len() % 8, 0);
+    assert_eq!(
+        Message::length(&encoded[..16].try_into().unwrap()).unwrap(),
+        encoded.len()
+    );
+    assert_eq!(Message::decode(&encoded).unwrap(), hello);
+
+    let args = [
+        Value::Str("nas".into()),
+        Value::strings(["nas".to_string(), "pc".to_string()]),
+        Value::Array("(su)".into(), vec![]),
+        Value::Struct(vec![Value::Bool(true), Value::Byte(7)]),
+        Value::Variant(Box::new(Value::U32(42))),
+    ];
+    let call = Message {
+        serial: 7,
+        sender: Some(":1.42".into()),
+        ..Message::method_call(
+            "org.wolcli.Wol",
+            "/org/wolcli/Wol",
+            "org.wolcli.Wol",
+            "Wake",
+            &args,
+        )
+    };
+    assert_eq!(call.signature, "sasa(su)(by)v");
+    let decoded = Message::decode(&call.encode()).unwrap();
+    assert_eq!(decoded.args().unwrap(), args);
+    assert!(decoded.expects_reply());
+
+    let error = Message::error(&decoded, "org.wolcli.Wol.Error.Failed", "'nas' timed-out");
+    let error = Message::decode(&error.encode()).unwrap();
+    assert_eq!(
+        (error.kind, error.reply_serial, error.destination.as_deref()),
+        (ERROR, Some(7), Some(":1.42"))
+    );
+
+    let mut truncated = call.encode();
+    truncated.pop();
+    assert!(Message::decode(&truncated).is_err());
+}
+
+#[test]
+fn test_split_type() {
+    assert_eq!(split_type("a(su)b").unwrap(), ("a(su)", "b"));
+    assert_eq!(split_type("a{sv}").unwrap(), ("a{sv}", ""));
+    assert_eq!(split_type("s").unwrap(), ("s", ""));
+    assert!(split_type("a(su").is_err());
+    assert_eq!(unescape("/run/user/1000/bus%2cx"), "/run/user/1000/bus,x");
+}
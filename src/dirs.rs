@@ -0,0 +1,65 @@
+//! Locations of the files wol-cli reads and writes, following the XDG base directory
+//! specification, or the known folders of Windows.
+use std::path::{Path, PathBuf};
+
+/// The directory named by the environment variable `var`, falling back to `fallback` within the
+/// home directory when it is unset or empty.
+fn base(var: &str, fallback: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(fallback)))
+}
+
+/// The directory named by a Windows environment variable such as `APPDATA`.
+fn known_folder(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `$XDG_CONFIG_HOME/wol`, or `%APPDATA%\wol` on Windows, for files edited by the user.
+pub fn config() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        known_folder("APPDATA")
+    } else {
+        base("XDG_CONFIG_HOME", ".config")
+    };
+    Some(base?.join("wol"))
+}
+
+/// The `wol` directories within `$XDG_CONFIG_DIRS`, in order of preference, holding system-wide
+/// configuration.
+pub fn system_config() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        return Vec::new();
+    }
+
+    let dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    dirs.split(':')
+        // relative paths are invalid and must be ignored
+        .filter(|dir| Path::new(dir).is_absolute())
+        .map(|dir| Path::new(dir).join("wol"))
+        .collect()
+}
+
+/// `$XDG_CACHE_HOME/wol`, or `%LOCALAPPDATA%\wol\cache` on Windows, for files that can be
+/// recreated.
+pub fn cache() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return Some(known_folder("LOCALAPPDATA")?.join("wol").join("cache"));
+    }
+    Some(base("XDG_CACHE_HOME", ".cache")?.join("wol"))
+}
+
+/// `$XDG_STATE_HOME/wol`, or `%LOCALAPPDATA%\wol` on Windows, for history that should persist
+/// but is not worth backing up.
+pub fn state() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return Some(known_folder("LOCALAPPDATA")?.join("wol"));
+    }
+    Some(base("XDG_STATE_HOME", ".local/state")?.join("wol"))
+}
@@ -0,0 +1,119 @@
+//! Durations written for humans, e.g. 90s, 2m 30s or 1h, as accepted by humantime.
+use std::time::Duration;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DurationError {
+    /// Nothing to parse
+    #[error("expected a duration, e.g. 90s or 2m 30s")]
+    Empty,
+
+    /// A number without a unit, or a unit without a number
+    #[error("expected a number followed by a unit at '{0}'")]
+    Expected(String),
+
+    /// An unknown unit
+    #[error("unknown unit '{0}', expected one of ms, s, m, h or d")]
+    UnknownUnit(String),
+
+    /// Too long to be represented
+    #[error("duration is too long")]
+    Overflow,
+}
+
+/// The length of a unit, accepting the spellings of humantime.
+fn unit(unit: &str) -> Option<Duration> {
+    Some(match unit {
+        "ns" | "nsec" => Duration::from_nanos(1),
+        "us" | "usec" => Duration::from_micros(1),
+        "ms" | "msec" => Duration::from_millis(1),
+        "s" | "sec" | "secs" | "second" | "seconds" => Duration::from_secs(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::from_secs(60),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Duration::from_secs(60 * 60),
+        "d" | "day" | "days" => Duration::from_secs(24 * 60 * 60),
+        _ => return None,
+    })
+}
+
+/// Parses a sequence of numbers followed by units, optionally separated by spaces.
+pub fn parse(input: &str) -> Result<Duration, DurationError> {
+    let mut rest = input.trim_start();
+    if rest.is_empty() {
+        return Err(DurationError::Empty);
+    }
+
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, after) = rest.split_at(digits);
+        let after = after.trim_start();
+        let letters = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let (name, after) = after.split_at(letters);
+        if number.is_empty() || name.is_empty() {
+            return Err(DurationError::Expected(rest.to_string()));
+        }
+
+        let unit = unit(name).ok_or_else(|| DurationError::UnknownUnit(name.to_string()))?;
+        let number = number.parse().map_err(|_| DurationError::Overflow)?;
+        total = unit
+            .checked_mul(number)
+            .and_then(|length| total.checked_add(length))
+            .ok_or(DurationError::Overflow)?;
+        rest = after.trim_start();
+    }
+
+    Ok(total)
+}
+
+/// Formats a duration the way it is parsed, e.g. 2m 30s, dropping any fraction of a second.
+pub fn format(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+
+    let parts = [
+        (secs / (24 * 60 * 60), "d"),
+        (secs / (60 * 60) % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    parts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| format!("{}{}", count, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse("120s"), Ok(Duration::from_secs(120)));
+    assert_eq!(parse("2m 30s"), Ok(Duration::from_secs(150)));
+    assert_eq!(parse("1h30min"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse(" 500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse("2 minutes"), Ok(Duration::from_secs(120)));
+
+    assert_eq!(parse(""), Err(DurationError::Empty));
+    assert_eq!(parse("120"), Err(DurationError::Expected("120".into())));
+    assert_eq!(parse("s"), Err(DurationError::Expected("s".into())));
+    assert_eq!(parse("2w"), Err(DurationError::UnknownUnit("w".into())));
+    assert_eq!(parse("99999999999999999999d"), Err(DurationError::Overflow));
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format(Duration::from_secs(120)), "2m");
+    assert_eq!(format(Duration::from_secs(3723)), "1h 2m 3s");
+    assert_eq!(format(Duration::from_millis(1500)), "1s");
+    assert_eq!(format(Duration::from_millis(250)), "250ms");
+    assert_eq!(
+        parse(&format(Duration::from_secs(90061))),
+        Ok(Duration::from_secs(90061))
+    );
+}
@@ -0,0 +1,58 @@
+//! The error of waking a host, from parsing its MAC address to sending the packet. Requires the
+//! `std` feature.
+use std::io;
+
+use thiserror::Error;
+
+use crate::wol::{PacketError, ParseError};
+
+#[derive(Error, Debug)]
+pub enum WolError {
+    /// The MAC address or SecureOn password is invalid
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    /// Received bytes are not a magic packet
+    #[error(transparent)]
+    Packet(#[from] PacketError),
+
+    /// The socket could not be opened, bound or set up
+    #[error("unable to open socket: {0}")]
+    Bind(io::Error),
+
+    /// The socket could not send the packet
+    #[error("unable to send packet: {0}")]
+    Send(io::Error),
+
+    /// The packet or a reply to it took too long
+    #[error("unable to send packet: timed out")]
+    Timeout,
+
+    /// Any other I/O error
+    #[error(transparent)]
+    Io(io::Error),
+}
+
+impl From<io::Error> for WolError {
+    fn from(err: io::Error) -> WolError {
+        match err.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => WolError::Timeout,
+            _ => WolError::Io(err),
+        }
+    }
+}
+
+#[test]
+fn test_wol_error() {
+    let err: WolError = "aa:bb".parse::<crate::MacAddr>().unwrap_err().into();
+    assert!(matches!(err, WolError::Parse(ParseError::InvalidLength)));
+    assert_eq!(err.to_string(), "invalid length");
+
+    let err = WolError::Send(io::Error::from(io::ErrorKind::PermissionDenied));
+    assert_eq!(err.to_string(), "unable to send packet: permission denied");
+
+    let timed_out: WolError = io::Error::from(io::ErrorKind::WouldBlock).into();
+    assert!(matches!(timed_out, WolError::Timeout));
+    let other: WolError = io::Error::other("no route").into();
+    assert_eq!(other.to_string(), "no route");
+}
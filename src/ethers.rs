@@ -0,0 +1,70 @@
+//! Reads `/etc/ethers`, the traditional database mapping hostnames to MAC addresses used by arp,
+//! rarpd and DHCP servers. Each line holds a MAC address followed by a hostname or IP address:
+//!
+//! ```text
+//! 8:0:20:1:2:3    nas
+//! aa:bb:cc:dd:ee:ff 192.168.1.20  # comments are allowed
+//! ```
+use std::fs;
+use std::io;
+
+use wol_cli::MacAddr;
+
+pub const PATH: &str = "/etc/ethers";
+
+/// Parses the contents of an ethers file into (hostname, MAC) pairs, skipping lines whose MAC
+/// address is invalid. The MAC addresses may have their leading zeroes dropped, as ethers(5)
+/// allows.
+pub fn parse(contents: &str) -> Vec<(String, MacAddr)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let (mac, name) = (words.next()?, words.next()?);
+            let mac = mac
+                .split(':')
+                .map(|octet| format!("{:0>2}", octet))
+                .collect::<Vec<_>>()
+                .join(":");
+            Some((name.to_string(), mac.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Looks up the MAC address of a hostname in `/etc/ethers`. A missing file is treated as an
+/// empty one.
+pub fn lookup(name: &str) -> io::Result<Option<MacAddr>> {
+    let contents = match fs::read_to_string(PATH) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    Ok(parse(&contents)
+        .into_iter()
+        .find(|(host, _)| host.eq_ignore_ascii_case(name))
+        .map(|(_, mac)| mac))
+}
+
+#[test]
+fn test_parse() {
+    let contents = "# static entries\n\
+                    8:0:20:1:2:3\tnas\n\
+                    aa:bb:cc:dd:ee:ff 192.168.1.20 # printer\n\
+                    nope desktop\n\
+                    11:22:33:44:55:66\n";
+    assert_eq!(
+        parse(contents),
+        [
+            (
+                "nas".to_string(),
+                MacAddr([0x08, 0, 0x20, 0x01, 0x02, 0x03])
+            ),
+            (
+                "192.168.1.20".to_string(),
+                MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff])
+            ),
+        ]
+    );
+}
@@ -0,0 +1,245 @@
+//! The gRPC server of `serve --grpc`, for the API described by proto/wol.proto. The service is
+//! generated by tonic at build time and answered on a tokio runtime of its own, handing every
+//! call to a [`Handler`] on a thread where it may block, e.g. while probing hosts.
+use std::io;
+use std::net::{IpAddr, TcpListener};
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response};
+
+pub use tonic::Code;
+
+/// The messages and service of wol.proto.
+pub mod proto {
+    tonic::include_proto!("wolcli.v1");
+}
+
+use proto::wol_server::{Wol, WolServer};
+use proto::{HostStatus, ListHostsReply, ListHostsRequest, StatusRequest, WakeReply, WakeRequest};
+
+/// How many statuses of the Status method may wait for a slow client.
+const STATUS_BACKLOG: usize = 16;
+
+/// The status a call fails with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub code: Code,
+    pub message: String,
+}
+
+impl Status {
+    pub fn new(code: Code, message: impl Into<String>) -> Status {
+        Status {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Status> for tonic::Status {
+    fn from(status: Status) -> tonic::Status {
+        tonic::Status::new(status.code, status.message)
+    }
+}
+
+/// A call to a method, with what authorizes it.
+#[derive(Debug)]
+pub struct Call<T> {
+    /// The IP address the call came from.
+    pub client: Option<IpAddr>,
+
+    /// The bearer token of the authorization metadata, if any.
+    pub token: Option<String>,
+
+    pub message: T,
+}
+
+impl<T> Call<T> {
+    fn new(request: Request<T>) -> Call<T> {
+        let client = request.remote_addr().map(|addr| addr.ip());
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim().to_string());
+        Call {
+            client,
+            token,
+            message: request.into_inner(),
+        }
+    }
+}
+
+/// The statuses sent by the Status method, for as long as the client keeps the call open.
+pub struct Statuses(mpsc::Sender<Result<HostStatus, tonic::Status>>);
+
+impl Statuses {
+    /// Whether the client is still there to receive statuses.
+    pub fn is_open(&self) -> bool {
+        !self.0.is_closed()
+    }
+
+    pub fn send(&self, status: HostStatus) -> io::Result<()> {
+        self.0
+            .blocking_send(Ok(status))
+            .map_err(|_| io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+/// Answers the methods of the service. Each is called on a thread it may block.
+pub trait Handler: Send + Sync + 'static {
+    fn wake(&self, call: Call<WakeRequest>) -> Result<WakeReply, Status>;
+
+    fn list_hosts(&self, call: Call<ListHostsRequest>) -> Result<ListHostsReply, Status>;
+
+    /// Sends the statuses of hosts until the client cancels the call, or the call fails.
+    fn status(&self, call: Call<StatusRequest>, statuses: &Statuses) -> Result<(), Status>;
+}
+
+struct Service<H>(Arc<H>);
+
+/// Runs `method` of the handler where it may block, answering with what it returns.
+async fn answer<H: Handler, T: Send + 'static, R: Send + 'static>(
+    handler: &Arc<H>,
+    request: Request<T>,
+    method: fn(&H, Call<T>) -> Result<R, Status>,
+) -> Result<Response<R>, tonic::Status> {
+    let handler = handler.clone();
+    let call = Call::new(request);
+    match tokio::task::spawn_blocking(move || method(&handler, call)).await {
+        Ok(Ok(reply)) => Ok(Response::new(reply)),
+        Ok(Err(status)) => Err(status.into()),
+        Err(err) => Err(tonic::Status::internal(err.to_string())),
+    }
+}
+
+#[tonic::async_trait]
+impl<H: Handler> Wol for Service<H> {
+    async fn wake(
+        &self,
+        request: Request<WakeRequest>,
+    ) -> Result<Response<WakeReply>, tonic::Status> {
+        answer(&self.0, request, H::wake).await
+    }
+
+    async fn list_hosts(
+        &self,
+        request: Request<ListHostsRequest>,
+    ) -> Result<Response<ListHostsReply>, tonic::Status> {
+        answer(&self.0, request, H::list_hosts).await
+    }
+
+    type StatusStream = ReceiverStream<Result<HostStatus, tonic::Status>>;
+
+    async fn status(
+        &self,
+        request: Request<StatusRequest>,
+    ) -> Result<Response<Self::StatusStream>, tonic::Status> {
+        let (sender, receiver) = mpsc::channel(STATUS_BACKLOG);
+        let handler = self.0.clone();
+        let call = Call::new(request);
+        tokio::task::spawn_blocking(move || {
+            let statuses = Statuses(sender);
+            // a failure ends the stream with its status, after those already sent
+            if let Err(status) = handler.status(call, &statuses) {
+                let _ = statuses.0.blocking_send(Err(status.into()));
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Answers the calls of clients connecting to `listener` with `handler`, until accepting fails.
+pub fn serve(listener: TcpListener, handler: Arc<impl Handler>) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        let incoming =
+            TcpIncoming::from_listener(listener, true, None).map_err(io::Error::other)?;
+        Server::builder()
+            .add_service(WolServer::new(Service(handler)))
+            .serve_with_incoming(incoming)
+            .await
+            .map_err(io::Error::other)
+    })
+}
+
+#[test]
+fn test_service() {
+    struct Refusing;
+
+    impl Handler for Refusing {
+        fn wake(&self, call: Call<WakeRequest>) -> Result<WakeReply, Status> {
+            match call.token.as_deref() {
+                Some("secret") => Ok(WakeReply {
+                    host: call.message.name,
+                    ..Default::default()
+                }),
+                _ => Err(Status::new(
+                    Code::Unauthenticated,
+                    "a valid bearer token is required",
+                )),
+            }
+        }
+
+        fn list_hosts(&self, _: Call<ListHostsRequest>) -> Result<ListHostsReply, Status> {
+            Ok(ListHostsReply::default())
+        }
+
+        fn status(&self, call: Call<StatusRequest>, statuses: &Statuses) -> Result<(), Status> {
+            for name in call.message.names {
+                let status = HostStatus {
+                    name,
+                    ..Default::default()
+                };
+                if statuses.send(status).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(Status::new(Code::NotFound, "no more hosts"))
+        }
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let service = Service(Arc::new(Refusing));
+    runtime.block_on(async {
+        let wake = |token: Option<&str>| {
+            let mut request = Request::new(WakeRequest { name: "nas".into() });
+            if let Some(token) = token {
+                let value = format!("Bearer {}", token).parse().unwrap();
+                request.metadata_mut().insert("authorization", value);
+            }
+            service.wake(request)
+        };
+        assert_eq!(wake(Some("secret")).await.unwrap().into_inner().host, "nas");
+        let err = wake(None).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+
+        let request = Request::new(StatusRequest {
+            names: vec!["nas".into(), "desktop".into()],
+            interval: 0,
+        });
+        let mut statuses = service
+            .status(request)
+            .await
+            .unwrap()
+            .into_inner()
+            .into_inner();
+        assert_eq!(statuses.recv().await.unwrap().unwrap().name, "nas");
+        assert_eq!(statuses.recv().await.unwrap().unwrap().name, "desktop");
+        let err = statuses.recv().await.unwrap().unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+        assert!(statuses.recv().await.is_none());
+    });
+}
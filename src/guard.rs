@@ -0,0 +1,250 @@
+//! Protection of the daemons answering the network, so that an exposed endpoint cannot be used
+//! to spray broadcasts: an allowlist of the hosts they may wake, and a limit on how often each
+//! client may have a host woken.
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+use wol_cli::MacAddr;
+
+use crate::config::Config;
+use crate::duration;
+
+/// How many clients are remembered before those whose bucket has filled up again are forgotten.
+const MAX_CLIENTS: usize = 1024;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RateError {
+    /// No slash between the count and the period
+    #[error("expected a count and a period, e.g. 5/min or 20/h")]
+    Expected,
+
+    /// The count is not a positive number
+    #[error("invalid count '{0}'")]
+    Count(String),
+
+    /// The period is neither a unit nor a duration
+    #[error("invalid period '{0}', expected a unit such as s, min or h, or a duration")]
+    Period(String),
+}
+
+/// How many wakes a client may ask for in a period, e.g. 5/min.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub count: u32,
+    pub period: Duration,
+}
+
+impl FromStr for Rate {
+    type Err = RateError;
+
+    fn from_str(s: &str) -> Result<Rate, RateError> {
+        let (count, period) = s.split_once('/').ok_or(RateError::Expected)?;
+        let count = match count.trim().parse() {
+            Ok(count) if count > 0 => count,
+            _ => return Err(RateError::Count(count.to_string())),
+        };
+        // a bare unit means one of it, as in 5/min
+        let period = period.trim();
+        let spelled = if period.starts_with(|c: char| c.is_ascii_digit()) {
+            period.to_string()
+        } else {
+            format!("1{}", period)
+        };
+        match duration::parse(&spelled) {
+            Ok(period) if !period.is_zero() => Ok(Rate { count, period }),
+            _ => Err(RateError::Period(period.to_string())),
+        }
+    }
+}
+
+/// Why a daemon refused to wake a host.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The host is not on the allowlist
+    #[error("not allowed to wake this host")]
+    NotAllowed,
+
+    /// The client asked for too many wakes
+    #[error("too many wakes, try again later")]
+    RateLimited,
+}
+
+/// A token bucket for each client, holding up to `count` wakes and refilling at the rate.
+struct Limiter {
+    rate: Rate,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>,
+}
+
+impl Limiter {
+    /// Takes a wake from the bucket of `client` at `now`, if there is one left.
+    fn take(&self, client: IpAddr, now: Instant) -> bool {
+        let capacity = f64::from(self.rate.count);
+        let per_second = capacity / self.rate.period.as_secs_f64();
+        let refilled = |(tokens, since): (f64, Instant)| {
+            let elapsed = now.saturating_duration_since(since).as_secs_f64();
+            (tokens + elapsed * per_second).min(capacity)
+        };
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(&client) {
+            // a full bucket is the same as none at all
+            buckets.retain(|_, bucket| refilled(*bucket) < capacity);
+        }
+        let bucket = buckets.entry(client).or_insert((capacity, now));
+        let tokens = refilled(*bucket);
+        let taken = tokens >= 1.0;
+        *bucket = (if taken { tokens - 1.0 } else { tokens }, now);
+        taken
+    }
+}
+
+/// Decides which wakes a daemon goes through with.
+pub struct Guard {
+    allow: Vec<String>,
+    limiter: Option<Limiter>,
+}
+
+impl Guard {
+    /// A guard allowing only the hosts in `allow`, by name, group or MAC address, or any host if
+    /// it is empty, and limiting every client to `rate`, if given.
+    pub fn new(allow: &[String], rate: Option<Rate>) -> Guard {
+        Guard {
+            allow: allow.to_vec(),
+            limiter: rate.map(|rate| Limiter {
+                rate,
+                buckets: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Whether the allowlist names hosts or groups, which have to be looked up in the registry.
+    pub fn needs_registry(&self) -> bool {
+        self.allow
+            .iter()
+            .any(|entry| entry.parse::<MacAddr>().is_err())
+    }
+
+    /// Whether the host with `mac` is on the allowlist, looking up names in `config`.
+    pub fn allows(&self, mac: MacAddr, config: &Config) -> bool {
+        self.allow.is_empty()
+            || self.allow.iter().any(|entry| {
+                entry.parse() == Ok(mac)
+                    || config.host(entry).is_some_and(|host| host.mac == mac)
+                    || config.group(entry).iter().any(|host| host.mac == mac)
+            })
+    }
+
+    /// Decides whether `client` may have the host with `mac` woken, counting the wake against
+    /// its rate limit if so.
+    pub fn check(&self, client: IpAddr, mac: MacAddr, config: &Config) -> Result<(), Refusal> {
+        if !self.allows(mac, config) {
+            return Err(Refusal::NotAllowed);
+        }
+        match &self.limiter {
+            Some(limiter) if !limiter.take(client, Instant::now()) => Err(Refusal::RateLimited),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_rate() {
+    let rate = |count, secs| {
+        Ok(Rate {
+            count,
+            period: Duration::from_secs(secs),
+        })
+    };
+    assert_eq!("5/min".parse(), rate(5, 60));
+    assert_eq!("20/h".parse(), rate(20, 3600));
+    assert_eq!(" 3 / 10s".parse(), rate(3, 10));
+    assert_eq!("1/1h 30m".parse(), rate(1, 5400));
+    assert_eq!("5".parse::<Rate>(), Err(RateError::Expected));
+    assert_eq!("0/min".parse::<Rate>(), Err(RateError::Count("0".into())));
+    assert_eq!("x/min".parse::<Rate>(), Err(RateError::Count("x".into())));
+    assert_eq!(
+        "5/week".parse::<Rate>(),
+        Err(RateError::Period("week".into()))
+    );
+    assert_eq!("5/0s".parse::<Rate>(), Err(RateError::Period("0s".into())));
+}
+
+#[test]
+fn test_limiter() {
+    let limiter = Limiter {
+        rate: "2/10s".parse().unwrap(),
+        buckets: Mutex::new(HashMap::new()),
+    };
+    let start = Instant::now();
+    let phone: IpAddr = "192.0.2.7".parse().unwrap();
+    let laptop: IpAddr = "192.0.2.8".parse().unwrap();
+    assert!(limiter.take(phone, start));
+    assert!(limiter.take(phone, start));
+    assert!(!limiter.take(phone, start));
+    assert!(limiter.take(laptop, start));
+    // one wake comes back every five seconds
+    assert!(!limiter.take(phone, start + Duration::from_secs(4)));
+    assert!(limiter.take(phone, start + Duration::from_secs(6)));
+    assert!(!limiter.take(phone, start + Duration::from_secs(7)));
+    assert!(limiter.take(phone, start + Duration::from_secs(60)));
+    assert!(limiter.take(phone, start + Duration::from_secs(60)));
+    assert!(!limiter.take(phone, start + Duration::from_secs(60)));
+}
+
+#[test]
+fn test_allows() {
+    let config = Config::parse(
+        r#"
+        [hosts.nas]
+        mac = "aa:bb:cc:dd:ee:01"
+        groups = ["servers"]
+
+        [hosts.desktop]
+        mac = "aa:bb:cc:dd:ee:02"
+
+        [hosts.printer]
+        mac = "aa:bb:cc:dd:ee:03"
+        "#,
+    )
+    .unwrap();
+    let mac = |mac: &str| mac.parse::<MacAddr>().unwrap();
+    assert!(Guard::new(&[], None).allows(mac("aa:bb:cc:dd:ee:03"), &config));
+    assert!(!Guard::new(&[], None).needs_registry());
+
+    let guard = Guard::new(
+        &[
+            "servers".into(),
+            "desktop".into(),
+            "AA-BB-CC-DD-EE-09".into(),
+        ],
+        None,
+    );
+    assert!(guard.needs_registry());
+    assert!(guard.allows(mac("aa:bb:cc:dd:ee:01"), &config));
+    assert!(guard.allows(mac("AA:BB:CC:DD:EE:02"), &config));
+    assert!(guard.allows(mac("aa:bb:cc:dd:ee:09"), &config));
+    assert!(!guard.allows(mac("aa:bb:cc:dd:ee:03"), &config));
+
+    let guard = Guard::new(&["aa:bb:cc:dd:ee:01".into()], "1/h".parse().ok());
+    assert!(!guard.needs_registry());
+    let client = "192.0.2.7".parse().unwrap();
+    let printer = mac("aa:bb:cc:dd:ee:03");
+    assert_eq!(
+        guard.check(client, printer, &config),
+        Err(Refusal::NotAllowed)
+    );
+    assert_eq!(
+        guard.check(client, mac("aa:bb:cc:dd:ee:01"), &config),
+        Ok(())
+    );
+    assert_eq!(
+        guard.check(client, mac("aa:bb:cc:dd:ee:01"), &config),
+        Err(Refusal::RateLimited)
+    );
+}
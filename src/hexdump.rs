@@ -0,0 +1,47 @@
+//! Hex dumps of packets, as printed by --dry-run.
+use std::fmt::Write;
+
+/// How many bytes are shown on each line.
+const WIDTH: usize = 16;
+
+/// Formats bytes as lines of their offset, sixteen bytes in hex in two groups of eight, and
+/// those bytes as ASCII, with a dot for each that is not printable, as `xxd` and `hexdump -C` do.
+pub fn format(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for (line, chunk) in bytes.chunks(WIDTH).enumerate() {
+        let _ = write!(output, "{:04x} ", line * WIDTH);
+        for i in 0..WIDTH {
+            if i % 8 == 0 {
+                output.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => {
+                    let _ = write!(output, "{:02x} ", byte);
+                }
+                None => output.push_str("   "),
+            }
+        }
+        output.push(' ');
+        output.extend(chunk.iter().map(|&byte| match byte {
+            0x20..=0x7e => byte as char,
+            _ => '.',
+        }));
+        output.push('\n');
+    }
+    output
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format(&[]), "");
+    let bytes: Vec<u8> = (0x3a..0x4d).collect();
+    assert_eq!(
+        format(&bytes),
+        "0000  3a 3b 3c 3d 3e 3f 40 41  42 43 44 45 46 47 48 49  :;<=>?@ABCDEFGHI\n\
+         0010  4a 4b 4c                                          JKL\n"
+    );
+    assert_eq!(
+        format(&[0xff, 0x00, b'a']),
+        "0000  ff 00 61                                          ..a\n"
+    );
+}
@@ -0,0 +1,122 @@
+//! Remembers when each host of the registry was last woken, in `$XDG_STATE_HOME/wol/history` (or
+//! `~/.local/state/wol/history`). Each line holds a Unix timestamp followed by a host name.
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dirs;
+use crate::schedule::Time;
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::state()?.join("history"))
+}
+
+fn parse(contents: &str) -> HashMap<String, u64> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (timestamp, name) = line.split_once(' ')?;
+            Some((name.to_string(), timestamp.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Reads the time each host was last woken at, in seconds since the Unix epoch. A missing file
+/// is treated as an empty history.
+pub fn load(path: &Path) -> io::Result<HashMap<String, u64>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse(&contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Records that the named hosts were just woken.
+pub fn record(path: &Path, names: &[&str]) -> io::Result<()> {
+    let mut history = load(path)?;
+    let now = now();
+    for name in names {
+        history.insert(name.to_string(), now);
+    }
+    save(path, history)
+}
+
+/// Moves the history of a host that was renamed.
+pub fn rename(path: &Path, old: &str, new: &str) -> io::Result<()> {
+    let mut history = load(path)?;
+    let Some(timestamp) = history.remove(old) else {
+        return Ok(());
+    };
+    history.insert(new.to_string(), timestamp);
+    save(path, history)
+}
+
+fn save(path: &Path, history: HashMap<String, u64>) -> io::Result<()> {
+    let mut entries: Vec<_> = history.into_iter().collect();
+    entries.sort();
+    let contents: String = entries
+        .iter()
+        .map(|(name, timestamp)| format!("{} {}\n", timestamp, name))
+        .collect();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
+
+/// Describes how long ago a timestamp was, e.g. "5 minutes ago".
+pub fn ago(timestamp: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(timestamp);
+    let (count, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+/// Formats a timestamp as a UTC date and time, e.g. "2024-06-10T06:13:20Z".
+pub fn format_time(timestamp: u64) -> String {
+    let time = Time::utc(timestamp);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year, time.month, time.day, time.hour, time.minute, time.second
+    )
+}
+
+#[test]
+fn test_parse() {
+    let history = parse("1718000000 nas\n1718000100 living room\nnope\n");
+    assert_eq!(history.get("nas"), Some(&1718000000));
+    assert_eq!(history.get("living room"), Some(&1718000100));
+    assert_eq!(history.len(), 2);
+}
+
+#[test]
+fn test_ago() {
+    assert_eq!(ago(1000, 1030), "just now");
+    assert_eq!(ago(1000, 1060), "1 minute ago");
+    assert_eq!(ago(1000, 1000 + 7200), "2 hours ago");
+    assert_eq!(ago(1000, 1000 + 86400 * 3), "3 days ago");
+    assert_eq!(ago(2000, 1000), "just now");
+}
+
+#[test]
+fn test_format_time() {
+    assert_eq!(format_time(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_time(1718000000), "2024-06-10T06:13:20Z");
+    assert_eq!(format_time(951782400), "2000-02-29T00:00:00Z");
+    assert_eq!(format_time(4107542399), "2100-02-28T23:59:59Z");
+}
@@ -0,0 +1,142 @@
+//! Home Assistant MQTT discovery, through which the hosts of the registry appear in Home
+//! Assistant as devices with a button waking them and a sensor telling whether they are up.
+
+use crate::config::Host;
+use crate::json::Value;
+
+pub struct Discovery {
+    /// The discovery prefix Home Assistant subscribes to, "homeassistant" by default.
+    prefix: String,
+
+    /// The topic the hosts are woken under, e.g. wol for the topic filter wol/#.
+    base: String,
+}
+
+impl Discovery {
+    /// Discovery for the hosts woken by messages on `topic`, which must be a filter matching
+    /// one topic per host, e.g. wol/# or wol/+.
+    pub fn new(prefix: &str, topic: &str) -> Option<Discovery> {
+        let base = topic
+            .strip_suffix("/#")
+            .or_else(|| topic.strip_suffix("/+"))
+            .filter(|base| !base.is_empty() && !base.contains(['#', '+']))?;
+        Some(Discovery {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            base: base.to_string(),
+        })
+    }
+
+    /// Where "online" is published while connected, and "offline" by the broker once the
+    /// connection is lost.
+    pub fn availability_topic(&self) -> String {
+        format!("{}/status", self.base)
+    }
+
+    /// Where whether the host is up is published, as ON or OFF.
+    pub fn state_topic(&self, host: &Host) -> String {
+        format!("{}/{}/state", self.base, host.name)
+    }
+
+    /// Whether a message was published by us on `topic`, rather than asking to wake a host.
+    pub fn is_own(&self, topic: &str) -> bool {
+        topic == self.availability_topic()
+            || topic
+                .strip_prefix(&self.base)
+                .is_some_and(|topic| topic.starts_with('/') && topic.ends_with("/state"))
+    }
+
+    /// The topics and payloads of the entities of a host, to be published retained.
+    pub fn configs(&self, host: &Host) -> Vec<(String, String)> {
+        // entity ids must be alphanumeric, so the MAC address identifies the host rather
+        // than its name
+        let mac = host.mac.to_string();
+        let id = format!("wol_{}", mac.replace(':', ""));
+        let device = Value::object([
+            ("identifiers", Value::Array(vec![id.as_str().into()])),
+            ("name", host.name.as_str().into()),
+            (
+                "connections",
+                Value::Array(vec![Value::Array(vec!["mac".into(), mac.as_str().into()])]),
+            ),
+        ]);
+        let button = Value::object([
+            ("name", "Wake".into()),
+            ("unique_id", format!("{}_wake", id).as_str().into()),
+            (
+                "command_topic",
+                format!("{}/{}", self.base, host.name).as_str().into(),
+            ),
+            ("payload_press", host.name.as_str().into()),
+            (
+                "availability_topic",
+                self.availability_topic().as_str().into(),
+            ),
+            ("device", device.clone()),
+        ]);
+        let sensor = Value::object([
+            ("name", "Online".into()),
+            ("unique_id", format!("{}_online", id).as_str().into()),
+            ("device_class", "connectivity".into()),
+            ("state_topic", self.state_topic(host).as_str().into()),
+            ("payload_on", "ON".into()),
+            ("payload_off", "OFF".into()),
+            (
+                "availability_topic",
+                self.availability_topic().as_str().into(),
+            ),
+            ("device", device),
+        ]);
+        vec![
+            (
+                format!("{}/button/{}/wake/config", self.prefix, id),
+                button.to_string(),
+            ),
+            (
+                format!("{}/binary_sensor/{}/online/config", self.prefix, id),
+                sensor.to_string(),
+            ),
+        ]
+    }
+}
+
+#[test]
+fn test_discovery() {
+    assert!(Discovery::new("homeassistant", "wol").is_none());
+    assert!(Discovery::new("homeassistant", "#").is_none());
+    assert!(Discovery::new("homeassistant", "wol/+/wake").is_none());
+
+    let discovery = Discovery::new("homeassistant/", "home/wol/+").unwrap();
+    let host = Host {
+        name: "nas".into(),
+        mac: "AA-BB-CC-DD-EE-FF".parse().unwrap(),
+        ..Default::default()
+    };
+    assert_eq!(discovery.availability_topic(), "home/wol/status");
+    assert_eq!(discovery.state_topic(&host), "home/wol/nas/state");
+    assert!(discovery.is_own("home/wol/status"));
+    assert!(discovery.is_own("home/wol/nas/state"));
+    assert!(!discovery.is_own("home/wol/nas"));
+    assert!(!discovery.is_own("home/wolf/nas/state"));
+
+    let configs = discovery.configs(&host);
+    assert_eq!(
+        configs[0].0,
+        "homeassistant/button/wol_aabbccddeeff/wake/config"
+    );
+    assert_eq!(
+        configs[0].1,
+        concat!(
+            r#"{"name":"Wake","unique_id":"wol_aabbccddeeff_wake","command_topic":"home/wol/nas","#,
+            r#""payload_press":"nas","availability_topic":"home/wol/status","#,
+            r#""device":{"identifiers":["wol_aabbccddeeff"],"name":"nas","#,
+            r#""connections":[["mac","aa:bb:cc:dd:ee:ff"]]}}"#
+        )
+    );
+    assert_eq!(
+        configs[1].0,
+        "homeassistant/binary_sensor/wol_aabbccddeeff/online/config"
+    );
+    assert!(configs[1]
+        .1
+        .contains(r#""state_topic":"home/wol/nas/state","payload_on":"ON""#));
+}
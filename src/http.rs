@@ -0,0 +1,298 @@
+//! A small HTTP/1.1 server for `serve`, answering one request per connection, which is all that
+//! scripts, phones and home automation systems need.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crate::json;
+
+/// How long a client may take to send its request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest request head and body read, as nothing served needs more.
+const MAX_LEN: u64 = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+
+    /// The percent-decoded segments of the path, e.g. ["wake", "living room"] for
+    /// /wake/living%20room. The query string is left out.
+    pub segments: Vec<String>,
+
+    /// The bearer token of the Authorization header, if any.
+    pub token: Option<String>,
+
+    /// The address of the client, when it is known.
+    pub client: Option<IpAddr>,
+}
+
+impl Request {
+    /// Whether the request carries one of the given tokens. They are compared in constant time,
+    /// so that how long the answer takes does not tell how much of a token was guessed right.
+    pub fn is_authorized(&self, tokens: &[String]) -> bool {
+        is_authorized(self.token.as_deref(), tokens)
+    }
+}
+
+/// Whether `token` is one of `tokens`, compared in constant time.
+pub fn is_authorized(token: Option<&str>, tokens: &[String]) -> bool {
+    let Some(token) = token else {
+        return false;
+    };
+    tokens.iter().fold(false, |authorized, expected| {
+        authorized | constant_time_eq(token.as_bytes(), expected.as_bytes())
+    })
+}
+
+/// Compares two byte strings in a time that depends only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: &json::Value) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn html(body: &str) -> Response {
+        Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn text(body: &str) -> Response {
+        Response {
+            status: 200,
+            content_type: "text/plain; charset=utf-8",
+            body: body.to_string(),
+        }
+    }
+
+    /// A response with a JSON body like `{"error":"no host named 'nas'"}`.
+    pub fn error(status: u16, message: &str) -> Response {
+        Response::json(status, &json::Value::object([("error", message.into())]))
+    }
+
+    fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            429 => "Too Many Requests",
+            _ if self.status >= 500 => "Internal Server Error",
+            _ => "",
+        };
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, reason)?;
+        if self.status == 401 {
+            write!(writer, "WWW-Authenticate: Bearer realm=\"wol-cli\"\r\n")?;
+        }
+        write!(
+            writer,
+            "Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.content_type,
+            self.body.len(),
+            self.body
+        )
+    }
+}
+
+/// Decodes the %XX escapes of a path segment, returning `None` if they are malformed.
+fn decode(segment: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let hex = [bytes.next()?, bytes.next()?];
+        decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Reads a request, skipping its body and the headers not needed, or returning `None` if it is malformed.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    let mut reader = reader.take(MAX_LEN);
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    if !version.starts_with("HTTP/1.") || !target.starts_with('/') {
+        return Ok(None);
+    }
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let Some(segments) = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(decode)
+        .collect()
+    else {
+        return Ok(None);
+    };
+    let method = method.to_string();
+
+    let mut len = 0;
+    let mut token = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            let Ok(value) = value.parse() else {
+                return Ok(None);
+            };
+            len = value;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            token = value
+                .split_once(' ')
+                .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                .map(|(_, token)| token.trim().to_string());
+        }
+    }
+    // the body is read so that closing the connection does not reset it before the response
+    io::copy(&mut reader.take(len), &mut io::sink())?;
+    Ok(Some(Request {
+        method,
+        segments,
+        token,
+        client: None,
+    }))
+}
+
+fn handle_connection(stream: TcpStream, handler: &impl Fn(&Request) -> Response) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let response = match read_request(&mut BufReader::new(&stream))? {
+        Some(mut request) => {
+            request.client = stream.peer_addr().ok().map(|addr| addr.ip());
+            handler(&request)
+        }
+        None => Response::error(400, "malformed request"),
+    };
+    response.write_to(&stream)
+}
+
+/// Answers the requests of clients connecting to `listener` with `handler`, until accepting
+/// fails.
+pub fn serve(
+    listener: &TcpListener,
+    handler: impl Fn(&Request) -> Response + Sync,
+) -> io::Result<()> {
+    thread::scope(|scope| loop {
+        let (stream, _) = listener.accept()?;
+        let handler = &handler;
+        // clients that break off are no concern of the others
+        scope.spawn(move || {
+            let _ = handle_connection(stream, handler);
+        });
+    })
+}
+
+/// Formats the request line and headers of a request, for tests.
+#[cfg(test)]
+fn format_request(method: &str, target: &str, body: &str) -> String {
+    format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        target,
+        body.len(),
+        body
+    )
+}
+
+#[test]
+fn test_read_request() {
+    let request = format_request("POST", "/wake/living%20room?now=1", "{}");
+    let mut reader = io::Cursor::new(request.as_bytes());
+    assert_eq!(
+        read_request(&mut reader).unwrap(),
+        Some(Request {
+            method: "POST".into(),
+            segments: vec!["wake".into(), "living room".into()],
+            token: None,
+            client: None,
+        })
+    );
+    assert_eq!(reader.position() as usize, request.len());
+
+    let request = "GET /hosts HTTP/1.1\r\nauthorization: Bearer  s3cret\r\n\r\n";
+    let request = read_request(&mut io::Cursor::new(request))
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.token.as_deref(), Some("s3cret"));
+    assert!(request.is_authorized(&["phone".into(), "s3cret".into()]));
+    assert!(!request.is_authorized(&["s3cre".into(), "s3cret!".into()]));
+    assert!(!request.is_authorized(&[]));
+
+    let request = format_request("GET", "//hosts/", "");
+    let request = read_request(&mut io::Cursor::new(request)).unwrap();
+    assert_eq!(request.unwrap().segments, ["hosts"]);
+
+    for malformed in [
+        "GET /hosts\r\n\r\n".to_string(),
+        format_request("GET", "/wake/%zz", ""),
+        "GET /hosts HTTP/1.1\r\nHost: local".to_string(),
+    ] {
+        let request = read_request(&mut io::Cursor::new(malformed)).unwrap();
+        assert_eq!(request, None);
+    }
+}
+
+#[test]
+fn test_serve() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        serve(&listener, |request| match request.segments.as_slice() {
+            [hosts] if hosts == "hosts" => Response::json(200, &json::Value::Array(Vec::new())),
+            _ => Response::error(404, "not found"),
+        })
+    });
+
+    let get = |target: &str| {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(format_request("GET", target, "").as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = get("/hosts");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("Content-Length: 2\r\nConnection: close\r\n\r\n[]"));
+    let response = get("/nope");
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"error\":\"not found\"}"));
+}
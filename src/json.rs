@@ -0,0 +1,351 @@
+//! A minimal JSON reader and writer for machine-readable input and output. Numbers are limited
+//! to integers.
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+#[derive(Error, Debug)]
+#[error("line {line}: {message}")]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Value {
+    /// The value of the named member, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// An object with the given members, in order.
+    pub fn object<const N: usize>(members: [(&str, Value); N]) -> Value {
+        Value::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Writes a value over several lines, indenting nested arrays and objects by two spaces.
+fn write_pretty(f: &mut fmt::Formatter<'_>, value: &Value, indent: usize) -> fmt::Result {
+    let (open, close, len) = match value {
+        Value::Array(values) if !values.is_empty() => ('[', ']', values.len()),
+        Value::Object(members) if !members.is_empty() => ('{', '}', members.len()),
+        value => return write!(f, "{}", value),
+    };
+
+    writeln!(f, "{}", open)?;
+    for i in 0..len {
+        write!(f, "{:width$}", "", width = indent + 2)?;
+        match value {
+            Value::Array(values) => write_pretty(f, &values[i], indent + 2)?,
+            Value::Object(members) => {
+                write_string(f, &members[i].0)?;
+                write!(f, ": ")?;
+                write_pretty(f, &members[i].1, indent + 2)?;
+            }
+            _ => unreachable!(),
+        }
+        writeln!(f, "{}", if i + 1 < len { "," } else { "" })?;
+    }
+    write!(f, "{:width$}{}", "", close, width = indent)
+}
+
+/// Formats the value on a single line, or indented over several lines with `{:#}`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return write_pretty(f, self, 0);
+        }
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SyntaxError> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.error(format!("expected '{}', found '{}'", expected, c)),
+            None => self.error(format!("expected '{}'", expected)),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, SyntaxError> {
+        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(n) if hex.len() == 4 => Ok(n),
+            _ => self.error(format!("invalid escape '\\u{}'", hex)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None => return self.error("unterminated string"),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let mut code = self.hex4()?;
+                        // characters outside the basic plane are escaped as surrogate pairs
+                        if (0xd800..0xdc00).contains(&code) {
+                            if self.next() != Some('\\') || self.next() != Some('u') {
+                                return self.error("unpaired surrogate");
+                            }
+                            let low = self.hex4()?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                        }
+                        match char::from_u32(code) {
+                            Some(c) => s.push(c),
+                            None => return self.error("invalid unicode escape"),
+                        }
+                    }
+                    Some(c) => return self.error(format!("invalid escape '\\{}'", c)),
+                    None => return self.error("unterminated string"),
+                },
+                Some(c) if c.is_control() => return self.error("control character in string"),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SyntaxError> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&']') {
+                    self.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return self.error("expected ',' or ']' in array"),
+                    }
+                }
+            }
+            Some('{') => {
+                self.next();
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'}') {
+                    self.next();
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Object(members)),
+                        _ => return self.error("expected ',' or '}' in object"),
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                        break;
+                    }
+                    word.push(c);
+                    self.next();
+                }
+                match word.as_str() {
+                    "null" => Ok(Value::Null),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "" => self.error("expected a value"),
+                    _ => match word.parse() {
+                        Ok(n) => Ok(Value::Number(n)),
+                        Err(_) => self.error(format!("invalid value '{}'", word)),
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Parses a JSON document.
+pub fn parse(input: &str) -> Result<Value, SyntaxError> {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+        line: 1,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.next() {
+        None => Ok(value),
+        Some(c) => parser.error(format!("unexpected '{}' after the document", c)),
+    }
+}
+
+#[test]
+fn test_display() {
+    let value = Value::Array(vec![
+        Value::object([
+            ("name", "living \"room\"".into()),
+            ("port", Value::Number(9)),
+            ("note", None::<&str>.into()),
+        ]),
+        Value::Array(vec![]),
+    ]);
+    assert_eq!(
+        value.to_string(),
+        r#"[{"name":"living \"room\"","port":9,"note":null},[]]"#
+    );
+    assert_eq!(
+        format!("{:#}", value),
+        "[\n  {\n    \"name\": \"living \\\"room\\\"\",\n    \"port\": 9,\n    \"note\": null\n  },\n  []\n]"
+    );
+}
+
+#[test]
+fn test_parse() {
+    let value = parse(
+        r#"{
+  "hosts": [
+    {"name": "living \"room\"", "port": 9, "on": true, "note": null, "emoji": "\ud83d\ude00"}
+  ],
+  "empty": {}
+}"#,
+    )
+    .unwrap();
+    let host = &value.get("hosts").unwrap();
+    let Value::Array(hosts) = host else {
+        panic!("hosts is not an array");
+    };
+    assert_eq!(hosts[0].get("name"), Some(&"living \"room\"".into()));
+    assert_eq!(hosts[0].get("port"), Some(&Value::Number(9)));
+    assert_eq!(hosts[0].get("on"), Some(&Value::Bool(true)));
+    assert_eq!(hosts[0].get("note"), Some(&Value::Null));
+    assert_eq!(hosts[0].get("emoji"), Some(&"\u{1f600}".into()));
+    assert_eq!(value.get("empty"), Some(&Value::Object(vec![])));
+
+    let err = parse("{\n\"a\": [1,\n}").unwrap_err();
+    assert_eq!(err.line, 3);
+    assert!(parse("[1] 2").is_err());
+    assert!(parse("\"a").is_err());
+    assert!(parse("1.5").is_err());
+}
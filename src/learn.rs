@@ -0,0 +1,118 @@
+//! Learns the MAC addresses of hosts passively, from the frames they send on the local network,
+//! for networks where scanning is not allowed.
+//!
+//! Only frames the interface receives anyway are seen, i.e. broadcasts like ARP requests and
+//! DHCP, and traffic to this host. That is enough to learn most hosts, as every host sends ARP
+//! requests for its gateway now and then.
+use std::collections::BTreeMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::subnet::Subnet;
+use wol_cli::MacAddr;
+
+use crate::platform;
+
+const ETHERTYPE_ALL: u16 = 0x0003;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// The source MAC and IPv4 address of an ARP or IPv4 frame.
+fn source(frame: &[u8]) -> Option<([u8; 6], Ipv4Addr)> {
+    let mac: [u8; 6] = frame.get(6..12)?.try_into().ok()?;
+    let mut ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+    let mut payload = frame.get(14..)?;
+    if ethertype == ETHERTYPE_VLAN {
+        ethertype = u16::from_be_bytes(payload.get(2..4)?.try_into().ok()?);
+        payload = payload.get(4..)?;
+    }
+    let ip = match ethertype {
+        // the sender's protocol address follows the header and its hardware address
+        ETHERTYPE_ARP => payload.get(14..18)?,
+        ETHERTYPE_IPV4 if payload.first()? >> 4 == 4 => payload.get(12..16)?,
+        _ => return None,
+    };
+    Some((mac, Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3])))
+}
+
+/// Listens on `interface` for `duration` and returns the IP and MAC address of every host seen
+/// sending from the interface's subnet, sorted by IP address. Requires Linux and root or
+/// CAP_NET_RAW.
+pub fn listen(interface: &str, duration: Duration) -> io::Result<Vec<(Ipv4Addr, MacAddr)>> {
+    let local = platform::interfaces()?
+        .into_iter()
+        .find(|local| local.name == interface)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no IPv4 address", interface),
+            )
+        })?;
+    // frames from further away carry the MAC address of the router that forwarded them
+    let subnet = Subnet::with_netmask(local.addr, local.netmask);
+    let own = platform::hardware_addr(interface)?;
+    let socket = platform::packet_socket(interface, ETHERTYPE_ALL)?;
+
+    let deadline = Instant::now() + duration;
+    let mut seen = BTreeMap::new();
+    let mut buf = [0; 65536];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        };
+        let Some((mac, ip)) = source(&buf[..len]) else {
+            continue;
+        };
+        // multicast sources are invalid, and 0.0.0.0 is used by hosts without an address yet
+        if mac == own || mac[0] & 1 != 0 || ip.is_unspecified() || !subnet.contains(ip) {
+            continue;
+        }
+        seen.entry(ip).or_insert(MacAddr(mac));
+    }
+    Ok(seen.into_iter().collect())
+}
+
+#[test]
+fn test_source() {
+    let mac = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+    let ip = Ipv4Addr::new(192, 168, 1, 20);
+
+    let mut arp = vec![0xff; 6];
+    arp.extend_from_slice(&mac);
+    arp.extend_from_slice(&[0x08, 0x06, 0, 1, 0x08, 0, 6, 4, 0, 1]);
+    arp.extend_from_slice(&mac);
+    arp.extend_from_slice(&ip.octets());
+    arp.extend_from_slice(&[0; 6]);
+    arp.extend_from_slice(&[192, 168, 1, 1]);
+    assert_eq!(source(&arp), Some((mac, ip)));
+
+    let mut ipv4 = vec![0xff; 6];
+    ipv4.extend_from_slice(&mac);
+    ipv4.extend_from_slice(&[0x81, 0x00, 0, 20, 0x08, 0x00]);
+    ipv4.extend_from_slice(&[0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0]);
+    ipv4.extend_from_slice(&ip.octets());
+    ipv4.extend_from_slice(&[255; 4]);
+    assert_eq!(source(&ipv4), Some((mac, ip)));
+
+    // IPv6, and truncated
+    let mut ipv6 = ipv4[..12].to_vec();
+    ipv6.extend_from_slice(&[0x86, 0xdd, 0x60, 0, 0, 0]);
+    assert_eq!(source(&ipv6), None);
+    assert_eq!(source(&arp[..30]), None);
+}
@@ -0,0 +1,143 @@
+//! Reads the lease databases of DHCP servers to learn the hostnames and MAC addresses of the
+//! machines on a network. Both dnsmasq's `dnsmasq.leases` and ISC dhcpd's `dhcpd.leases` are
+//! supported, and told apart by their contents.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use wol_cli::MacAddr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    pub hostname: String,
+    pub mac: MacAddr,
+}
+
+/// Parses dnsmasq leases, one per line as `<expiry> <mac> <ip> <hostname> <client id>`. Clients
+/// that did not send a hostname are listed as `*` and are skipped, as are DHCPv6 leases, which
+/// have the IAID of the client in place of its MAC address.
+fn parse_dnsmasq(contents: &str) -> Vec<Lease> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [_, mac, _, hostname, ..] = fields[..] else {
+                return None;
+            };
+            if hostname == "*" {
+                return None;
+            }
+            Some(Lease {
+                hostname: hostname.to_string(),
+                mac: mac.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Parses dhcpd leases, blocks like
+///
+/// ```text
+/// lease 192.168.1.20 {
+///   hardware ethernet aa:bb:cc:dd:ee:ff;
+///   client-hostname "nas";
+/// }
+/// ```
+///
+/// Leases without a hostname are skipped.
+fn parse_dhcpd(contents: &str) -> Vec<Lease> {
+    let mut leases = Vec::new();
+    let (mut mac, mut hostname) = (None, None);
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let statement = line.trim_end_matches(';');
+        let words: Vec<&str> = statement.split_whitespace().collect();
+        match words[..] {
+            ["lease", _, "{"] => (mac, hostname) = (None, None),
+            ["hardware", "ethernet", addr] => mac = addr.parse().ok(),
+            ["client-hostname", name] => hostname = Some(name.trim_matches('"').to_string()),
+            ["}"] => {
+                if let (Some(mac), Some(hostname)) = (mac.take(), hostname.take()) {
+                    leases.push(Lease { hostname, mac });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    leases
+}
+
+/// Parses a lease file of either format. Only the most recent lease of each hostname is kept, as
+/// both servers append renewed leases, and leases without a MAC address are skipped.
+pub fn parse(contents: &str) -> Vec<Lease> {
+    let is_dhcpd = contents
+        .lines()
+        .any(|line| line.trim_start().starts_with("lease "));
+    let all = if is_dhcpd {
+        parse_dhcpd(contents)
+    } else {
+        parse_dnsmasq(contents)
+    };
+
+    let mut leases: Vec<Lease> = Vec::new();
+    for lease in all {
+        leases.retain(|other| other.hostname != lease.hostname);
+        leases.push(lease);
+    }
+
+    leases
+}
+
+pub fn read_file(path: &Path) -> io::Result<Vec<Lease>> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+#[test]
+fn test_parse_dnsmasq() {
+    let contents = "1718000000 aa:bb:cc:dd:ee:ff 192.168.1.20 nas 01:aa:bb:cc:dd:ee:ff\n\
+                    1718000100 11:22:33:44:55:66 192.168.1.21 * *\n\
+                    1718000200 11:22:33:44:55:77 fd00::21 desktop *\n\
+                    duid 00:01:00:01:2d:5e:8f:a1:aa:bb:cc:dd:ee:01\n\
+                    1718000300 12345678 fd00::30 laptop 00:01:00:01:2d:5e:8f:a1:11:22:33:44:55:88\n\
+                    1718000400 305419896 fd00::31 desktop 00:04:aa:bb:cc:dd:ee:ff:00:11\n";
+    assert_eq!(
+        parse(contents),
+        [
+            Lease {
+                hostname: "nas".into(),
+                mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+            },
+            Lease {
+                hostname: "desktop".into(),
+                mac: "11:22:33:44:55:77".parse().unwrap(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_dhcpd() {
+    let contents = r#"
+# The format of this file is documented in the dhcpd.leases(5) manual page.
+lease 192.168.1.20 {
+  starts 4 2024/06/13 10:00:00;
+  hardware ethernet aa:bb:cc:dd:ee:00;
+  client-hostname "nas";
+}
+lease 192.168.1.21 {
+  hardware ethernet 11:22:33:44:55:66;
+}
+lease 192.168.1.22 {
+  hardware ethernet aa:bb:cc:dd:ee:ff;
+  client-hostname "nas";
+}
+"#;
+    assert_eq!(
+        parse(contents),
+        [Lease {
+            hostname: "nas".into(),
+            mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+        }]
+    );
+}
@@ -0,0 +1,41 @@
+//! Wake-on-LAN: building, parsing and sending magic packets, as done by the wol-cli command.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use wol_cli::MagicPacket;
+//!
+//! MagicPacket::builder("aa:bb:cc:dd:ee:ff".parse()?)
+//!     .destination("192.168.1.255".parse()?)
+//!     .port(7)
+//!     .repeat(3)
+//!     .send()?;
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() {}
+//! ```
+//!
+//! Without the default `std` feature, the crate is `no_std` and needs no allocator: MAC addresses
+//! are parsed and packets built and read, for sending by other means.
+//!
+//! Sending emits [`tracing`] events: a trace event for each socket set up, with its local
+//! address and options, and a debug event for each packet sent.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod send;
+#[cfg(feature = "std")]
+mod sys;
+pub mod wol;
+
+#[cfg(feature = "std")]
+pub use error::WolError;
+#[cfg(feature = "std")]
+pub use send::{AsyncUdpSocket, MagicPacketBuilder, SendOptions};
+pub use wol::{
+    create_magic_packet, MacAddr, MagicPacket, PacketError, ParseError, COMMON_PORTS, DEFAULT_PORT,
+    ETHERTYPE_WOL, IPV6_ALL_NODES,
+};
@@ -1,23 +1,234 @@
-use clap::Parser;
+use std::process::ExitCode;
 
-mod wol;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+use wol_cli::wol;
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// MAC address to wake.
-    #[arg(short, long)]
-    mac: String,
+use cli::{Args, Command, ConfigAction, ImportSource};
+use config::{Config, Host};
+
+mod activation;
+mod cli;
+mod color;
+mod commands;
+mod config;
+mod dbus;
+mod dirs;
+mod duration;
+mod ethers;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod guard;
+mod hexdump;
+mod history;
+mod homeassistant;
+mod http;
+mod json;
+mod learn;
+mod leases;
+mod mdns;
+mod metrics;
+mod mqtt;
+mod neighbors;
+mod netbios;
+mod notify;
+mod oui;
+mod platform;
+mod probe;
+mod progress;
+mod proxy;
+mod relay;
+mod schedule;
+mod snmp;
+mod subnet;
+mod table;
+mod targets;
+mod template;
+mod toml;
+mod tui;
+mod wsd;
+
+/// Prints the tracing events of sending to standard error: warnings, debug events with -v and
+/// trace events with -vv. RUST_LOG takes precedence, for the events of a single module.
+fn init_tracing(verbose: u8) {
+    let level = match verbose {
+        0 => tracing::Level::WARN,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    let filter = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(tracing::level_filters::LevelFilter::from_level(level).into())
+        .from_env_lossy();
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .without_time()
+        .with_target(false)
+        .init();
 }
 
-fn main() {
-    let args = Args::parse();
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    color::init(args.color);
+    let send = match &mut args.command {
+        Some(
+            Command::Group { send, .. }
+            | Command::All { send }
+            | Command::Serve { send, .. }
+            | Command::Mqtt { send, .. }
+            | Command::Dbus { send, .. }
+            | Command::Schedule { send }
+            | Command::Proxy { send, .. }
+            | Command::Tui { send, .. },
+        ) => send,
+        _ => &mut args.send,
+    };
+    if let Err(err) = send.apply_env() {
+        Args::command()
+            .error(ErrorKind::ValueValidation, err)
+            .exit();
+    }
+    init_tracing(send.verbose);
+    let config_path = Config::locate(args.config.as_deref()).unwrap_or_default();
+    let config_path = config_path.as_path();
 
-    match wol::create_magic_packet(&args.mac) {
-        Ok(packet) => {
-            packet.broadcast().expect("unable to send packet");
-            println!("packet sent to 255.255.255.255 with MAC {}", args.mac);
+    match &args.command {
+        Some(Command::Group { groups, send }) => {
+            commands::wake::wake_groups(groups, send, config_path)
+        }
+        Some(Command::All { send }) => commands::wake::wake_registry(send, config_path),
+        #[cfg(feature = "grpc")]
+        Some(Command::Serve {
+            listen,
+            grpc,
+            guard,
+            send,
+        }) => commands::serve::serve(*listen, *grpc, guard, send, config_path),
+        #[cfg(not(feature = "grpc"))]
+        Some(Command::Serve {
+            listen,
+            guard,
+            send,
+        }) => commands::serve::serve(*listen, None, guard, send, config_path),
+        Some(Command::Mqtt {
+            broker,
+            topic,
+            homeassistant,
+            send,
+        }) => commands::mqtt::subscribe(broker, topic, homeassistant.as_deref(), send, config_path),
+        Some(Command::Dbus { system, send }) => {
+            commands::dbus::dbus_service(*system, send, config_path)
+        }
+        Some(Command::Schedule { send }) => commands::schedule::run_schedule(send, config_path),
+        Some(Command::List { json }) => commands::hosts::list(*json, config_path),
+        Some(Command::Status {
+            hosts,
+            port,
+            probe,
+            timeout,
+        }) => commands::hosts::status(hosts, *port, *probe, *timeout, config_path),
+        Some(Command::Tui {
+            hosts,
+            refresh,
+            send,
+        }) => commands::tui::pick_hosts(hosts, *refresh, send, config_path),
+        Some(Command::Offline {
+            hosts,
+            port,
+            probe,
+            timeout,
+        }) => commands::hosts::offline(hosts, *port, *probe, *timeout, config_path),
+        Some(Command::Neighbors { subnet, json }) => {
+            commands::discover::list_neighbors(*subnet, *json, config_path)
+        }
+        Some(Command::Listen { port, bind }) => commands::listen::listen(port, *bind, config_path),
+        Some(Command::Relay {
+            listen,
+            tcp,
+            tls_cert,
+            tls_key,
+            emit,
+            emit_port,
+            guard,
+        }) => {
+            let tls = tls_cert.as_deref().zip(tls_key.as_deref());
+            commands::relay::run_relay(listen, *tcp, tls, emit, *emit_port, guard, config_path)
+        }
+        Some(Command::Proxy {
+            hosts,
+            wake_on,
+            send,
+        }) => commands::proxy::sleep_proxy(hosts, wake_on, send, config_path),
+        Some(Command::Learn {
+            interface,
+            duration,
+        }) => commands::discover::learn(interface, *duration, config_path),
+        Some(Command::Scan { subnet, timeout }) => {
+            commands::discover::scan(*subnet, *timeout, config_path)
+        }
+        Some(Command::Discover {
+            mdns,
+            netbios,
+            wsd,
+            timeout,
+        }) => commands::discover::discover((*mdns, *netbios, *wsd), *timeout, config_path),
+        Some(Command::Add {
+            name,
+            mac,
+            broadcast,
+            ip,
+            port,
+            password,
+            interface,
+            count,
+            groups,
+            note,
+            schedule,
+        }) => commands::registry::add(
+            Host {
+                name: name.clone(),
+                mac: *mac,
+                broadcast: *broadcast,
+                ip: *ip,
+                port: *port,
+                password: password.clone(),
+                interface: interface.clone(),
+                count: *count,
+                groups: groups.clone(),
+                note: note.clone(),
+                schedule: schedule.clone(),
+                ..Default::default()
+            },
+            config_path,
+        ),
+        Some(Command::Remove { name, force }) => {
+            commands::registry::remove(name, *force, config_path)
         }
-        Err(err) => eprintln!("unable to create magic packet: {}", err),
+        Some(Command::Rename { old, new }) => commands::registry::rename(old, new, config_path),
+        Some(Command::Edit) => commands::registry::edit(config_path),
+        Some(Command::Config {
+            action: ConfigAction::Export { format },
+        }) => commands::registry::export(*format, config_path),
+        Some(Command::Config {
+            action: ConfigAction::Import { file, replace },
+        }) => commands::registry::import(file, *replace, config_path),
+        Some(Command::Config {
+            action: ConfigAction::Validate { file },
+        }) => commands::registry::validate(file.as_deref().unwrap_or(config_path)),
+        Some(Command::Import {
+            source: ImportSource::Leases { files, replace },
+        }) => commands::registry::import_leases(files, *replace, config_path),
+        Some(Command::Import {
+            source:
+                ImportSource::Snmp {
+                    switch,
+                    community,
+                    prefix,
+                    replace,
+                },
+        }) => commands::registry::import_snmp(switch, community, prefix, *replace, config_path),
+        Some(Command::Completions { shell }) => commands::generate::completions(*shell),
+        Some(Command::Man { dir }) => commands::generate::man(dir.as_deref()),
+        None => commands::wake::wake_targets(&args, config_path),
     }
 }
@@ -0,0 +1,284 @@
+//! Discovery of hosts that announce themselves over multicast DNS (RFC 6762), e.g. as nas.local.
+//!
+//! The common service types are browsed with a one-shot query from an ephemeral port, which
+//! responders answer directly (RFC 6762, section 6.7), and the addresses of the hosts offering
+//! them are read from the records of the answers.
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// Service types commonly advertised by computers and NASes.
+const SERVICES: &[&str] = &[
+    "_workstation._tcp.local",
+    "_device-info._tcp.local",
+    "_ssh._tcp.local",
+    "_sftp-ssh._tcp.local",
+    "_smb._tcp.local",
+    "_afpovertcp._tcp.local",
+    "_adisk._tcp.local",
+    "_nfs._tcp.local",
+    "_rfb._tcp.local",
+    "_http._tcp.local",
+];
+
+/// A host found over mDNS.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Device {
+    /// The name of the host without the .local domain.
+    pub name: String,
+
+    pub addrs: Vec<IpAddr>,
+
+    /// The service types the host advertises, e.g. ssh or smb.
+    pub services: Vec<String>,
+}
+
+/// A resource record from an answer, as far as it is needed for discovery.
+#[derive(Debug, PartialEq, Eq)]
+enum Record {
+    /// An A or AAAA record, the address of a host.
+    Addr(String, IpAddr),
+
+    /// An instance of a service type, e.g. "nas._smb._tcp.local" for "_smb._tcp.local".
+    Ptr(String, String),
+
+    /// The host offering a service instance.
+    Srv(String, String),
+}
+
+fn write_name(packet: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+}
+
+/// Builds a query for the instances of the given service types.
+fn query(services: &[&str]) -> Vec<u8> {
+    let mut packet = vec![0; 12];
+    packet[4..6].copy_from_slice(&(services.len() as u16).to_be_bytes());
+    for service in services {
+        write_name(&mut packet, service);
+        packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    }
+    packet
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(pos)?,
+        *packet.get(pos + 1)?,
+    ]))
+}
+
+/// Reads a possibly compressed name at `pos`, returning it in lowercase along with the position
+/// after it.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            // a pointer to the rest of the name elsewhere in the packet
+            end.get_or_insert(pos + 2);
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+            pos = (len & 0x3f) << 8 | *packet.get(pos + 1)? as usize;
+            continue;
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_lowercase());
+        pos += 1 + len;
+    }
+    Some((labels.join("."), end.unwrap_or(pos)))
+}
+
+/// Reads the records of a response, returning `None` if it is malformed or not a response.
+fn parse(packet: &[u8]) -> Option<Vec<Record>> {
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let records = (6..12)
+        .step_by(2)
+        .map(|pos| read_u16(packet, pos).map(usize::from))
+        .sum::<Option<usize>>()?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(packet, pos)?.1 + 4;
+    }
+    let mut parsed = Vec::new();
+    for _ in 0..records {
+        let (name, after) = read_name(packet, pos)?;
+        let kind = read_u16(packet, after)?;
+        let len = read_u16(packet, after + 8)? as usize;
+        let data = after + 10;
+        let rdata = packet.get(data..data + len)?;
+        match (kind, rdata.len()) {
+            (TYPE_A, 4) => {
+                let ip = Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]);
+                parsed.push(Record::Addr(name, ip.into()));
+            }
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = rdata.try_into().ok()?;
+                parsed.push(Record::Addr(name, Ipv6Addr::from(octets).into()));
+            }
+            (TYPE_PTR, _) => parsed.push(Record::Ptr(name, read_name(packet, data)?.0)),
+            // the target follows the priority, weight and port
+            (TYPE_SRV, 7..) => parsed.push(Record::Srv(name, read_name(packet, data + 6)?.0)),
+            _ => {}
+        }
+        pos = data + len;
+    }
+    Some(parsed)
+}
+
+/// Groups the records of all answers by host, leaving out hosts without an address.
+fn devices(records: &[Record]) -> Vec<Device> {
+    let mut devices: BTreeMap<&str, Device> = BTreeMap::new();
+    for record in records {
+        if let Record::Addr(host, ip) = record {
+            let device = devices.entry(host).or_default();
+            if !device.addrs.contains(ip) {
+                device.addrs.push(*ip);
+            }
+        }
+    }
+    for record in records {
+        let Record::Ptr(service, instance) = record else {
+            continue;
+        };
+        let host = records.iter().find_map(|record| match record {
+            Record::Srv(name, host) if name == instance => Some(host.as_str()),
+            _ => None,
+        });
+        let Some(device) = host.and_then(|host| devices.get_mut(host)) else {
+            continue;
+        };
+        // _ssh._tcp.local is listed as ssh
+        let service = service.trim_start_matches('_');
+        let service = service.split('.').next().unwrap_or(service).to_string();
+        if !device.services.contains(&service) {
+            device.services.push(service);
+        }
+    }
+
+    devices
+        .into_iter()
+        .map(|(host, device)| Device {
+            name: host.strip_suffix(".local").unwrap_or(host).to_string(),
+            ..device
+        })
+        .collect()
+}
+
+/// Browses the common service types and returns the hosts that answered within `timeout`.
+pub fn discover(timeout: Duration) -> io::Result<Vec<Device>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_multicast_ttl_v4(255)?;
+    socket.send_to(&query(SERVICES), (MDNS_ADDR, MDNS_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut records = Vec::new();
+    let mut buf = [0; 9000];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, _)) => records.extend(parse(&buf[..len]).unwrap_or_default()),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(devices(&records))
+}
+
+#[cfg(test)]
+fn write_record(packet: &mut Vec<u8>, name: &[u8], kind: u16, rdata: &[u8]) {
+    packet.extend_from_slice(name);
+    packet.extend_from_slice(&kind.to_be_bytes());
+    packet.extend_from_slice(&[0x80, 1, 0, 0, 0x11, 0x94]);
+    packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+    packet.extend_from_slice(rdata);
+}
+
+#[test]
+fn test_query() {
+    let packet = query(&["_ssh._tcp.local"]);
+    assert_eq!(&packet[..12], [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(&packet[12..17], b"\x04_ssh");
+    assert!(packet.ends_with(b"\x05local\x00\x00\x0c\x00\x01"));
+    assert_eq!(parse(&packet), None);
+}
+
+#[test]
+fn test_parse() {
+    let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, 3, 0, 0, 0, 0];
+    // _ssh._tcp.local, which is then pointed to at offset 12
+    let mut service = Vec::new();
+    write_name(&mut service, "_ssh._tcp.local");
+    let mut instance = b"\x03NAS".to_vec();
+    instance.extend_from_slice(&[0xc0, 12]);
+    let host = b"\x03nas\x05local\x00";
+
+    write_record(&mut packet, &service, TYPE_PTR, &instance);
+    let mut srv = vec![0, 0, 0, 0, 0, 22];
+    srv.extend_from_slice(host);
+    write_record(&mut packet, &instance, TYPE_SRV, &srv);
+    write_record(&mut packet, host, TYPE_A, &[192, 168, 1, 20]);
+
+    let records = parse(&packet).unwrap();
+    assert_eq!(
+        records,
+        [
+            Record::Ptr("_ssh._tcp.local".into(), "nas._ssh._tcp.local".into()),
+            Record::Srv("nas._ssh._tcp.local".into(), "nas.local".into()),
+            Record::Addr("nas.local".into(), Ipv4Addr::new(192, 168, 1, 20).into()),
+        ]
+    );
+    // a pointer out of the packet
+    let mut malformed = packet.clone();
+    malformed[11] = 1;
+    write_record(&mut malformed, host, TYPE_PTR, &[0xc0, 0xff]);
+    assert_eq!(parse(&malformed), None);
+
+    assert_eq!(
+        devices(&records),
+        [Device {
+            name: "nas".into(),
+            addrs: vec![Ipv4Addr::new(192, 168, 1, 20).into()],
+            services: vec!["ssh".into()],
+        }]
+    );
+}
@@ -0,0 +1,175 @@
+//! Metrics of `serve` in the Prometheus text format, for alerting on hosts that refuse to wake.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Magic packets sent by this process, counted where they are sent rather than per wake, as a
+/// wake sends as many as there are destinations and --count asks for.
+static PACKETS_SENT: AtomicU64 = AtomicU64::new(0);
+
+pub fn count_packet() {
+    PACKETS_SENT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The wakes requested from `serve`, by host.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: BTreeMap<String, u64>,
+
+    /// The failed wakes by host and outcome, e.g. timed-out.
+    failures: BTreeMap<(String, &'static str), u64>,
+}
+
+/// What probing a host for the metrics found.
+pub struct ProbeResult<'a> {
+    pub host: &'a str,
+
+    /// Whether the host is up, or `None` if it could not be probed.
+    pub up: Option<bool>,
+
+    pub duration: Duration,
+}
+
+impl Metrics {
+    /// Counts a wake of `host`, and that it failed unless `outcome` is "woken".
+    pub fn count_wake(&mut self, host: &str, outcome: &'static str) {
+        *self.requests.entry(host.to_string()).or_default() += 1;
+        if outcome != "woken" {
+            *self
+                .failures
+                .entry((host.to_string(), outcome))
+                .or_default() += 1;
+        }
+    }
+
+    /// Formats the counters, along with what the hosts were just probed to be.
+    pub fn render(&self, probes: &[ProbeResult]) -> String {
+        let mut out = String::new();
+        family(
+            &mut out,
+            "wol_packets_sent_total",
+            "counter",
+            "Magic packets sent.",
+        );
+        let _ = writeln!(
+            out,
+            "wol_packets_sent_total {}",
+            PACKETS_SENT.load(Ordering::Relaxed)
+        );
+
+        family(
+            &mut out,
+            "wol_wake_requests_total",
+            "counter",
+            "Wakes requested, by host.",
+        );
+        for (host, count) in &self.requests {
+            let _ = writeln!(
+                out,
+                "wol_wake_requests_total{{host=\"{}\"}} {}",
+                escape(host),
+                count
+            );
+        }
+
+        family(
+            &mut out,
+            "wol_wake_failures_total",
+            "counter",
+            "Wakes that failed, by host and outcome.",
+        );
+        for ((host, outcome), count) in &self.failures {
+            let _ = writeln!(
+                out,
+                "wol_wake_failures_total{{host=\"{}\",outcome=\"{}\"}} {}",
+                escape(host),
+                outcome,
+                count
+            );
+        }
+
+        family(
+            &mut out,
+            "wol_host_up",
+            "gauge",
+            "Whether the host answered the probe, by host.",
+        );
+        for probe in probes {
+            if let Some(up) = probe.up {
+                let _ = writeln!(
+                    out,
+                    "wol_host_up{{host=\"{}\"}} {}",
+                    escape(probe.host),
+                    u8::from(up)
+                );
+            }
+        }
+
+        family(
+            &mut out,
+            "wol_probe_duration_seconds",
+            "gauge",
+            "How long probing the host took, by host.",
+        );
+        for probe in probes.iter().filter(|probe| probe.up.is_some()) {
+            let _ = writeln!(
+                out,
+                "wol_probe_duration_seconds{{host=\"{}\"}} {:.6}",
+                escape(probe.host),
+                probe.duration.as_secs_f64()
+            );
+        }
+        out
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[test]
+fn test_render() {
+    let mut metrics = Metrics::default();
+    metrics.count_wake("nas", "woken");
+    metrics.count_wake("nas", "timed-out");
+    metrics.count_wake("living \"room\"", "woken");
+    let probes = [
+        ProbeResult {
+            host: "nas",
+            up: Some(true),
+            duration: Duration::from_micros(1500),
+        },
+        ProbeResult {
+            host: "pc",
+            up: None,
+            duration: Duration::ZERO,
+        },
+    ];
+    let out = metrics.render(&probes);
+
+    assert!(out.starts_with(
+        "# HELP wol_packets_sent_total Magic packets sent.\n\
+         # TYPE wol_packets_sent_total counter\n\
+         wol_packets_sent_total "
+    ));
+    assert!(out.contains(
+        "wol_wake_requests_total{host=\"living \\\"room\\\"\"} 1\n\
+         wol_wake_requests_total{host=\"nas\"} 2\n"
+    ));
+    assert!(out.contains("\nwol_wake_failures_total{host=\"nas\",outcome=\"timed-out\"} 1\n"));
+    assert!(out.contains("\nwol_host_up{host=\"nas\"} 1\n"));
+    assert!(out.ends_with(
+        "# TYPE wol_probe_duration_seconds gauge\n\
+         wol_probe_duration_seconds{host=\"nas\"} 0.001500\n"
+    ));
+    assert!(!out.contains("\"pc\""));
+}
@@ -0,0 +1,328 @@
+//! Reads the operating system's neighbor table (the ARP cache for IPv4 and the NDP cache for
+//! IPv6), which maps the IP addresses of recently seen hosts to their MAC addresses.
+//!
+//! As a sleeping host soon drops out of the neighbor table, every MAC address learned from it is
+//! also remembered in a cache file, `$XDG_CACHE_HOME/wol/ethers` (or `~/.cache/wol/ethers`),
+//! written in the format of `/etc/ethers`.
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use wol_cli::MacAddr;
+
+use crate::{dirs, ethers, wol};
+
+/// How long to wait for the kernel to resolve a probed address.
+const PROBE_WAIT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    pub ip: IpAddr,
+    pub mac: MacAddr,
+    pub interface: Option<String>,
+}
+
+/// Accepts only complete MAC addresses, skipping incomplete entries which some tools print as
+/// all zeroes.
+fn valid_mac(mac: &str) -> Option<MacAddr> {
+    mac.parse().ok().filter(|mac| *mac != MacAddr::default())
+}
+
+/// Parses the output of `ip neigh show`, e.g.
+/// `192.168.1.1 dev eth0 lladdr aa:bb:cc:dd:ee:ff REACHABLE`.
+fn parse_ip_neigh(output: &str) -> Vec<Neighbor> {
+    let mut neighbors = Vec::new();
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+        let Some(Ok(ip)) = words.next().map(str::parse) else {
+            continue;
+        };
+        let (mut mac, mut interface) = (None, None);
+        while let Some(word) = words.next() {
+            match word {
+                "dev" => interface = words.next(),
+                "lladdr" => mac = words.next(),
+                _ => {}
+            }
+        }
+        if let Some(mac) = mac.and_then(valid_mac) {
+            neighbors.push(Neighbor {
+                ip,
+                mac,
+                interface: interface.map(String::from),
+            });
+        }
+    }
+
+    neighbors
+}
+
+/// Parses `/proc/net/arp`, which has a header followed by lines of
+/// `IP address, HW type, Flags, HW address, Mask, Device`.
+fn parse_proc_arp(contents: &str) -> Vec<Neighbor> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, _, mac, _, interface] = fields[..] else {
+                return None;
+            };
+            Some(Neighbor {
+                ip: ip.parse().ok()?,
+                mac: valid_mac(mac)?,
+                interface: Some(interface.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Parses the output of `arp -a` on macOS and the BSDs, e.g.
+/// `? (192.168.1.1) at aa:bb:cc:dd:ee:ff on en0 ifscope [ethernet]`, as well as on Windows, e.g.
+/// `  192.168.1.1           aa-bb-cc-dd-ee-ff     dynamic`.
+fn parse_arp(output: &str) -> Vec<Neighbor> {
+    let mut neighbors = Vec::new();
+    for line in output.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let neighbor = match words[..] {
+            [_, ip, "at", mac, "on", interface, ..] => {
+                let ip = ip.trim_start_matches('(').trim_end_matches(')');
+                // BSD arp drops leading zeroes, e.g. a:b:c:d:e:f
+                let mac = mac
+                    .split(':')
+                    .map(|octet| format!("{:0>2}", octet))
+                    .collect::<Vec<_>>()
+                    .join(":");
+                ip.parse()
+                    .ok()
+                    .zip(valid_mac(&mac))
+                    .map(|(ip, mac)| Neighbor {
+                        ip,
+                        mac,
+                        interface: Some(interface.to_string()),
+                    })
+            }
+            [ip, mac, _] => ip
+                .parse()
+                .ok()
+                .zip(valid_mac(mac))
+                .map(|(ip, mac)| Neighbor {
+                    ip,
+                    mac,
+                    interface: None,
+                }),
+            _ => None,
+        };
+        neighbors.extend(neighbor);
+    }
+
+    neighbors
+}
+
+fn run(program: &str, args: &[&str]) -> io::Result<String> {
+    let output = Command::new(program).args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            program, output.status
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Lists the entries of the neighbor table that have a known MAC address.
+pub fn table() -> io::Result<Vec<Neighbor>> {
+    if cfg!(target_os = "linux") {
+        match run("ip", &["neigh", "show"]) {
+            Ok(output) => Ok(parse_ip_neigh(&output)),
+            // iproute2 is not installed everywhere, but IPv4 entries are always in procfs
+            Err(_) => Ok(parse_proc_arp(&std::fs::read_to_string("/proc/net/arp")?)),
+        }
+    } else if cfg!(windows) {
+        Ok(parse_arp(&run("arp", &["-a"])?))
+    } else {
+        Ok(parse_arp(&run("arp", &["-an"])?))
+    }
+}
+
+/// Looks up the MAC address of an IP address in the neighbor table.
+pub fn lookup(ip: IpAddr) -> io::Result<Option<MacAddr>> {
+    Ok(table()?
+        .into_iter()
+        .find(|neighbor| neighbor.ip == ip)
+        .map(|neighbor| neighbor.mac))
+}
+
+/// Sends an empty datagram to the discard port of `ip`, which makes the kernel resolve its MAC
+/// address into the neighbor table if the host is awake.
+fn probe(ip: IpAddr) {
+    let bind: SocketAddr = match ip {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    if let Ok(socket) = UdpSocket::bind(bind) {
+        let _ = socket.send_to(&[], (ip, wol::DEFAULT_PORT));
+        thread::sleep(PROBE_WAIT);
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    Some(dirs::cache()?.join("ethers"))
+}
+
+fn cached(path: &Path, ip: IpAddr) -> Option<MacAddr> {
+    let contents = fs::read_to_string(path).ok()?;
+    ethers::parse(&contents)
+        .into_iter()
+        .find(|(name, _)| name.parse() == Ok(ip))
+        .map(|(_, mac)| mac)
+}
+
+fn remember(path: &Path, ip: IpAddr, mac: MacAddr) -> io::Result<()> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    let mut entries: Vec<(String, MacAddr)> = ethers::parse(&contents)
+        .into_iter()
+        .filter(|(name, _)| name.parse() != Ok(ip))
+        .collect();
+    entries.push((ip.to_string(), mac));
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents: String = entries
+        .iter()
+        .map(|(name, mac)| format!("{} {}\n", mac, name))
+        .collect();
+    fs::write(path, contents)
+}
+
+/// Learns the MAC address of an IP address from the neighbor table, probing the host first if it
+/// is not in the table yet, and falling back to the cache of previously learned addresses when
+/// the host does not answer, e.g. because it is asleep.
+pub fn discover(ip: IpAddr) -> io::Result<Option<MacAddr>> {
+    let mut mac = lookup(ip)?;
+    if mac.is_none() {
+        probe(ip);
+        mac = lookup(ip)?;
+    }
+
+    let Some(path) = cache_path() else {
+        return Ok(mac);
+    };
+    match mac {
+        Some(mac) => {
+            // the cache only saves a later lookup, so failing to write it is not an error
+            let _ = remember(&path, ip, mac);
+            Ok(Some(mac))
+        }
+        None => Ok(cached(&path, ip)),
+    }
+}
+
+/// Resolves a hostname and discovers the MAC address of the first of its addresses that has one,
+/// returning that address along with its MAC address.
+pub fn resolve_host(host: &str) -> io::Result<(IpAddr, MacAddr)> {
+    let addrs: Vec<IpAddr> = (host, 0).to_socket_addrs()?.map(|addr| addr.ip()).collect();
+    for &ip in &addrs {
+        if let Some(mac) = discover(ip)? {
+            return Ok((ip, mac));
+        }
+    }
+
+    let addrs: Vec<String> = addrs.iter().map(IpAddr::to_string).collect();
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "no MAC address known for {} ({}), it must be awake once for its MAC address to be learned",
+            host,
+            addrs.join(", ")
+        ),
+    ))
+}
+
+#[test]
+fn test_parse_ip_neigh() {
+    let output = "192.168.1.1 dev eth0 lladdr aa:bb:cc:dd:ee:ff REACHABLE\n\
+                  192.168.1.7 dev eth0 FAILED\n\
+                  fe80::1 dev eth0 lladdr 11:22:33:44:55:66 router STALE\n";
+    assert_eq!(
+        parse_ip_neigh(output),
+        [
+            Neighbor {
+                ip: "192.168.1.1".parse().unwrap(),
+                mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+                interface: Some("eth0".into()),
+            },
+            Neighbor {
+                ip: "fe80::1".parse().unwrap(),
+                mac: "11:22:33:44:55:66".parse().unwrap(),
+                interface: Some("eth0".into()),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_proc_arp() {
+    let contents =
+        "IP address       HW type     Flags       HW address            Mask     Device\n\
+                    192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0\n\
+                    192.168.1.7      0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+    assert_eq!(
+        parse_proc_arp(contents),
+        [Neighbor {
+            ip: "192.168.1.1".parse().unwrap(),
+            mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+            interface: Some("eth0".into()),
+        }]
+    );
+}
+
+#[test]
+fn test_parse_arp() {
+    let output = "? (192.168.1.1) at a:bb:c:dd:e:ff on en0 ifscope [ethernet]\n\
+                  ? (192.168.1.7) at (incomplete) on en0 ifscope [ethernet]\n\
+                  Interface: 192.168.1.20 --- 0x4\n  \
+                    Internet Address      Physical Address      Type\n  \
+                    192.168.1.2           11-22-33-44-55-66     dynamic\n";
+    assert_eq!(
+        parse_arp(output),
+        [
+            Neighbor {
+                ip: "192.168.1.1".parse().unwrap(),
+                mac: "0a:bb:0c:dd:0e:ff".parse().unwrap(),
+                interface: Some("en0".into()),
+            },
+            Neighbor {
+                ip: "192.168.1.2".parse().unwrap(),
+                mac: "11-22-33-44-55-66".parse().unwrap(),
+                interface: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_cache() {
+    let path = std::env::temp_dir().join(format!("wol-cli-test-{}", std::process::id()));
+    let nas: IpAddr = "192.168.1.20".parse().unwrap();
+    let desktop: IpAddr = "fd00::21".parse().unwrap();
+
+    assert_eq!(cached(&path, nas), None);
+    remember(&path, nas, "aa:bb:cc:dd:ee:00".parse().unwrap()).unwrap();
+    remember(&path, desktop, "11:22:33:44:55:66".parse().unwrap()).unwrap();
+    remember(&path, nas, "aa:bb:cc:dd:ee:ff".parse().unwrap()).unwrap();
+    assert_eq!(cached(&path, nas), "aa:bb:cc:dd:ee:ff".parse().ok());
+    assert_eq!(cached(&path, desktop), "11:22:33:44:55:66".parse().ok());
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "11:22:33:44:55:66 fd00::21\naa:bb:cc:dd:ee:ff 192.168.1.20\n"
+    );
+
+    fs::remove_file(path).unwrap();
+}
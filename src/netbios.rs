@@ -0,0 +1,193 @@
+//! NetBIOS node status queries (RFC 1002), which Windows hosts answer with their computer name
+//! and MAC address even when they do not answer ping or mDNS.
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wol_cli::MacAddr;
+
+const NETBIOS_PORT: u16 = 137;
+
+const TYPE_NBSTAT: u16 = 0x21;
+const CLASS_IN: u16 = 1;
+
+/// The pause between queries sent to consecutive hosts.
+const QUERY_INTERVAL: Duration = Duration::from_micros(200);
+
+/// Identifies our queries among other datagrams received on the socket.
+const QUERY_ID: u16 = 0x776f;
+
+/// What a host answered to a node status query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    /// The computer name, e.g. DESKTOP-1234.
+    pub name: String,
+
+    /// The MAC address of the interface the host answered on, unless it hides it, as Samba does.
+    pub mac: Option<MacAddr>,
+}
+
+/// Builds a node status query for the wildcard name "*", which every host answers.
+fn query() -> Vec<u8> {
+    let mut packet = Vec::with_capacity(50);
+    packet.extend_from_slice(&QUERY_ID.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    // the name is padded to 16 bytes with NULs, and each of its nibbles encoded as a letter
+    let mut name = [0u8; 16];
+    name[0] = b'*';
+    packet.push(32);
+    for byte in name {
+        packet.extend_from_slice(&[b'A' + (byte >> 4), b'A' + (byte & 0xf)]);
+    }
+    packet.push(0);
+    packet.extend_from_slice(&TYPE_NBSTAT.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    packet
+}
+
+/// Skips the name at `pos`, returning the position after it.
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => return Some(pos + 1),
+            _ if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            _ => pos += 1 + len,
+        }
+    }
+}
+
+/// Reads the answer to a node status query.
+fn parse(packet: &[u8]) -> Option<NodeStatus> {
+    if packet.get(..2)? != QUERY_ID.to_be_bytes() || packet.get(2)? & 0x80 == 0 {
+        return None;
+    }
+    let pos = skip_name(packet, 12)?;
+    if packet.get(pos..pos + 2)? != TYPE_NBSTAT.to_be_bytes() {
+        return None;
+    }
+    // the type and class are followed by the TTL and the length of the data
+    let mut pos = pos + 10;
+    let count = *packet.get(pos)? as usize;
+    pos += 1;
+
+    let mut name = None;
+    for _ in 0..count {
+        let entry = packet.get(pos..pos + 18)?;
+        pos += 18;
+        // the computer name is the unique name with the workstation suffix 0x00
+        let group = entry[16] & 0x80 != 0;
+        if entry[15] == 0 && !group && name.is_none() {
+            name = Some(String::from_utf8_lossy(&entry[..15]).trim_end().to_string());
+        }
+    }
+    let mac = packet
+        .get(pos..pos + 6)
+        .and_then(|mac| MacAddr::try_from(mac).ok())
+        .filter(|mac| *mac != MacAddr::default());
+    Some(NodeStatus { name: name?, mac })
+}
+
+/// Asks each of the given hosts for its node status, returning the answers received within
+/// `timeout` of the last query.
+pub fn query_all(
+    ips: impl Iterator<Item = Ipv4Addr> + Send,
+    timeout: Duration,
+) -> io::Result<BTreeMap<Ipv4Addr, NodeStatus>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let sender = socket.try_clone()?;
+    let request = query();
+
+    let mut found = BTreeMap::new();
+    thread::scope(|scope| {
+        let sending = scope.spawn(move || {
+            for ip in ips {
+                // unreachable hosts are simply not found
+                let _ = sender.send_to(&request, (ip, NETBIOS_PORT));
+                thread::sleep(QUERY_INTERVAL);
+            }
+        });
+
+        let mut buf = [0; 1500];
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None if sending.is_finished() => {
+                    deadline = Some(Instant::now() + timeout);
+                    timeout
+                }
+                None => QUERY_INTERVAL * 100,
+            };
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv_from(&mut buf) {
+                Ok((len, SocketAddr::V4(addr))) => {
+                    if let Some(status) = parse(&buf[..len]) {
+                        found.entry(*addr.ip()).or_insert(status);
+                    }
+                }
+                Ok(_) => {}
+                // the port unreachable errors of hosts without NetBIOS are reported as refused
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::ConnectionRefused
+                            | io::ErrorKind::WouldBlock
+                            | io::ErrorKind::TimedOut
+                    ) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(found)
+}
+
+#[test]
+fn test_query() {
+    let packet = query();
+    assert_eq!(packet.len(), 50);
+    assert_eq!(&packet[13..17], b"CKAA");
+    assert_eq!(&packet[46..], [0, 0x21, 0, 1]);
+}
+
+#[test]
+fn test_parse() {
+    let mut packet = vec![0x77, 0x6f, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+    packet.extend_from_slice(&query()[12..46]);
+    packet.extend_from_slice(&[0, 0x21, 0, 1, 0, 0, 0, 0, 0, 0]);
+    packet.push(3);
+    for (name, suffix, flags) in [
+        (b"WORKGROUP      ", 0x00, 0x84),
+        (b"DESKTOP-1234   ", 0x00, 0x04),
+        (b"DESKTOP-1234   ", 0x20, 0x04),
+    ] {
+        packet.extend_from_slice(name);
+        packet.extend_from_slice(&[suffix, flags, 0]);
+    }
+    packet.extend_from_slice(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+
+    assert_eq!(
+        parse(&packet),
+        Some(NodeStatus {
+            name: "DESKTOP-1234".into(),
+            mac: "aa:bb:cc:dd:ee:ff".parse().ok(),
+        })
+    );
+
+    // Samba does not tell its MAC address
+    let len = packet.len();
+    packet[len - 6..].fill(0);
+    assert_eq!(parse(&packet).unwrap().mac, None);
+
+    // truncated, or answering another query
+    assert_eq!(parse(&packet[..70]), None);
+    packet[0] = 0;
+    assert_eq!(parse(&packet), None);
+}
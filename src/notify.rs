@@ -0,0 +1,264 @@
+//! Webhook notifications, POSTing a JSON document to a URL after waking a host.
+//!
+//! https:// URLs are posted to over TLS, checking the certificate of the server against the
+//! public certificate authorities Mozilla trusts, as relays are spoken to.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+
+use crate::{json, relay};
+
+/// How long to wait for the server to accept the connection and to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The parts of an http:// or https:// URL needed to post to it.
+#[derive(Debug, PartialEq, Eq)]
+struct Url<'a> {
+    /// Whether it is https://, posted to over TLS.
+    tls: bool,
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Splits an http:// or https:// URL.
+fn parse_url(url: &str) -> io::Result<Url<'_>> {
+    let (tls, rest) = match url.split_once("://") {
+        Some(("http", rest)) => (false, rest),
+        Some(("https", rest)) => (true, rest),
+        _ => {
+            return Err(invalid(format!(
+                "'{}' is not an http:// or https:// URL",
+                url
+            )))
+        }
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, "/"),
+    };
+    // an IPv6 address is bracketed, and may contain colons itself
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| invalid(format!("invalid port in '{}'", url)))?;
+            (host, port)
+        }
+        _ => (authority, if tls { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(invalid(format!("no host in '{}'", url)));
+    }
+    Ok(Url {
+        tls,
+        host,
+        port,
+        path,
+    })
+}
+
+/// POSTs `body` to `url`, failing unless the server answers with a 2xx status.
+pub fn post(url: &str, body: &json::Value) -> io::Result<()> {
+    post_with(url, body, || relay::client_config(None))
+}
+
+/// Posts as [`post`] does, with the TLS settings of `tls` for https:// URLs.
+fn post_with(
+    url: &str,
+    body: &json::Value,
+    tls: impl FnOnce() -> io::Result<Arc<ClientConfig>>,
+) -> io::Result<()> {
+    let url = parse_url(url)?;
+    let stream = connect(&url)?;
+    let body = body.to_string();
+    if !url.tls {
+        return send_request(stream, &url, &body);
+    }
+    let name = ServerName::try_from(url.host.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let conn = ClientConnection::new(tls()?, name).map_err(io::Error::other)?;
+    send_request(StreamOwned::new(conn, stream), &url, &body)
+}
+
+/// Connects to the host of `url`, trying each of its addresses in turn.
+fn connect(url: &Url) -> io::Result<TcpStream> {
+    let mut last_err = invalid(format!("{} did not resolve to any address", url.host));
+    let mut stream = None;
+    for addr in (url.host, url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    let stream = stream.ok_or(last_err)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+fn send_request(mut stream: impl Read + Write, url: &Url, body: &str) -> io::Result<()> {
+    let host = if url.host.contains(':') {
+        format!("[{}]:{}", url.host, url.port)
+    } else {
+        format!("{}:{}", url.host, url.port)
+    };
+    // all at once, rather than a TLS record for each part
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: wol-cli/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        host,
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    let status = status.trim_end();
+    match status.split(' ').nth(1) {
+        Some(code) if code.starts_with('2') && status.starts_with("HTTP/") => Ok(()),
+        Some(_) if status.starts_with("HTTP/") => Err(io::Error::other(format!(
+            "the server answered '{}'",
+            status
+        ))),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the server did not answer with HTTP",
+        )),
+    }
+}
+
+#[test]
+fn test_parse_url() {
+    assert_eq!(
+        parse_url("http://localhost:8080/hooks/wol").unwrap(),
+        Url {
+            tls: false,
+            host: "localhost",
+            port: 8080,
+            path: "/hooks/wol"
+        }
+    );
+    assert_eq!(
+        parse_url("http://[::1]/").unwrap(),
+        Url {
+            tls: false,
+            host: "::1",
+            port: 80,
+            path: "/"
+        }
+    );
+    assert_eq!(parse_url("http://ntfy.local").unwrap().path, "/");
+    assert_eq!(
+        parse_url("https://ntfy.sh/wol").unwrap(),
+        Url {
+            tls: true,
+            host: "ntfy.sh",
+            port: 443,
+            path: "/wol"
+        }
+    );
+    assert!(parse_url("ftp://example.com").is_err());
+    assert!(parse_url("http://example.com:http/").is_err());
+    assert!(parse_url("http:///path").is_err());
+}
+
+#[test]
+fn test_post() {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/wol", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"}") {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let body = json::Value::object([("result", "woken".into())]);
+    post(&url, &body).unwrap();
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /wol HTTP/1.1\r\n"));
+    assert!(request.contains("Content-Type: application/json\r\n"));
+    assert!(request.ends_with("\r\n\r\n{\"result\":\"woken\"}"));
+}
+
+#[test]
+fn test_post_https() {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use rustls::ServerConnection;
+
+    let dir = std::env::temp_dir().join(format!("wol-cli-test-notify-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let signed = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::write(&cert, signed.cert.pem()).unwrap();
+    std::fs::write(&key, signed.key_pair.serialize_pem()).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}/wol",
+        listener.local_addr().unwrap().port()
+    );
+    let config = relay::server_config(&cert, &key).unwrap();
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let conn = ServerConnection::new(config).unwrap();
+        let mut stream = StreamOwned::new(conn, stream);
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"}") {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let body = json::Value::object([("result", "woken".into())]);
+    post_with(&url, &body, || relay::client_config(Some(&cert))).unwrap();
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST /wol HTTP/1.1\r\n"));
+    assert!(request.ends_with("\r\n\r\n{\"result\":\"woken\"}"));
+
+    // the certificate is not one of a public certificate authority
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}/wol",
+        listener.local_addr().unwrap().port()
+    );
+    let config = relay::server_config(&cert, &key).unwrap();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let conn = ServerConnection::new(config).unwrap();
+        let _ = StreamOwned::new(conn, stream).read(&mut [0; 1]);
+    });
+    assert!(post(&url, &body).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,168 @@
+//! The vendors of network interfaces, identified by the organizationally unique identifier (OUI)
+//! in the first three octets of their MAC address.
+//!
+//! The full IEEE registry is read from `~/.cache/wol/oui.txt`, or where distributions install it,
+//! the first time a vendor is looked up. A few common vendors are built in for systems without
+//! it.
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use wol_cli::MacAddr;
+
+use crate::dirs;
+
+/// Where the IEEE registry, or nmap's copy of it, is installed, in order of preference.
+const DATABASES: &[&str] = &[
+    "/usr/share/ieee-data/oui.txt",
+    "/usr/share/hwdata/oui.txt",
+    "/usr/share/misc/oui.txt",
+    "/usr/share/nmap/nmap-mac-prefixes",
+];
+
+/// A few vendors commonly seen on home and lab networks, sorted by OUI.
+const VENDORS: &[([u8; 3], &str)] = &[
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x04, 0x4b], "NVIDIA"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x08, 0x9b], "QNAP"),
+    ([0x00, 0x09, 0x0f], "Fortinet"),
+    ([0x00, 0x0a, 0x95], "Apple"),
+    ([0x00, 0x0c, 0x29], "VMware"),
+    ([0x00, 0x0d, 0xb9], "PC Engines"),
+    ([0x00, 0x0e, 0xc6], "ASIX"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x15, 0x17], "Intel"),
+    ([0x00, 0x15, 0x5d], "Microsoft Hyper-V"),
+    ([0x00, 0x16, 0x3e], "Xen"),
+    ([0x00, 0x16, 0xcb], "Apple"),
+    ([0x00, 0x17, 0x88], "Philips Hue"),
+    ([0x00, 0x18, 0x0a], "Cisco Meraki"),
+    ([0x00, 0x1a, 0x11], "Google"),
+    ([0x00, 0x1b, 0x21], "Intel"),
+    ([0x00, 0x1c, 0x42], "Parallels"),
+    ([0x00, 0x1c, 0xb3], "Apple"),
+    ([0x00, 0x1e, 0xc2], "Apple"),
+    ([0x00, 0x25, 0x00], "Apple"),
+    ([0x00, 0x25, 0x90], "Super Micro"),
+    ([0x00, 0x26, 0xb9], "Dell"),
+    ([0x00, 0x27, 0x22], "Ubiquiti"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x90, 0xa9], "Western Digital"),
+    ([0x00, 0xd8, 0x61], "Micro-Star"),
+    ([0x00, 0xe0, 0x4c], "Realtek"),
+    ([0x04, 0x18, 0xd6], "Ubiquiti"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x18, 0xb4, 0x30], "Nest"),
+    ([0x24, 0x5e, 0xbe], "QNAP"),
+    ([0x24, 0xa4, 0x3c], "Ubiquiti"),
+    ([0x28, 0xcd, 0xc1], "Raspberry Pi"),
+    ([0x2c, 0xcf, 0x67], "Raspberry Pi"),
+    ([0x52, 0x54, 0x00], "QEMU"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi"),
+    ([0xd8, 0x3a, 0xdd], "Raspberry Pi"),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi"),
+    ([0xe4, 0x5f, 0x01], "Raspberry Pi"),
+];
+
+/// Reads the vendors of a database, either the IEEE's `oui.txt`, with lines like
+/// `00-11-32   (hex)\t\tSynology Incorporated`, or nmap's, with lines like
+/// `001132 Synology Incorporated`.
+fn parse(contents: &str) -> HashMap<[u8; 3], String> {
+    let mut vendors = HashMap::new();
+    for line in contents.lines() {
+        let (prefix, vendor) = match line.split_once("(hex)") {
+            Some((prefix, vendor)) => (prefix.trim().replace('-', ""), vendor),
+            // the IEEE's lines in base 16 repeat those in hex
+            None if line.contains("(base 16)") || line.starts_with('#') => continue,
+            None => match line.split_once(char::is_whitespace) {
+                Some((prefix, vendor)) => (prefix.to_string(), vendor),
+                _ => continue,
+            },
+        };
+        let vendor = vendor.trim();
+        if prefix.len() != 6 || vendor.is_empty() {
+            continue;
+        }
+        let Ok(oui) = u32::from_str_radix(&prefix, 16) else {
+            continue;
+        };
+        let [_, a, b, c] = oui.to_be_bytes();
+        vendors
+            .entry([a, b, c])
+            .or_insert_with(|| vendor.to_string());
+    }
+    vendors
+}
+
+/// The vendors of the first database found, loaded once.
+fn database() -> &'static HashMap<[u8; 3], String> {
+    static DATABASE: OnceLock<HashMap<[u8; 3], String>> = OnceLock::new();
+    DATABASE.get_or_init(|| {
+        let cached = dirs::cache().map(|dir| dir.join("oui.txt"));
+        cached
+            .into_iter()
+            .chain(DATABASES.iter().map(PathBuf::from))
+            .find_map(|path| fs::read_to_string(path).ok())
+            .map(|contents| parse(&contents))
+            .unwrap_or_default()
+    })
+}
+
+/// The vendor of the interface with the given MAC address, if known. Addresses that are locally
+/// administered, e.g. those of containers and most virtual machines, have no vendor.
+pub fn vendor(mac: MacAddr) -> Option<&'static str> {
+    lookup(database(), mac)
+}
+
+fn lookup(database: &HashMap<[u8; 3], String>, mac: MacAddr) -> Option<&str> {
+    let octets = mac.octets();
+    let oui = [octets[0], octets[1], octets[2]];
+    if let Some(vendor) = database.get(&oui) {
+        return Some(vendor);
+    }
+    if let Ok(i) = VENDORS.binary_search_by_key(&oui, |(oui, _)| *oui) {
+        return Some(VENDORS[i].1);
+    }
+    (octets[0] & 0x02 != 0).then_some("locally administered")
+}
+
+#[test]
+fn test_vendor() {
+    assert!(VENDORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+    // the built in vendors, without a database
+    let database = HashMap::new();
+    let vendor = |mac: &str| lookup(&database, mac.parse().unwrap());
+    assert_eq!(vendor("00:11:32:12:34:56"), Some("Synology"));
+    assert_eq!(vendor("B8-27-EB-00-00-01"), Some("Raspberry Pi"));
+    assert_eq!(vendor("5254.0012.3456"), Some("QEMU"));
+    assert_eq!(vendor("02:42:ac:11:00:02"), Some("locally administered"));
+    assert_eq!(vendor("00:00:01:00:00:00"), None);
+}
+
+#[test]
+fn test_parse() {
+    let ieee = "OUI/MA-L                                                    Organization\n\
+                company_id                                                  Organization\n\
+                \n\
+                00-11-32   (hex)\t\tSynology Incorporated\n\
+                001132     (base 16)\t\tSynology Incorporated\n\
+                \t\t\t\tTaipei  Taiwan\n";
+    let vendors = parse(ieee);
+    assert_eq!(vendors.len(), 1);
+    assert_eq!(vendors[&[0x00, 0x11, 0x32]], "Synology Incorporated");
+
+    let nmap = "# $Id$ generated with make-mac-prefixes.pl\n\
+                000000 Xerox\n\
+                B827EB Raspberry Pi Foundation\n";
+    let vendors = parse(nmap);
+    assert_eq!(vendors.len(), 2);
+    assert_eq!(vendors[&[0xb8, 0x27, 0xeb]], "Raspberry Pi Foundation");
+    assert_eq!(
+        lookup(&vendors, "b8:27:eb:00:00:01".parse().unwrap()),
+        Some("Raspberry Pi Foundation")
+    );
+}
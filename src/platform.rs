@@ -0,0 +1,498 @@
+//! Thin platform bindings for what the command needs of the system beyond sending packets:
+//! local interfaces, ICMP and packet sockets, sockets passed by systemd, the local time zone and
+//! the user id.
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+
+#[cfg(unix)]
+mod ffi {
+    use std::os::raw::{c_char, c_int, c_long, c_uint, c_void};
+
+    pub const AF_INET: c_int = 2;
+    #[cfg(target_os = "linux")]
+    pub const AF_INET6: c_int = 10;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub const AF_INET6: c_int = 30;
+    #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+    pub const AF_INET6: c_int = 28;
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    )))]
+    pub const AF_INET6: c_int = 24;
+
+    pub const SOCK_DGRAM: c_int = 2;
+    pub const SOCK_RAW: c_int = 3;
+    pub const IPPROTO_ICMP: c_int = 1;
+    pub const IPPROTO_ICMPV6: c_int = 58;
+
+    pub const IFF_UP: c_uint = 0x1;
+    pub const IFF_BROADCAST: c_uint = 0x2;
+    pub const IFF_LOOPBACK: c_uint = 0x8;
+
+    #[repr(C)]
+    pub struct sockaddr {
+        #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        pub sa_len: u8,
+        #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+        pub sa_family: u8,
+        #[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+        pub sa_family: u16,
+        pub sa_data: [u8; 14],
+    }
+
+    #[repr(C)]
+    pub struct tm {
+        pub tm_sec: c_int,
+        pub tm_min: c_int,
+        pub tm_hour: c_int,
+        pub tm_mday: c_int,
+        pub tm_mon: c_int,
+        pub tm_year: c_int,
+        pub tm_wday: c_int,
+        pub tm_yday: c_int,
+        pub tm_isdst: c_int,
+        pub tm_gmtoff: c_long,
+        pub tm_zone: *const c_char,
+    }
+
+    #[repr(C)]
+    pub struct ifaddrs {
+        pub ifa_next: *mut ifaddrs,
+        pub ifa_name: *mut c_char,
+        pub ifa_flags: c_uint,
+        pub ifa_addr: *mut sockaddr,
+        pub ifa_netmask: *mut sockaddr,
+        pub ifa_broadaddr: *mut sockaddr,
+        pub ifa_data: *mut c_void,
+    }
+
+    extern "C" {
+        pub fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        pub fn getifaddrs(ifap: *mut *mut ifaddrs) -> c_int;
+        pub fn freeifaddrs(ifa: *mut ifaddrs);
+        pub fn setsockopt(
+            socket: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: c_uint,
+        ) -> c_int;
+        pub fn if_nametoindex(name: *const c_char) -> c_uint;
+        pub fn tzset();
+        pub fn localtime_r(time: *const c_long, result: *mut tm) -> *mut tm;
+        pub fn geteuid() -> c_uint;
+    }
+}
+
+#[cfg(unix)]
+fn setsockopt(socket: &UdpSocket, level: i32, name: i32, value: &[u8]) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let ret = unsafe {
+        ffi::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            value.as_ptr() as *const _,
+            value.len() as u32,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the named network interface.
+#[cfg(unix)]
+fn interface_index(interface: &str) -> io::Result<u32> {
+    let name = std::ffi::CString::new(interface)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+    match unsafe { ffi::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no such interface '{}'", interface),
+        )),
+        index => Ok(index),
+    }
+}
+
+/// Returns the index of the named network interface.
+#[cfg(not(unix))]
+fn interface_index(_interface: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "looking up interfaces is not supported on this platform",
+    ))
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(target_os = "linux")]
+pub fn bind_to_device(socket: &UdpSocket, interface: &str) -> io::Result<()> {
+    const SOL_SOCKET: i32 = 1;
+    const SO_BINDTODEVICE: i32 = 25;
+
+    // validates the name and gives a friendlier error than ENODEV
+    interface_index(interface)?;
+    setsockopt(socket, SOL_SOCKET, SO_BINDTODEVICE, interface.as_bytes())
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn bind_to_device(socket: &UdpSocket, interface: &str) -> io::Result<()> {
+    const IPPROTO_IP: i32 = 0;
+    const IP_BOUND_IF: i32 = 25;
+
+    let index = interface_index(interface)?;
+    setsockopt(socket, IPPROTO_IP, IP_BOUND_IF, &index.to_ne_bytes())
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+pub fn bind_to_device(_socket: &UdpSocket, _interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is not supported on this platform",
+    ))
+}
+
+/// Opens a socket for sending ICMP (or ICMPv6) echo requests, preferring the unprivileged ping
+/// sockets of Linux and macOS over raw sockets, which require root or CAP_NET_RAW. Returns the
+/// socket along with whether it is raw, in which case replies include the IPv4 header and
+/// every ICMP message received by the host.
+///
+/// The socket is wrapped in a `UdpSocket` for its sendto and recvfrom, which work the same for
+/// ICMP as long as the port of the addresses is 0.
+#[cfg(unix)]
+pub fn icmp_socket(ipv6: bool) -> io::Result<(UdpSocket, bool)> {
+    use std::os::fd::FromRawFd;
+
+    let (domain, protocol) = if ipv6 {
+        (ffi::AF_INET6, ffi::IPPROTO_ICMPV6)
+    } else {
+        (ffi::AF_INET, ffi::IPPROTO_ICMP)
+    };
+    for (ty, raw) in [(ffi::SOCK_DGRAM, false), (ffi::SOCK_RAW, true)] {
+        let fd = unsafe { ffi::socket(domain, ty, protocol) };
+        if fd >= 0 {
+            return Ok((unsafe { UdpSocket::from_raw_fd(fd) }, raw));
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "ICMP sockets require root, CAP_NET_RAW or a matching net.ipv4.ping_group_range",
+    ))
+}
+
+/// Opens a socket for sending ICMP echo requests.
+#[cfg(not(unix))]
+pub fn icmp_socket(_ipv6: bool) -> io::Result<(UdpSocket, bool)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ICMP sockets are not supported on this platform",
+    ))
+}
+
+/// Returns the offset of the local time zone from UTC in seconds at the given Unix timestamp,
+/// which differs with daylight saving time.
+#[cfg(unix)]
+pub fn utc_offset(timestamp: u64) -> io::Result<i32> {
+    let time = timestamp as std::os::raw::c_long;
+    let mut tm = std::mem::MaybeUninit::<ffi::tm>::uninit();
+    // localtime_r need not read the TZ variable and /etc/localtime by itself
+    let tm = unsafe {
+        ffi::tzset();
+        if ffi::localtime_r(&time, tm.as_mut_ptr()).is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        tm.assume_init()
+    };
+    Ok(tm.tm_gmtoff as i32)
+}
+
+/// Returns the user id the process runs as.
+#[cfg(unix)]
+pub fn euid() -> u32 {
+    unsafe { ffi::geteuid() }
+}
+
+/// Returns the offset of the local time zone from UTC, taken to be UTC itself.
+#[cfg(not(unix))]
+pub fn utc_offset(_timestamp: u64) -> io::Result<i32> {
+    Ok(0)
+}
+
+/// An IPv4 address assigned to a local network interface.
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub name: String,
+    pub addr: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+impl Interface {
+    /// The directed broadcast address of the interface's subnet.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) | !u32::from(self.netmask))
+    }
+}
+
+/// Reads the IPv4 address out of a `sockaddr_in`, skipping other address families.
+#[cfg(unix)]
+unsafe fn sockaddr_ipv4(addr: *const ffi::sockaddr) -> Option<Ipv4Addr> {
+    if addr.is_null() || (*addr).sa_family as i32 != ffi::AF_INET {
+        return None;
+    }
+    // sin_port occupies the first two bytes of sa_data, followed by sin_addr
+    let data = &(*addr).sa_data;
+    Some(Ipv4Addr::new(data[2], data[3], data[4], data[5]))
+}
+
+/// Lists the IPv4 addresses of all local interfaces that are up, not loopback and support
+/// broadcasting.
+#[cfg(unix)]
+pub fn interfaces() -> io::Result<Vec<Interface>> {
+    let mut head = std::ptr::null_mut();
+    if unsafe { ffi::getifaddrs(&mut head) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut interfaces = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        let ifa = unsafe { &*cursor };
+        cursor = ifa.ifa_next;
+
+        let wanted = ffi::IFF_UP | ffi::IFF_BROADCAST;
+        if ifa.ifa_flags & wanted != wanted || ifa.ifa_flags & ffi::IFF_LOOPBACK != 0 {
+            continue;
+        }
+        let (Some(addr), Some(netmask)) = (unsafe { sockaddr_ipv4(ifa.ifa_addr) }, unsafe {
+            sockaddr_ipv4(ifa.ifa_netmask)
+        }) else {
+            continue;
+        };
+        let name = unsafe { std::ffi::CStr::from_ptr(ifa.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+
+        interfaces.push(Interface {
+            name,
+            addr,
+            netmask,
+        });
+    }
+    unsafe { ffi::freeifaddrs(head) };
+
+    Ok(interfaces)
+}
+
+/// Lists the IPv4 addresses of all local interfaces that are up, not loopback and support
+/// broadcasting.
+#[cfg(not(unix))]
+pub fn interfaces() -> io::Result<Vec<Interface>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "listing interfaces is not supported on this platform",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod packet {
+    use std::os::raw::c_int;
+
+    pub const AF_PACKET: c_int = 17;
+    pub const SOCK_RAW: c_int = 3;
+
+    #[repr(C)]
+    pub struct sockaddr_ll {
+        pub sll_family: u16,
+        pub sll_protocol: u16,
+        pub sll_ifindex: c_int,
+        pub sll_hatype: u16,
+        pub sll_pkttype: u8,
+        pub sll_halen: u8,
+        pub sll_addr: [u8; 8],
+    }
+
+    extern "C" {
+        pub fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        pub fn bind(socket: c_int, addr: *const sockaddr_ll, addrlen: u32) -> c_int;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inherit {
+    use std::os::raw::{c_int, c_void};
+
+    pub const SOL_SOCKET: c_int = 1;
+    pub const SO_TYPE: c_int = 3;
+    pub const SOCK_STREAM: c_int = 1;
+    pub const SOCK_DGRAM: c_int = 2;
+    pub const F_SETFD: c_int = 2;
+    pub const FD_CLOEXEC: c_int = 1;
+
+    extern "C" {
+        pub fn getsockopt(
+            socket: c_int,
+            level: c_int,
+            name: c_int,
+            value: *mut c_void,
+            len: *mut u32,
+        ) -> c_int;
+        pub fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    }
+}
+
+/// A listening socket inherited from the process that started this one.
+#[derive(Debug)]
+pub enum InheritedSocket {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+/// Takes ownership of the inherited socket `fd`, marking it close-on-exec so that the commands
+/// of hooks do not inherit it in turn.
+///
+/// # Safety
+///
+/// `fd` must be an open file descriptor not owned by anything else.
+#[cfg(target_os = "linux")]
+pub unsafe fn inherit_socket(fd: i32) -> io::Result<InheritedSocket> {
+    use std::os::fd::FromRawFd;
+
+    let mut ty: i32 = 0;
+    let mut len = std::mem::size_of::<i32>() as u32;
+    let ret = inherit::getsockopt(
+        fd,
+        inherit::SOL_SOCKET,
+        inherit::SO_TYPE,
+        &mut ty as *mut i32 as *mut _,
+        &mut len,
+    );
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if inherit::fcntl(fd, inherit::F_SETFD, inherit::FD_CLOEXEC) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    match ty {
+        inherit::SOCK_STREAM => Ok(InheritedSocket::Tcp(TcpListener::from_raw_fd(fd))),
+        inherit::SOCK_DGRAM => Ok(InheritedSocket::Udp(UdpSocket::from_raw_fd(fd))),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "file descriptor {} is neither a stream nor a datagram socket",
+                fd
+            ),
+        )),
+    }
+}
+
+/// Returns the hardware (MAC) address of the named network interface.
+#[cfg(target_os = "linux")]
+pub fn hardware_addr(interface: &str) -> io::Result<[u8; 6]> {
+    let path = format!("/sys/class/net/{}/address", interface);
+    let contents = std::fs::read_to_string(path).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no such interface '{}'", interface),
+        )
+    })?;
+
+    let mut addr = [0u8; 6];
+    let mut octets = contents.trim().split(':');
+    for byte in addr.iter_mut() {
+        *byte = octets
+            .next()
+            .and_then(|octet| u8::from_str_radix(octet, 16).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("interface '{}' has no ethernet address", interface),
+                )
+            })?;
+    }
+
+    Ok(addr)
+}
+
+/// Opens a packet socket bound to the named interface, receiving the frames of the given
+/// EtherType. Frames sent with `send` on the returned socket must include their ethernet header.
+/// Like [`icmp_socket`] the socket is wrapped in a `UdpSocket`, for its send, recv and read
+/// timeout. Requires root or CAP_NET_RAW.
+#[cfg(target_os = "linux")]
+pub fn packet_socket(interface: &str, ethertype: u16) -> io::Result<UdpSocket> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let index = interface_index(interface)?;
+    let protocol = ethertype.to_be();
+    let fd = unsafe { packet::socket(packet::AF_PACKET, packet::SOCK_RAW, protocol.into()) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "raw sockets require root or CAP_NET_RAW",
+            ));
+        }
+        return Err(err);
+    }
+    let socket = unsafe { UdpSocket::from_raw_fd(fd) };
+
+    let addr = packet::sockaddr_ll {
+        sll_family: packet::AF_PACKET as u16,
+        sll_protocol: protocol,
+        sll_ifindex: index as i32,
+        sll_hatype: 0,
+        sll_pkttype: 0,
+        sll_halen: 0,
+        sll_addr: [0; 8],
+    };
+    let ret = unsafe {
+        packet::bind(
+            socket.as_raw_fd(),
+            &addr,
+            std::mem::size_of::<packet::sockaddr_ll>() as u32,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(socket)
+}
+
+/// Returns the hardware (MAC) address of the named network interface.
+#[cfg(not(target_os = "linux"))]
+pub fn hardware_addr(_interface: &str) -> io::Result<[u8; 6]> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw ethernet is only supported on Linux",
+    ))
+}
+
+/// Opens a packet socket bound to the named interface.
+#[cfg(not(target_os = "linux"))]
+pub fn packet_socket(_interface: &str, _ethertype: u16) -> io::Result<UdpSocket> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw ethernet is only supported on Linux",
+    ))
+}
+
+#[test]
+fn test_interface_broadcast() {
+    let interface = Interface {
+        name: "eth0".into(),
+        addr: Ipv4Addr::new(192, 168, 10, 42),
+        netmask: Ipv4Addr::new(255, 255, 255, 0),
+    };
+    assert_eq!(interface.broadcast(), Ipv4Addr::new(192, 168, 10, 255));
+}
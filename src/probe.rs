@@ -0,0 +1,459 @@
+//! Probes whether a woken host has come up.
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wol_cli::MacAddr;
+
+use crate::platform;
+use crate::subnet::Subnet;
+
+/// How long to wait for a host to come up.
+pub const WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often a host is probed while waiting for it.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The delay before the first resent packet when waiting with [`wait_resending`].
+pub const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest delay between resent packets when waiting with [`wait_resending`].
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const ECHO_REQUEST: u8 = 8;
+const ECHO_REPLY: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+pub const ARP_REQUEST: u16 = 1;
+pub const ARP_REPLY: u16 = 2;
+
+/// The pause between the requests of an ARP scan.
+const SCAN_INTERVAL: Duration = Duration::from_micros(200);
+
+/// Identifies our echo requests among the other ICMP messages seen by raw sockets.
+const PAYLOAD: &[u8] = b"wol-cli";
+
+/// Whether a socket error means that nothing was received before the read timeout.
+pub fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// The internet checksum of RFC 1071.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| u32::from(chunk[0]) << 8 | u32::from(*chunk.get(1).unwrap_or(&0)))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Builds an echo request. ICMPv6 checksums cover a pseudo header and are always filled in by
+/// the kernel, so they are left blank.
+fn echo_request(ipv6: bool, id: u16, seq: u16) -> Vec<u8> {
+    let ty = if ipv6 { ECHO_REQUEST_V6 } else { ECHO_REQUEST };
+    let mut packet = vec![ty, 0, 0, 0];
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+    if !ipv6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// Checks whether a received message is the reply to our echo request. The identifier is only
+/// checked when given, as ping sockets replace it with one of their own.
+fn is_reply(mut packet: &[u8], ipv6: bool, id: Option<u16>, seq: u16) -> bool {
+    // raw IPv4 sockets, and ping sockets on macOS, receive the IP header too
+    if !ipv6 && packet.first().is_some_and(|byte| byte >> 4 == 4) {
+        let len = usize::from(packet[0] & 0xf) * 4;
+        packet = packet.get(len..).unwrap_or_default();
+    }
+    let [ty, _, _, _, id_hi, id_lo, seq_hi, seq_lo, payload @ ..] = packet else {
+        return false;
+    };
+
+    let expected = if ipv6 { ECHO_REPLY_V6 } else { ECHO_REPLY };
+    *ty == expected
+        && id.is_none_or(|id| id == u16::from_be_bytes([*id_hi, *id_lo]))
+        && u16::from_be_bytes([*seq_hi, *seq_lo]) == seq
+        && payload == PAYLOAD
+}
+
+/// Sends a single ICMP echo request to `ip`, returning whether it replied within `timeout`. The
+/// system's ping command is used instead where ICMP sockets are not available.
+pub fn ping(ip: IpAddr, timeout: Duration) -> io::Result<bool> {
+    static SEQ: AtomicU16 = AtomicU16::new(0);
+
+    let (socket, raw) = match platform::icmp_socket(ip.is_ipv6()) {
+        Ok(socket) => socket,
+        Err(_) => return ping_command(ip, timeout),
+    };
+    let id = process::id() as u16;
+    let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+    let request = echo_request(ip.is_ipv6(), id, seq);
+    match socket.send_to(&request, SocketAddr::new(ip, 0)) {
+        Ok(_) => {}
+        // there is no route to the host until its link is back up
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(err) => return Err(err),
+    }
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) if from.ip() == ip => {
+                if is_reply(&buf[..len], ip.is_ipv6(), raw.then_some(id), seq) {
+                    return Ok(true);
+                }
+            }
+            Ok(_) => {}
+            Err(err) if is_timeout(&err) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Pings `ip` once with the system's ping command.
+fn ping_command(ip: IpAddr, timeout: Duration) -> io::Result<bool> {
+    let secs = timeout.as_secs().max(1).to_string();
+    let mut command = process::Command::new("ping");
+    if cfg!(windows) {
+        command.args(["-n", "1", "-w", &timeout.as_millis().to_string()]);
+    } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+        command.args(["-c", "1", "-t", &secs]);
+    } else {
+        command.args(["-c", "1", "-W", &secs]);
+    }
+
+    let status = command
+        .arg(ip.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|err| io::Error::new(err.kind(), format!("unable to run ping: {}", err)))?;
+    Ok(status.success())
+}
+
+/// Builds an ARP frame sent to `to` by the host with `mac` and `ip`, about `target`.
+pub fn arp_frame(
+    operation: u16,
+    to: [u8; 6],
+    (mac, ip): ([u8; 6], Ipv4Addr),
+    (target_mac, target): ([u8; 6], Ipv4Addr),
+) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(42);
+    frame.extend_from_slice(&to);
+    frame.extend_from_slice(&mac);
+    frame.extend_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+    // ethernet hardware (1) and IPv4 protocol addresses, of 6 and 4 bytes
+    frame.extend_from_slice(&[0, 1, 0x08, 0x00, 6, 4]);
+    frame.extend_from_slice(&operation.to_be_bytes());
+    frame.extend_from_slice(&mac);
+    frame.extend_from_slice(&ip.octets());
+    frame.extend_from_slice(&target_mac);
+    frame.extend_from_slice(&target.octets());
+    frame
+}
+
+/// Builds a broadcast ARP request asking who has `target`.
+fn arp_request(mac: [u8; 6], ip: Ipv4Addr, target: Ipv4Addr) -> Vec<u8> {
+    arp_frame(ARP_REQUEST, [0xff; 6], (mac, ip), ([0; 6], target))
+}
+
+/// The sender's IP and MAC address of a received ARP reply.
+fn arp_reply_sender(frame: &[u8]) -> Option<(Ipv4Addr, MacAddr)> {
+    if frame.len() < 42
+        || frame[12..14] != ETHERTYPE_ARP.to_be_bytes()
+        || frame[20..22] != ARP_REPLY.to_be_bytes()
+    {
+        return None;
+    }
+    let ip = Ipv4Addr::new(frame[28], frame[29], frame[30], frame[31]);
+    let mac = MacAddr::try_from(&frame[22..28]).ok()?;
+    Some((ip, mac))
+}
+
+/// Checks whether a received frame is an ARP reply from `target`.
+fn is_arp_reply(frame: &[u8], target: Ipv4Addr) -> bool {
+    arp_reply_sender(frame).is_some_and(|(ip, _)| ip == target)
+}
+
+/// The local interface on whose subnet `ip` is.
+fn local_interface(ip: Ipv4Addr) -> io::Result<platform::Interface> {
+    platform::interfaces()?
+        .into_iter()
+        .find(|interface| {
+            let mask = u32::from(interface.netmask);
+            u32::from(interface.addr) & mask == u32::from(ip) & mask
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not on the subnet of any local interface", ip),
+            )
+        })
+}
+
+/// Asks who has `ip` over ARP, returning whether it answered within `timeout`. Hosts answer ARP
+/// even when they filter ICMP, but only hosts on the subnet of a local interface can be probed.
+/// Requires Linux and root or CAP_NET_RAW.
+pub fn arp(ip: IpAddr, timeout: Duration) -> io::Result<bool> {
+    let IpAddr::V4(ip) = ip else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "ARP only works for IPv4 addresses",
+        ));
+    };
+    let interface = local_interface(ip)?;
+    let mac = platform::hardware_addr(&interface.name)?;
+    let socket = platform::packet_socket(&interface.name, ETHERTYPE_ARP)?;
+    socket.send(&arp_request(mac, interface.addr, ip))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv(&mut buf) {
+            Ok(len) if is_arp_reply(&buf[..len], ip) => return Ok(true),
+            Ok(_) => {}
+            Err(err) if is_timeout(&err) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Asks every host on `subnet` who it is over ARP, returning the IP and MAC address of those that
+/// answered within `timeout` of the last request, sorted by IP address. The subnet has to be
+/// that of a local interface, or a part of it. Requires Linux and root or CAP_NET_RAW.
+pub fn arp_scan(subnet: &Subnet, timeout: Duration) -> io::Result<Vec<(Ipv4Addr, MacAddr)>> {
+    let interface = local_interface(subnet.network())?;
+    let mac = platform::hardware_addr(&interface.name)?;
+    let socket = platform::packet_socket(&interface.name, ETHERTYPE_ARP)?;
+    let sender = socket.try_clone()?;
+
+    let done = AtomicBool::new(false);
+    let mut found = BTreeMap::new();
+    thread::scope(|scope| {
+        let sending = scope.spawn(|| {
+            let result = subnet.hosts().try_for_each(|ip| {
+                sender.send(&arp_request(mac, interface.addr, ip))?;
+                // pace the requests so that the replies do not overflow the receive buffer
+                thread::sleep(SCAN_INTERVAL);
+                Ok(())
+            });
+            done.store(true, Ordering::Relaxed);
+            result
+        });
+
+        let mut buf = [0; 1500];
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None if done.load(Ordering::Relaxed) => {
+                    deadline = Some(Instant::now() + timeout);
+                    timeout
+                }
+                None => SCAN_INTERVAL * 100,
+            };
+            if remaining.is_zero() {
+                break;
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv(&mut buf) {
+                Ok(len) => {
+                    if let Some((ip, mac)) = arp_reply_sender(&buf[..len]) {
+                        if subnet.contains(ip) {
+                            found.entry(ip).or_insert(mac);
+                        }
+                    }
+                }
+                Err(err) if is_timeout(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        sending.join().unwrap()
+    })?;
+
+    Ok(found.into_iter().collect())
+}
+
+/// Attempts a TCP connection to `addr`, returning whether it was accepted within `timeout`.
+/// Refused and unroutable connections are expected while the host boots and are not errors.
+pub fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<bool> {
+    match TcpStream::connect_timeout(&addr, timeout) {
+        Ok(_) => Ok(true),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Calls `probe` about once a second until it succeeds or `timeout` elapses, passing it how long
+/// the attempt may take. Returns how long it took for the probe to succeed.
+pub fn wait(
+    timeout: Duration,
+    probe: impl FnMut(Duration) -> io::Result<bool>,
+) -> io::Result<Option<Duration>> {
+    wait_resending(timeout, timeout, || {}, probe)
+}
+
+/// Like [`wait`], but calls `resend` while the probe keeps failing, first after `backoff` and
+/// then doubling the delay up to 30 seconds.
+pub fn wait_resending(
+    timeout: Duration,
+    mut backoff: Duration,
+    mut resend: impl FnMut(),
+    mut probe: impl FnMut(Duration) -> io::Result<bool>,
+) -> io::Result<Option<Duration>> {
+    let start = Instant::now();
+    let mut next_send = start + backoff;
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+
+        let attempt = Instant::now();
+        if probe(remaining.min(PROBE_INTERVAL))? {
+            return Ok(Some(start.elapsed()));
+        }
+        if Instant::now() >= next_send {
+            resend();
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            next_send = Instant::now() + backoff;
+        }
+        let remaining = timeout.saturating_sub(start.elapsed());
+        thread::sleep(
+            PROBE_INTERVAL
+                .saturating_sub(attempt.elapsed())
+                .min(remaining),
+        );
+    }
+}
+
+#[test]
+fn test_echo() {
+    let request = echo_request(false, 0x1234, 7);
+    assert_eq!(&request[..8], [8, 0, 0x35, 0xbb, 0x12, 0x34, 0, 7]);
+    assert_eq!(checksum(&request), 0);
+
+    let mut reply = request.clone();
+    reply[0] = ECHO_REPLY;
+    assert!(is_reply(&reply, false, None, 7));
+    assert!(is_reply(&reply, false, Some(0x1234), 7));
+    assert!(!is_reply(&reply, false, Some(0x1235), 7));
+    assert!(!is_reply(&reply, false, None, 8));
+    assert!(!is_reply(&request, false, None, 7));
+
+    // as received by a raw socket
+    let mut packet = vec![0x45; 20];
+    packet.extend_from_slice(&reply);
+    assert!(is_reply(&packet, false, Some(0x1234), 7));
+
+    let mut reply = echo_request(true, 1, 2);
+    reply[0] = ECHO_REPLY_V6;
+    assert!(is_reply(&reply, true, None, 2));
+}
+
+#[test]
+fn test_wait() {
+    let mut attempts = 0;
+    let up = wait(Duration::from_secs(10), |timeout| {
+        assert!(timeout <= PROBE_INTERVAL);
+        attempts += 1;
+        Ok(true)
+    });
+    assert!(up.unwrap().unwrap() < PROBE_INTERVAL);
+    assert_eq!(attempts, 1);
+
+    let up = wait(Duration::from_millis(50), |_| Ok(false)).unwrap();
+    assert_eq!(up, None);
+    assert!(wait(
+        Duration::from_secs(10),
+        |_| Err(io::ErrorKind::Other.into())
+    )
+    .is_err());
+
+    let mut sent = Vec::new();
+    let start = Instant::now();
+    let up = wait_resending(
+        Duration::from_secs(5),
+        Duration::from_millis(100),
+        || sent.push(start.elapsed()),
+        |_| Ok(start.elapsed() > Duration::from_millis(2500)),
+    );
+    assert!(up.unwrap().is_some());
+    // probed at 0s, 1s and 2s, resending after the second and third probe
+    assert_eq!(sent.len(), 2);
+}
+
+#[test]
+fn test_arp() {
+    let mac = [0x02, 0, 0, 0, 0, 1];
+    let ip = Ipv4Addr::new(192, 168, 1, 2);
+    let target = Ipv4Addr::new(192, 168, 1, 20);
+    let request = arp_request(mac, ip, target);
+    assert_eq!(request.len(), 42);
+    assert_eq!(&request[12..14], [0x08, 0x06]);
+    assert!(!is_arp_reply(&request, target));
+
+    // the reply swaps the sender and target of the request
+    let mut reply = request.clone();
+    reply[21] = 2;
+    reply[22..28].copy_from_slice(&[0xaa; 6]);
+    reply[28..32].copy_from_slice(&target.octets());
+    assert!(is_arp_reply(&reply, target));
+    assert!(!is_arp_reply(&reply, ip));
+    assert_eq!(arp_reply_sender(&reply), Some((target, MacAddr([0xaa; 6]))));
+}
+
+#[test]
+fn test_connect() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    assert!(connect(addr, PROBE_INTERVAL).unwrap());
+
+    drop(listener);
+    assert!(!connect(addr, PROBE_INTERVAL).unwrap());
+}
@@ -0,0 +1,87 @@
+//! A progress bar on standard error for waking many targets at once, shown in place of a line
+//! for each packet sent.
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How many targets it takes for the bar to be shown.
+const MIN_TARGETS: usize = 10;
+
+/// How many characters the bar itself is wide.
+const WIDTH: usize = 30;
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The bar while targets are being woken, cleared when dropped.
+pub struct Progress {
+    total: usize,
+    done: usize,
+}
+
+impl Progress {
+    /// Shows a bar for waking `total` targets, if there are enough of them and standard error
+    /// is a terminal.
+    pub fn start(total: usize) -> Option<Progress> {
+        if total < MIN_TARGETS || !io::stderr().is_terminal() {
+            return None;
+        }
+        ACTIVE.store(true, Ordering::Relaxed);
+        let progress = Progress { total, done: 0 };
+        progress.draw("");
+        Some(progress)
+    }
+
+    /// Clears the bar, so that errors printed before it is drawn again start on a line of
+    /// their own.
+    pub fn clear(&self) {
+        eprint!("\r\x1b[K");
+    }
+
+    /// Counts a target as done, drawing the bar again with its name.
+    pub fn advance(&mut self, name: &str) {
+        self.done += 1;
+        self.draw(name);
+    }
+
+    fn draw(&self, name: &str) {
+        let mut stderr = io::stderr();
+        let _ = write!(stderr, "\r\x1b[K{}", bar(self.done, self.total, name));
+        let _ = stderr.flush();
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Whether a bar is shown, leaving out the lines of the packets sent.
+pub fn active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Formats the bar after `done` of `total` targets, the last of which was `name`.
+fn bar(done: usize, total: usize, name: &str) -> String {
+    let filled = WIDTH * done / total.max(1);
+    format!(
+        "[{}{}] {}/{} {}",
+        "#".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        done,
+        total,
+        name
+    )
+    .trim_end()
+    .to_string()
+}
+
+#[test]
+fn test_bar() {
+    assert_eq!(bar(0, 20, ""), format!("[{}] 0/20", " ".repeat(30)));
+    assert_eq!(
+        bar(5, 20, "nas"),
+        format!("[{}{}] 5/20 nas", "#".repeat(7), " ".repeat(23))
+    );
+    assert_eq!(bar(20, 20, "pc"), format!("[{}] 20/20 pc", "#".repeat(30)));
+}
@@ -0,0 +1,142 @@
+//! A sleep proxy, answering ARP for hosts that are asleep so that connections to them reach
+//! this one instead, which wakes them and steps aside once they are up.
+use std::net::Ipv4Addr;
+
+use crate::probe::{self, ARP_REPLY, ARP_REQUEST, ETHERTYPE_ARP};
+
+/// Receives the frames of every EtherType.
+pub const ETHERTYPE_ALL: u16 = 0x0003;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const PROTOCOL_TCP: u8 = 6;
+const TCP_SYN: u8 = 0x02;
+const TCP_ACK: u8 = 0x10;
+
+/// What a frame received by the proxy is about.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// An ARP request asking who has `target`, from the host with `ip`.
+    WhoHas {
+        ip: Ipv4Addr,
+        target: Ipv4Addr,
+    },
+
+    /// A TCP connection attempt to `ip`, i.e. a SYN without an ACK.
+    Connect {
+        source: Ipv4Addr,
+        ip: Ipv4Addr,
+        port: u16,
+    },
+
+    Other,
+}
+
+impl Frame {
+    /// Reads a frame, returning the MAC address it was sent from along with what it is about.
+    pub fn parse(frame: &[u8]) -> Option<([u8; 6], Frame)> {
+        let mac: [u8; 6] = frame.get(6..12)?.try_into().ok()?;
+        let ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+        let payload = frame.get(14..)?;
+        let ipv4 = |bytes: &[u8]| Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
+        match ethertype {
+            ETHERTYPE_ARP if payload.get(6..8)? == ARP_REQUEST.to_be_bytes() => {
+                let ip = ipv4(payload.get(14..18)?);
+                let target = ipv4(payload.get(24..28)?);
+                Some((mac, Frame::WhoHas { ip, target }))
+            }
+            ETHERTYPE_IPV4 if payload.first()? >> 4 == 4 && payload.get(9)? == &PROTOCOL_TCP => {
+                let tcp = payload.get(usize::from(payload[0] & 0xf) * 4..)?;
+                let flags = *tcp.get(13)?;
+                if flags & (TCP_SYN | TCP_ACK) != TCP_SYN {
+                    return Some((mac, Frame::Other));
+                }
+                let connect = Frame::Connect {
+                    source: ipv4(payload.get(12..16)?),
+                    ip: ipv4(payload.get(16..20)?),
+                    port: u16::from_be_bytes(tcp.get(2..4)?.try_into().ok()?),
+                };
+                Some((mac, connect))
+            }
+            _ => Some((mac, Frame::Other)),
+        }
+    }
+}
+
+/// Answers the ARP request of the host with `to` that asked who has `ip`, claiming it for `mac`.
+pub fn claim(mac: [u8; 6], ip: Ipv4Addr, to: ([u8; 6], Ipv4Addr)) -> Vec<u8> {
+    probe::arp_frame(ARP_REPLY, to.0, (mac, ip), to)
+}
+
+/// Announces to every host that `ip` is at `mac`, so that they update their ARP caches.
+pub fn announce(mac: [u8; 6], ip: Ipv4Addr) -> Vec<u8> {
+    probe::arp_frame(ARP_REQUEST, [0xff; 6], (mac, ip), ([0; 6], ip))
+}
+
+/// Announces from `own` that `ip` is back at the host with `mac`. The frame is sent from our
+/// own address, as switches would forward the host's traffic here otherwise.
+pub fn hand_back(own: [u8; 6], mac: [u8; 6], ip: Ipv4Addr) -> Vec<u8> {
+    let mut frame = announce(mac, ip);
+    frame[6..12].copy_from_slice(&own);
+    frame
+}
+
+#[test]
+fn test_parse() {
+    let proxy = [0x02, 0, 0, 0, 0, 1];
+    let client = [0x02, 0, 0, 0, 0, 2];
+    let nas = Ipv4Addr::new(192, 168, 1, 20);
+    let source = Ipv4Addr::new(192, 168, 1, 30);
+
+    let request = probe::arp_frame(ARP_REQUEST, [0xff; 6], (client, source), ([0; 6], nas));
+    assert_eq!(
+        Frame::parse(&request),
+        Some((
+            client,
+            Frame::WhoHas {
+                ip: source,
+                target: nas
+            }
+        ))
+    );
+    let handed_back = hand_back(proxy, client, nas);
+    assert_eq!(&handed_back[6..12], proxy);
+    assert_eq!(&handed_back[22..28], client);
+
+    let reply = claim(proxy, nas, (client, source));
+    assert_eq!(&reply[..6], client);
+    assert_eq!(Frame::parse(&reply), Some((proxy, Frame::Other)));
+    assert_eq!(
+        Frame::parse(&announce(proxy, nas)),
+        Some((
+            proxy,
+            Frame::WhoHas {
+                ip: nas,
+                target: nas
+            }
+        ))
+    );
+
+    let mut syn = proxy.to_vec();
+    syn.extend_from_slice(&client);
+    syn.extend_from_slice(&[0x08, 0x00, 0x45, 0, 0, 40, 0, 0, 0, 0, 64, 6, 0, 0]);
+    syn.extend_from_slice(&source.octets());
+    syn.extend_from_slice(&nas.octets());
+    syn.extend_from_slice(&[
+        0xc3, 0x50, 0x01, 0xbd, 0, 0, 0, 0, 0, 0, 0, 0, 0x50, TCP_SYN,
+    ]);
+    syn.extend_from_slice(&[0; 6]);
+    assert_eq!(
+        Frame::parse(&syn),
+        Some((
+            client,
+            Frame::Connect {
+                source,
+                ip: nas,
+                port: 445
+            }
+        ))
+    );
+    let len = syn.len();
+    syn[len - 7] |= TCP_ACK;
+    assert_eq!(Frame::parse(&syn), Some((client, Frame::Other)));
+    assert_eq!(Frame::parse(&syn[..20]), None);
+}
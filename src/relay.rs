@@ -0,0 +1,439 @@
+//! The relay protocol, used to ask a wol-cli relay on a remote network to broadcast a magic
+//! packet on our behalf, e.g. when the ISP blocks inbound UDP broadcasts.
+//!
+//! The protocol is line based over TCP: the client sends `WAKE <mac> [password]\n` and the relay
+//! answers with `OK\n` once the packet has been broadcast, or `ERR <reason>\n`. Relays
+//! given with the `tls://` scheme are spoken to over TLS, as a relay with a certificate answers.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::StreamOwned;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection};
+use thiserror::Error;
+use wol_cli::wol::{self, WolError};
+use wol_cli::MacAddr;
+
+/// TCP port a relay listens on unless told otherwise.
+pub const DEFAULT_RELAY_PORT: u16 = 7009;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolves `relay` given as "host:port", falling back to [`DEFAULT_RELAY_PORT`] when there is
+/// no port.
+fn resolve(relay: &str) -> io::Result<Vec<SocketAddr>> {
+    match relay.to_socket_addrs() {
+        Ok(addrs) => Ok(addrs.collect()),
+        Err(_) => Ok((relay, DEFAULT_RELAY_PORT).to_socket_addrs()?.collect()),
+    }
+}
+
+/// The host of `relay` given as "host:port" or "host", to check the certificate of the relay
+/// against.
+fn host(relay: &str) -> &str {
+    if let Some(bracketed) = relay.strip_prefix('[') {
+        return bracketed
+            .split_once(']')
+            .map_or(bracketed, |(host, _)| host);
+    }
+    match relay.rsplit_once(':') {
+        // a colon of its own is that of a port, those of an IPv6 address come in numbers
+        Some((host, _)) if !host.contains(':') => host,
+        _ => relay,
+    }
+}
+
+/// The TLS settings of speaking to relays, trusting the certificates of the PEM file `ca`, or
+/// those of the public certificate authorities Mozilla trusts.
+pub fn client_config(ca: Option<&Path>) -> io::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    match ca {
+        Some(ca) => {
+            for cert in CertificateDer::pem_file_iter(ca).map_err(pem_error)? {
+                roots
+                    .add(cert.map_err(pem_error)?)
+                    .map_err(io::Error::other)?;
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// The TLS settings of a relay answering with the certificate chain and private key of the PEM
+/// files `cert` and `key`.
+pub fn server_config(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .map_err(pem_error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(pem_error)?;
+    if chain.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} holds no certificate", cert.display()),
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(key).map_err(pem_error)?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Arc::new(config))
+}
+
+fn pem_error(err: rustls::pki_types::pem::Error) -> io::Error {
+    match err {
+        rustls::pki_types::pem::Error::Io(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+/// Asks the relay to wake the given MAC address, optionally with a SecureOn password. A relay
+/// given as `tls://host:port` is spoken to over TLS, trusting the certificates of the PEM file
+/// `ca` if given.
+pub fn request_wake(
+    relay: &str,
+    mac: MacAddr,
+    password: Option<&str>,
+    ca: Option<&Path>,
+) -> Result<(), WolError> {
+    let failed = |err: io::Error| match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => WolError::Timeout,
+        _ => WolError::Send(err),
+    };
+    let mut last_err = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("could not resolve relay '{}'", relay),
+    );
+    let (tls, relay) = match relay.strip_prefix("tls://") {
+        Some(relay) => {
+            let name = ServerName::try_from(host(relay).to_string())
+                .map_err(|err| failed(io::Error::new(io::ErrorKind::InvalidInput, err)))?;
+            (Some((client_config(ca).map_err(failed)?, name)), relay)
+        }
+        None => (None, relay),
+    };
+    for addr in resolve(relay).map_err(failed)? {
+        let stream = match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(stream) => stream,
+            Err(err) => {
+                last_err = err;
+                continue;
+            }
+        };
+        let sent = stream
+            .set_read_timeout(Some(TIMEOUT))
+            .and_then(|()| stream.set_write_timeout(Some(TIMEOUT)))
+            .and_then(|()| match &tls {
+                Some((config, name)) => {
+                    let conn = ClientConnection::new(config.clone(), name.clone())
+                        .map_err(io::Error::other)?;
+                    send_request(StreamOwned::new(conn, stream), mac, password)
+                }
+                None => send_request(stream, mac, password),
+            });
+        return sent.map_err(failed);
+    }
+
+    Err(failed(last_err))
+}
+
+fn send_request(
+    mut stream: impl Read + Write,
+    mac: MacAddr,
+    password: Option<&str>,
+) -> io::Result<()> {
+    match password {
+        Some(password) => writeln!(stream, "WAKE {} {}", mac, password)?,
+        None => writeln!(stream, "WAKE {}", mac)?,
+    }
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    let response = response.trim_end();
+    match response.split_once(' ') {
+        _ if response == "OK" => Ok(()),
+        Some(("ERR", reason)) => Err(io::Error::other(format!("relay refused: {}", reason))),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected relay response '{}'", response),
+        )),
+    }
+}
+
+/// Where a relay receives magic packets, written as "wan0:9" for a port on an interface, or as
+/// a local address like "192.168.1.2:9". The port defaults to 9.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// The interface to receive on, or `None` for all of them.
+    pub interface: Option<String>,
+
+    pub addr: SocketAddr,
+}
+
+#[derive(Error, Debug)]
+pub enum EndpointError {
+    /// The part after the last colon is not a port number
+    #[error("invalid port '{0}'")]
+    InvalidPort(String),
+}
+
+impl FromStr for Endpoint {
+    type Err = EndpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(addr) = s.parse() {
+            return Ok(Endpoint {
+                interface: None,
+                addr,
+            });
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(Endpoint {
+                interface: None,
+                addr: SocketAddr::new(ip, wol::DEFAULT_PORT),
+            });
+        }
+        let (interface, port) = match s.rsplit_once(':') {
+            Some((interface, port)) => (
+                interface,
+                port.parse()
+                    .map_err(|_| EndpointError::InvalidPort(port.to_string()))?,
+            ),
+            None => (s, wol::DEFAULT_PORT),
+        };
+        Ok(Endpoint {
+            interface: (!interface.is_empty()).then(|| interface.to_string()),
+            addr: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+        })
+    }
+}
+
+/// Reads a request, returning the MAC address and password to wake.
+fn parse_request(line: &str) -> Result<(MacAddr, Option<MacAddr>), String> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("WAKE") {
+        return Err("unknown request".to_string());
+    }
+    let mac = words.next().ok_or("missing MAC address")?;
+    let password = words.next();
+    if words.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+    let mac = mac.parse::<MacAddr>().map_err(|err| err.to_string())?;
+    let password = password
+        .map(str::parse::<MacAddr>)
+        .transpose()
+        .map_err(|err| format!("invalid SecureOn password: {}", err))?;
+    Ok((mac, password))
+}
+
+fn handle_request(
+    peer: SocketAddr,
+    mut stream: impl Read + Write,
+    wake: &impl Fn(SocketAddr, MacAddr, Option<MacAddr>) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&mut stream).read_line(&mut line)?;
+    let result = parse_request(&line)
+        .and_then(|(mac, password)| wake(peer, mac, password).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => writeln!(stream, "OK")?,
+        Err(reason) => writeln!(stream, "ERR {}", reason)?,
+    }
+    stream.flush()
+}
+
+/// Answers a client over TLS, closing the connection with a close_notify once answered.
+fn handle_tls_request(
+    peer: SocketAddr,
+    stream: TcpStream,
+    config: Arc<ServerConfig>,
+    wake: &impl Fn(SocketAddr, MacAddr, Option<MacAddr>) -> io::Result<()>,
+) -> io::Result<()> {
+    let conn = ServerConnection::new(config).map_err(io::Error::other)?;
+    let mut stream = StreamOwned::new(conn, stream);
+    handle_request(peer, &mut stream, wake)?;
+    stream.conn.send_close_notify();
+    stream.flush()
+}
+
+/// Answers the requests of clients connecting to `listener`, over TLS if given its settings,
+/// calling `wake` with the client's address for each of them, until accepting fails.
+pub fn serve(
+    listener: &TcpListener,
+    tls: Option<Arc<ServerConfig>>,
+    wake: impl Fn(SocketAddr, MacAddr, Option<MacAddr>) -> io::Result<()> + Sync,
+) -> io::Result<()> {
+    thread::scope(|scope| loop {
+        let (stream, peer) = listener.accept()?;
+        let (tls, wake) = (tls.clone(), &wake);
+        // clients that break off are no concern of the others
+        scope.spawn(move || {
+            let _ = stream
+                .set_read_timeout(Some(TIMEOUT))
+                .and_then(|()| stream.set_write_timeout(Some(TIMEOUT)))
+                .and_then(|()| match tls {
+                    Some(config) => handle_tls_request(peer, stream, config, wake),
+                    None => handle_request(peer, &stream, wake),
+                });
+        });
+    })
+}
+
+#[test]
+fn test_request_wake() {
+    use std::net::TcpListener;
+
+    let mac = "aa:bb:cc:dd:ee:ff".parse().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay = listener.local_addr().unwrap().to_string();
+
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        writeln!(&stream, "OK").unwrap();
+        line
+    });
+
+    request_wake(&relay, mac, None, None).unwrap();
+    assert_eq!(server.join().unwrap(), "WAKE aa:bb:cc:dd:ee:ff\n");
+}
+
+#[test]
+fn test_request_wake_refused() {
+    use std::net::TcpListener;
+
+    let mac = "aa:bb:cc:dd:ee:ff".parse().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay = listener.local_addr().unwrap().to_string();
+
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        writeln!(&stream, "ERR not allowed").unwrap();
+    });
+
+    let err = request_wake(&relay, mac, None, None).unwrap_err();
+    assert!(err.to_string().contains("not allowed"));
+}
+
+#[test]
+fn test_serve() {
+    use std::sync::mpsc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay = listener.local_addr().unwrap().to_string();
+    let (sender, woken) = mpsc::channel();
+    let sender = std::sync::Mutex::new(sender);
+    thread::spawn(move || {
+        serve(&listener, None, |_, mac, password| {
+            let woken = (
+                mac.to_string(),
+                password.map(|password| password.to_string()),
+            );
+            sender.lock().unwrap().send(woken).unwrap();
+            Ok(())
+        })
+    });
+
+    let mac = "aa:bb:cc:dd:ee:ff".parse().unwrap();
+    request_wake(&relay, mac, Some("01:02:03:04:05:06"), None).unwrap();
+    assert_eq!(
+        woken.recv().unwrap(),
+        ("aa:bb:cc:dd:ee:ff".into(), Some("01:02:03:04:05:06".into()))
+    );
+    let stream = TcpStream::connect(&relay).unwrap();
+    writeln!(&stream, "WAKE nas").unwrap();
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).unwrap();
+    assert!(line.starts_with("ERR "));
+}
+
+#[test]
+fn test_serve_tls() {
+    let dir = std::env::temp_dir().join(format!("wol-cli-test-relay-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let signed = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+    let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+    std::fs::write(&cert, signed.cert.pem()).unwrap();
+    std::fs::write(&key, signed.key_pair.serialize_pem()).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let config = server_config(&cert, &key).unwrap();
+    thread::spawn(move || serve(&listener, Some(config), |_, _, _| Ok(())));
+
+    let relay = format!("tls://localhost:{}", port);
+    let mac = "aa:bb:cc:dd:ee:ff".parse().unwrap();
+    request_wake(&relay, mac, None, Some(&cert)).unwrap();
+    // the certificate is not one of a public certificate authority
+    assert!(request_wake(&relay, mac, None, None).is_err());
+    // nor is it of 127.0.0.1
+    let relay = format!("tls://127.0.0.1:{}", port);
+    assert!(request_wake(&relay, mac, None, Some(&cert)).is_err());
+    // and the relay does not answer in plain text
+    let relay = format!("127.0.0.1:{}", port);
+    assert!(request_wake(&relay, mac, None, None).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(host("relay.example.com:7009"), "relay.example.com");
+    assert_eq!(host("relay.example.com"), "relay.example.com");
+    assert_eq!(host("[fd00::1]:7009"), "fd00::1");
+    assert_eq!(host("fd00::1"), "fd00::1");
+}
+
+#[test]
+fn test_parse_request() {
+    assert_eq!(
+        parse_request("WAKE aa:bb:cc:dd:ee:ff\n"),
+        Ok((MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]), None))
+    );
+    assert_eq!(
+        parse_request("WAKE aa:bb:cc:dd:ee:ff 01:02:03:04:05:06\n"),
+        Ok((
+            MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]),
+            Some(MacAddr([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]))
+        ))
+    );
+    assert!(parse_request("SLEEP aa:bb:cc:dd:ee:ff\n").is_err());
+    assert!(parse_request("WAKE\n").is_err());
+    assert!(parse_request("WAKE aa:bb:cc:dd:ee:ff secret\n").is_err());
+}
+
+#[test]
+fn test_endpoint() {
+    let any = |port| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+    let endpoint: Endpoint = "wan0:7".parse().unwrap();
+    assert_eq!(endpoint.interface.as_deref(), Some("wan0"));
+    assert_eq!(endpoint.addr, any(7));
+
+    let endpoint: Endpoint = "wan0".parse().unwrap();
+    assert_eq!(endpoint.interface.as_deref(), Some("wan0"));
+    assert_eq!(endpoint.addr, any(9));
+
+    let endpoint: Endpoint = ":9".parse().unwrap();
+    assert_eq!(endpoint.interface, None);
+    assert_eq!(endpoint.addr, any(9));
+
+    let endpoint: Endpoint = "192.168.1.2:9".parse().unwrap();
+    assert_eq!(endpoint.interface, None);
+    assert_eq!(endpoint.addr, "192.168.1.2:9".parse().unwrap());
+    let endpoint: Endpoint = "::".parse().unwrap();
+    assert_eq!(endpoint.addr, "[::]:9".parse().unwrap());
+
+    assert!("wan0:http".parse::<Endpoint>().is_err());
+}
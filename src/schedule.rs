@@ -0,0 +1,324 @@
+//! Scheduled wakes: the times of day given to `--at`, and the cron-style schedules of the hosts
+//! of the registry, e.g. "45 6 * * mon-fri" for 06:45 on weekdays.
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::platform;
+
+/// A point in time broken down into its calendar fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+    pub year: i64,
+
+    /// 1 to 12.
+    pub month: u32,
+
+    /// 1 to 31.
+    pub day: u32,
+
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+
+    /// 0 for Sunday to 6 for Saturday.
+    pub weekday: u32,
+}
+
+impl Time {
+    /// The UTC time of a Unix timestamp.
+    pub fn utc(timestamp: u64) -> Time {
+        let (days, seconds) = (timestamp / 86400, timestamp % 86400);
+        // 1970-01-01 was a Thursday
+        let weekday = ((days + 4) % 7) as u32;
+        // the civil date of a day count, counting in eras of 400 years from 0000-03-01
+        let days = days as i64 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        Time {
+            year: year_of_era + era * 400 + i64::from(month <= 2),
+            month: month as u32,
+            day: day as u32,
+            hour: (seconds / 3600) as u32,
+            minute: (seconds / 60 % 60) as u32,
+            second: (seconds % 60) as u32,
+            weekday,
+        }
+    }
+
+    /// The local time of a Unix timestamp, or the UTC time if the time zone cannot be read.
+    pub fn local(timestamp: u64) -> Time {
+        let offset = platform::utc_offset(timestamp).unwrap_or(0);
+        Time::utc(timestamp.saturating_add_signed(offset.into()))
+    }
+}
+
+/// A time of day, e.g. 06:45, as given to `--at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeOfDay {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("expected a time of day, e.g. 06:45 or 18:30")]
+pub struct TimeOfDayError;
+
+impl FromStr for TimeOfDay {
+    type Err = TimeOfDayError;
+
+    fn from_str(s: &str) -> Result<TimeOfDay, TimeOfDayError> {
+        let (hour, minute) = s.split_once(':').ok_or(TimeOfDayError)?;
+        if minute.len() != 2 {
+            return Err(TimeOfDayError);
+        }
+        match (hour.parse(), minute.parse()) {
+            (Ok(hour), Ok(minute)) if hour < 24 && minute < 60 => Ok(TimeOfDay { hour, minute }),
+            _ => Err(TimeOfDayError),
+        }
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl TimeOfDay {
+    /// The next Unix timestamp after `now` at which it is this time of day locally, i.e. later
+    /// today or else tomorrow.
+    pub fn next(&self, now: u64) -> u64 {
+        let local = Time::local(now);
+        let elapsed = u64::from(local.hour * 3600 + local.minute * 60 + local.second);
+        let target = u64::from(self.hour * 3600 + self.minute * 60);
+        let wait = (target + 86400 - elapsed - 1) % 86400 + 1;
+        now + wait
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// Not the five fields of a crontab line
+    #[error("expected 5 fields (minute, hour, day of month, month, day of week), found {0}")]
+    FieldCount(usize),
+
+    /// A field with a value, range or step out of bounds, or not a number
+    #[error("invalid {field} '{value}'")]
+    Invalid { field: &'static str, value: String },
+}
+
+/// A cron-style schedule matching the minutes at which a host is woken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    /// The schedule as written, kept for writing the registry back.
+    spec: String,
+
+    /// Bit sets of the matching values of each field.
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+
+    /// Whether the day of month and the day of week were both restricted, in which case a day
+    /// matches if either does, as in cron.
+    either_day: bool,
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Parses a value of a field, a number or one of `names`, numbered from `min`.
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Option<u32> {
+    if let Some(index) = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        return Some(min + index as u32);
+    }
+    value.parse().ok()
+}
+
+/// Parses a field of a schedule, e.g. "*/15", "1-5" or "mon,wed,fri", into the bit set of the
+/// values it matches.
+fn parse_field(
+    field: &'static str,
+    value: &str,
+    (min, max): (u32, u32),
+    names: &[&str],
+) -> Result<u64, ScheduleError> {
+    let invalid = || ScheduleError::Invalid {
+        field,
+        value: value.to_string(),
+    };
+    let mut bits = 0;
+    for part in value.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|&step| step > 0)),
+            None => (part, Some(1)),
+        };
+        let step = step.ok_or_else(invalid)?;
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (
+                parse_value(start, min, names).ok_or_else(invalid)?,
+                parse_value(end, min, names).ok_or_else(invalid)?,
+            ),
+            // a single value with a step runs to the end, as in "5/15"
+            None => {
+                let start = parse_value(range, min, names).ok_or_else(invalid)?;
+                (start, if part.contains('/') { max } else { start })
+            }
+        };
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Schedule, ScheduleError> {
+        let expanded = match s.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            s => s,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(ScheduleError::FieldCount(fields.len()));
+        };
+        let mut weekdays = parse_field("day of week", weekday, (0, 7), &WEEKDAYS)?;
+        // 7 is Sunday too
+        if weekdays & 1 << 7 != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Schedule {
+            spec: s.trim().to_string(),
+            minutes: parse_field("minute", minute, (0, 59), &[])?,
+            hours: parse_field("hour", hour, (0, 23), &[])?,
+            days: parse_field("day of month", day, (1, 31), &[])?,
+            months: parse_field("month", month, (1, 12), &MONTHS)?,
+            weekdays,
+            either_day: !day.starts_with('*') && !weekday.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+impl Schedule {
+    /// Whether the schedule matches the minute of `time`.
+    pub fn matches(&self, time: &Time) -> bool {
+        let day = self.days & 1 << time.day != 0;
+        let weekday = self.weekdays & 1 << time.weekday != 0;
+        let day = if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        self.minutes & 1 << time.minute != 0
+            && self.hours & 1 << time.hour != 0
+            && self.months & 1 << time.month != 0
+            && day
+    }
+}
+
+#[test]
+fn test_time() {
+    assert_eq!(
+        Time::utc(1718000000),
+        Time {
+            year: 2024,
+            month: 6,
+            day: 10,
+            hour: 6,
+            minute: 13,
+            second: 20,
+            weekday: 1,
+        }
+    );
+    assert_eq!(Time::utc(0).weekday, 4);
+    assert_eq!(Time::utc(951782400).day, 29);
+}
+
+#[test]
+fn test_time_of_day() {
+    assert_eq!(
+        "06:45".parse(),
+        Ok(TimeOfDay {
+            hour: 6,
+            minute: 45
+        })
+    );
+    assert_eq!("6:05".parse::<TimeOfDay>().unwrap().to_string(), "06:05");
+    for invalid in ["24:00", "12:60", "12:5", "noon", "12"] {
+        assert_eq!(invalid.parse::<TimeOfDay>(), Err(TimeOfDayError));
+    }
+}
+
+#[test]
+fn test_schedule() {
+    // Monday 2024-06-10 06:45 UTC
+    let monday = Time::utc(1718001900);
+    let saturday = Time::utc(1718001900 + 5 * 86400);
+
+    let weekdays: Schedule = "45 6 * * mon-fri".parse().unwrap();
+    assert!(weekdays.matches(&monday));
+    assert!(!weekdays.matches(&saturday));
+    assert!(!weekdays.matches(&Time::utc(1718001900 + 60)));
+    assert_eq!(weekdays.to_string(), "45 6 * * mon-fri");
+
+    let every = |spec: &str, time: &Time| spec.parse::<Schedule>().unwrap().matches(time);
+    assert!(every("*/15 * * * *", &monday));
+    assert!(!every("*/20 * * * *", &monday));
+    assert!(every("5/10 6 * * *", &monday));
+    assert!(every("45 6 * jun 0,7", &Time::utc(1718001900 + 6 * 86400)));
+    // with both days restricted, either matches
+    assert!(every("45 6 1 * 1", &monday));
+    assert!(!every("45 6 1 * 2", &monday));
+    assert!(every("@hourly", &Time::utc(1718002800)));
+
+    assert_eq!(
+        "45 6 * *".parse::<Schedule>(),
+        Err(ScheduleError::FieldCount(4))
+    );
+    for invalid in [
+        "60 * * * *",
+        "* * 0 * *",
+        "* * * * 8",
+        "5-1 * * * *",
+        "*/0 * * * *",
+    ] {
+        assert!(matches!(
+            invalid.parse::<Schedule>(),
+            Err(ScheduleError::Invalid { .. })
+        ));
+    }
+}
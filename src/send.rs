@@ -0,0 +1,550 @@
+//! Sending magic packets over UDP, from std sockets or those of async runtimes, and as raw
+//! ethernet frames. Requires the `std` feature.
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::WolError;
+use crate::sys;
+#[cfg(test)]
+use crate::wol::create_magic_packet;
+use crate::wol::{MacAddr, MagicPacket, DEFAULT_PORT, ETHERTYPE_WOL};
+
+/// EtherType (TPID) marking an 802.1Q VLAN tag.
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// Options controlling how the socket used to send a packet is set up.
+#[derive(Debug, Default, Clone)]
+pub struct SendOptions {
+    /// Network interface to send the packet out of, e.g. "eth1".
+    pub interface: Option<String>,
+
+    /// Local address to send the packet from, defaults to the unspecified address of the
+    /// destination's address family.
+    pub bind: Option<IpAddr>,
+
+    /// Local UDP port to send the packet from, defaults to an ephemeral port.
+    pub source_port: Option<u16>,
+
+    /// Send a plain unicast datagram, without enabling broadcasting on the socket.
+    pub unicast: bool,
+
+    /// Time-to-live (or IPv6 hop limit) of the packet, for directed broadcasts that have to
+    /// cross routers.
+    pub ttl: Option<u32>,
+
+    /// DSCP value (0-63) to mark the packet with for QoS classification.
+    pub dscp: Option<u8>,
+}
+
+impl SendOptions {
+    /// Creates a socket set up with the options for sending to `dest`, which callers sending
+    /// on their own, e.g. with [`MagicPacket::send_with_async`], can wrap as they need.
+    pub fn socket(&self, dest: &IpAddr) -> io::Result<UdpSocket> {
+        let bind = match (self.bind, dest) {
+            (Some(bind), _) if bind.is_ipv4() != dest.is_ipv4() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot send from {} to {}", bind, dest),
+                ));
+            }
+            (Some(bind), _) => bind,
+            (None, IpAddr::V4(_)) => Ipv4Addr::UNSPECIFIED.into(),
+            (None, IpAddr::V6(_)) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+
+        let socket = UdpSocket::bind((bind, self.source_port.unwrap_or(0)))?;
+        if dest.is_ipv4() && !self.unicast {
+            socket.set_broadcast(true)?;
+        }
+        if let Some(interface) = &self.interface {
+            sys::bind_to_device(&socket, interface)?;
+        }
+        match (self.ttl, dest) {
+            (Some(ttl), IpAddr::V4(_)) => socket.set_ttl(ttl)?,
+            (Some(ttl), IpAddr::V6(_)) => sys::set_hop_limit(&socket, ttl)?,
+            (None, _) => {}
+        }
+        if let Some(dscp) = self.dscp {
+            sys::set_dscp(&socket, dest.is_ipv6(), dscp)?;
+        }
+        if tracing::enabled!(tracing::Level::TRACE) {
+            let local = match socket.local_addr() {
+                Ok(local) => local.to_string(),
+                Err(err) => format!("an unknown address ({})", err),
+            };
+            let interface = match &self.interface {
+                Some(interface) => format!("bound to {}", interface),
+                None => "on the interface routing picks".to_string(),
+            };
+            let kind = if self.unicast || dest.is_ipv6() {
+                "unicast"
+            } else {
+                "broadcast"
+            };
+            let ttl = self
+                .ttl
+                .map_or("default".to_string(), |ttl| ttl.to_string());
+            tracing::trace!(
+                "{} socket from {} {}, ttl {}, dscp {}",
+                kind,
+                local,
+                interface,
+                ttl,
+                self.dscp.unwrap_or(0)
+            );
+        }
+
+        Ok(socket)
+    }
+
+    /// Resolves the socket address to send to, attaching the interface as the scope id of
+    /// link-local and multicast IPv6 destinations.
+    pub fn socket_addr(&self, addr: IpAddr, port: u16) -> io::Result<SocketAddr> {
+        match (addr, &self.interface) {
+            (IpAddr::V6(addr), Some(interface))
+                if addr.is_multicast() || addr.is_unicast_link_local() =>
+            {
+                let scope_id = sys::interface_index(interface)?;
+                Ok(SocketAddrV6::new(addr, port, 0, scope_id).into())
+            }
+            _ => Ok(SocketAddr::new(addr, port)),
+        }
+    }
+}
+
+impl MagicPacket {
+    /// Starts building the packet waking `mac`, along with where and how it is sent. By
+    /// default, it is broadcast once to 255.255.255.255 on port 9.
+    pub fn builder(mac: MacAddr) -> MagicPacketBuilder {
+        MagicPacketBuilder {
+            mac,
+            password: None,
+            destination: Ipv4Addr::BROADCAST.into(),
+            port: DEFAULT_PORT,
+            repeat: 1,
+            interval: Duration::ZERO,
+            options: SendOptions::default(),
+        }
+    }
+
+    /// Sends the packet to the given address and UDP port. The address may be the limited
+    /// broadcast address, a directed broadcast (e.g. 192.168.1.255), a routed unicast address,
+    /// or an IPv6 unicast or multicast address such as
+    /// [`IPV6_ALL_NODES`](crate::wol::IPV6_ALL_NODES).
+    pub fn send_to(&self, addr: IpAddr, port: u16, options: &SendOptions) -> Result<(), WolError> {
+        let socket = options.socket(&addr).map_err(WolError::Bind)?;
+        let dest = options.socket_addr(addr, port).map_err(WolError::Bind)?;
+        self.send_with(&socket, dest)
+    }
+
+    /// Sends the packet from a socket of the caller's, set up as they see fit. Broadcasting
+    /// requires the socket to allow it.
+    pub fn send_with(&self, socket: &UdpSocket, dest: SocketAddr) -> Result<(), WolError> {
+        tracing::debug!("sending {} bytes to {}", self.as_bytes().len(), dest);
+        socket
+            .send_to(self.as_bytes(), dest)
+            .map_err(WolError::Send)?;
+
+        Ok(())
+    }
+
+    /// Wraps the packet in a broadcast ethernet frame with EtherType 0x0842, sent from `src` and
+    /// optionally tagged with an 802.1Q VLAN id.
+    fn ethernet_frame(&self, src: [u8; 6], vlan: Option<u16>) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(18 + self.as_bytes().len());
+        frame.extend_from_slice(&[0xFF; 6]);
+        frame.extend_from_slice(&src);
+        if let Some(vlan) = vlan {
+            // priority and drop eligibility are left at 0
+            frame.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            frame.extend_from_slice(&(vlan & 0x0FFF).to_be_bytes());
+        }
+        frame.extend_from_slice(&ETHERTYPE_WOL.to_be_bytes());
+        frame.extend_from_slice(self.as_bytes());
+
+        frame
+    }
+
+    /// Sends the packet as a raw layer-2 frame out of the given interface, for NICs that only
+    /// wake on ethernet magic frames. Passing a VLAN id inserts an 802.1Q tag, for reaching hosts
+    /// from a trunk port. Only supported on Linux, and requires root or CAP_NET_RAW.
+    pub fn send_raw(&self, interface: &str, vlan: Option<u16>) -> Result<(), WolError> {
+        let src = sys::hardware_addr(interface).map_err(WolError::Bind)?;
+        tracing::debug!(
+            "sending a {}-byte packet out of {}",
+            self.as_bytes().len(),
+            interface
+        );
+        sys::send_frame(interface, &self.ethernet_frame(src, vlan)).map_err(WolError::Send)
+    }
+}
+
+/// A UDP socket of an async runtime, which magic packets can be sent on with
+/// [`MagicPacket::send_with_async`]. It is implemented for the sockets of tokio and async-io,
+/// the latter of which async-std and smol use, with their features.
+pub trait AsyncUdpSocket {
+    /// Sends `buf` to `target`, returning how many bytes were sent.
+    fn send_to(&self, buf: &[u8], target: SocketAddr) -> impl Future<Output = io::Result<usize>>;
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncUdpSocket for tokio::net::UdpSocket {
+    fn send_to(&self, buf: &[u8], target: SocketAddr) -> impl Future<Output = io::Result<usize>> {
+        tokio::net::UdpSocket::send_to(self, buf, target)
+    }
+}
+
+#[cfg(feature = "async-io")]
+impl AsyncUdpSocket for async_io::Async<UdpSocket> {
+    fn send_to(&self, buf: &[u8], target: SocketAddr) -> impl Future<Output = io::Result<usize>> {
+        async_io::Async::<UdpSocket>::send_to(self, buf, target)
+    }
+}
+
+impl MagicPacket {
+    /// Sends the packet from a socket of any async runtime, like [`MagicPacket::send_with`].
+    pub async fn send_with_async(
+        &self,
+        socket: &impl AsyncUdpSocket,
+        dest: SocketAddr,
+    ) -> Result<(), WolError> {
+        tracing::debug!("sending {} bytes to {}", self.as_bytes().len(), dest);
+        socket
+            .send_to(self.as_bytes(), dest)
+            .await
+            .map_err(WolError::Send)?;
+
+        Ok(())
+    }
+}
+
+/// Sending on tokio sockets, for async services to wake hosts without blocking their runtime.
+/// Requires the `tokio` feature, and a running tokio runtime with I/O enabled.
+#[cfg(feature = "tokio")]
+impl MagicPacket {
+    /// Sends the packet like [`MagicPacket::send_to`], without blocking.
+    pub async fn send_to_async(
+        &self,
+        addr: IpAddr,
+        port: u16,
+        options: &SendOptions,
+    ) -> Result<(), WolError> {
+        let socket = options.socket(&addr).map_err(WolError::Bind)?;
+        socket.set_nonblocking(true).map_err(WolError::Bind)?;
+        let socket = tokio::net::UdpSocket::from_std(socket).map_err(WolError::Bind)?;
+        let dest = options.socket_addr(addr, port).map_err(WolError::Bind)?;
+        self.send_with_async(&socket, dest).await
+    }
+
+    /// Broadcasts the packet to 255.255.255.255 on `port`, without blocking.
+    pub async fn broadcast_async(&self, port: u16) -> Result<(), WolError> {
+        let options = SendOptions::default();
+        self.send_to_async(Ipv4Addr::BROADCAST.into(), port, &options)
+            .await
+    }
+}
+
+/// A magic packet together with everything about sending it, started by
+/// [`MagicPacket::builder`].
+#[derive(Debug, Clone)]
+pub struct MagicPacketBuilder {
+    mac: MacAddr,
+    password: Option<[u8; 6]>,
+    destination: IpAddr,
+    port: u16,
+    repeat: u32,
+    interval: Duration,
+    options: SendOptions,
+}
+
+impl MagicPacketBuilder {
+    /// Appends a SecureOn password to the packet.
+    pub fn password(mut self, password: [u8; 6]) -> MagicPacketBuilder {
+        self.password = Some(password);
+        self
+    }
+
+    /// The address to send the packet to, as for [`MagicPacket::send_to`].
+    pub fn destination(mut self, addr: IpAddr) -> MagicPacketBuilder {
+        self.destination = addr;
+        self
+    }
+
+    /// The UDP port to send the packet to.
+    pub fn port(mut self, port: u16) -> MagicPacketBuilder {
+        self.port = port;
+        self
+    }
+
+    /// How many times to send the packet, for networks that drop some. It is sent at least once.
+    pub fn repeat(mut self, count: u32) -> MagicPacketBuilder {
+        self.repeat = count.max(1);
+        self
+    }
+
+    /// How long to wait between repeated packets.
+    pub fn interval(mut self, interval: Duration) -> MagicPacketBuilder {
+        self.interval = interval;
+        self
+    }
+
+    /// The network interface to send the packet out of.
+    pub fn interface(mut self, interface: &str) -> MagicPacketBuilder {
+        self.options.interface = Some(interface.to_string());
+        self
+    }
+
+    /// The local address to send the packet from.
+    pub fn bind(mut self, addr: IpAddr) -> MagicPacketBuilder {
+        self.options.bind = Some(addr);
+        self
+    }
+
+    /// The rest of the options of the socket the packet is sent from, replacing any interface
+    /// or local address set before.
+    pub fn options(mut self, options: SendOptions) -> MagicPacketBuilder {
+        self.options = options;
+        self
+    }
+
+    /// The packet itself.
+    pub fn build(&self) -> MagicPacket {
+        let packet = MagicPacket::new(self.mac);
+        match self.password {
+            Some(password) => packet.with_password_bytes(password),
+            None => packet,
+        }
+    }
+
+    /// Sends the packet as many times as asked, stopping at the first failure.
+    pub fn send(&self) -> Result<(), WolError> {
+        let packet = self.build();
+        for i in 0..self.repeat {
+            if i > 0 {
+                thread::sleep(self.interval);
+            }
+            packet.send_to(self.destination, self.port, &self.options)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_send_to() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    pkt.send_to(Ipv4Addr::LOCALHOST.into(), port, &SendOptions::default())
+        .unwrap();
+
+    let mut buf = [0u8; 256];
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], pkt.as_bytes());
+}
+
+#[test]
+fn test_builder() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    let mac = "AA-aa-aa-aa-aa-aa".parse().unwrap();
+    let builder = MagicPacket::builder(mac)
+        .password([1, 2, 3, 4, 5, 6])
+        .destination(Ipv4Addr::LOCALHOST.into())
+        .port(port)
+        .repeat(3)
+        .bind(Ipv4Addr::LOCALHOST.into());
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa")
+        .unwrap()
+        .with_password("01-02-03-04-05-06")
+        .unwrap();
+    assert_eq!(builder.build(), pkt);
+    builder.send().unwrap();
+
+    let mut buf = [0u8; 256];
+    for _ in 0..3 {
+        let (len, from) = receiver.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], pkt.as_bytes());
+        assert_eq!(from.ip(), Ipv4Addr::LOCALHOST);
+    }
+}
+
+#[test]
+fn test_send_with() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    pkt.send_with(&sender, receiver.local_addr().unwrap())
+        .unwrap();
+
+    let mut buf = [0u8; 256];
+    let (len, from) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], pkt.as_bytes());
+    assert_eq!(from, sender.local_addr().unwrap());
+}
+
+#[test]
+fn test_send_with_async() {
+    use std::cell::RefCell;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// A socket that is always ready, remembering what was sent.
+    struct Sent(RefCell<Vec<(Vec<u8>, SocketAddr)>>);
+
+    impl AsyncUdpSocket for Sent {
+        fn send_to(
+            &self,
+            buf: &[u8],
+            target: SocketAddr,
+        ) -> impl Future<Output = io::Result<usize>> {
+            self.0.borrow_mut().push((buf.to_vec(), target));
+            std::future::ready(Ok(buf.len()))
+        }
+    }
+
+    let socket = Sent(RefCell::new(Vec::new()));
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let dest = SocketAddr::new(Ipv4Addr::BROADCAST.into(), DEFAULT_PORT);
+    let polled =
+        pin!(pkt.send_with_async(&socket, dest)).poll(&mut Context::from_waker(Waker::noop()));
+    assert!(matches!(polled, Poll::Ready(Ok(()))));
+    assert_eq!(socket.0.into_inner(), [(pkt.as_bytes().to_vec(), dest)]);
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn test_send_with_async_io() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let dest = receiver.local_addr().unwrap();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let socket = SendOptions::default().socket(&dest.ip()).unwrap();
+    let socket = async_io::Async::new(socket).unwrap();
+    async_io::block_on(pkt.send_with_async(&socket, dest)).unwrap();
+
+    let mut buf = [0u8; 256];
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], pkt.as_bytes());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn test_send_to_async() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let options = SendOptions::default();
+    let sent = pkt.send_to_async(Ipv4Addr::LOCALHOST.into(), port, &options);
+    runtime.block_on(sent).unwrap();
+
+    let mut buf = [0u8; 256];
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], pkt.as_bytes());
+}
+
+#[test]
+fn test_send_to_bind() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let options = SendOptions {
+        bind: Some(Ipv4Addr::LOCALHOST.into()),
+        ..Default::default()
+    };
+    pkt.send_to(Ipv4Addr::LOCALHOST.into(), port, &options)
+        .unwrap();
+
+    let mut buf = [0u8; 256];
+    let (_, from) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(from.ip(), Ipv4Addr::LOCALHOST);
+}
+
+#[test]
+fn test_send_to_source_port() {
+    let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = receiver.local_addr().unwrap().port();
+
+    // borrow a free port from the OS for the sender
+    let source_port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let options = SendOptions {
+        source_port: Some(source_port),
+        ..Default::default()
+    };
+    pkt.send_to(Ipv4Addr::LOCALHOST.into(), port, &options)
+        .unwrap();
+
+    let mut buf = [0u8; 256];
+    let (_, from) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(from.port(), source_port);
+}
+
+#[test]
+fn test_send_to_bind_mismatch() {
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let options = SendOptions {
+        bind: Some(Ipv4Addr::LOCALHOST.into()),
+        ..Default::default()
+    };
+    assert!(pkt
+        .send_to(Ipv6Addr::LOCALHOST.into(), DEFAULT_PORT, &options)
+        .is_err());
+}
+
+#[test]
+fn test_send_to_ipv6() {
+    let Ok(receiver) = UdpSocket::bind("[::1]:0") else {
+        // IPv6 is not available on this host
+        return;
+    };
+    let port = receiver.local_addr().unwrap().port();
+
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    pkt.send_to(Ipv6Addr::LOCALHOST.into(), port, &SendOptions::default())
+        .unwrap();
+
+    let mut buf = [0u8; 256];
+    let (len, _) = receiver.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..len], pkt.as_bytes());
+}
+
+#[test]
+fn test_ethernet_frame() {
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let frame = pkt.ethernet_frame([1, 2, 3, 4, 5, 6], None);
+
+    assert_eq!(frame.len(), 14 + 102);
+    assert_eq!(&frame[..6], &[0xFF; 6]);
+    assert_eq!(&frame[6..12], &[1, 2, 3, 4, 5, 6]);
+    assert_eq!(&frame[12..14], &[0x08, 0x42]);
+    assert_eq!(&frame[14..], pkt.as_bytes());
+}
+
+#[test]
+fn test_ethernet_frame_vlan() {
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    let frame = pkt.ethernet_frame([1, 2, 3, 4, 5, 6], Some(20));
+
+    assert_eq!(frame.len(), 18 + 102);
+    assert_eq!(&frame[12..14], &[0x81, 0x00]);
+    assert_eq!(&frame[14..16], &[0x00, 20]);
+    assert_eq!(&frame[16..18], &[0x08, 0x42]);
+    assert_eq!(&frame[18..], pkt.as_bytes());
+}
@@ -0,0 +1,375 @@
+//! Just enough of SNMPv2c to walk the forwarding database of a managed switch, the table of MAC
+//! addresses it has learned on each of its ports (dot1dTpFdbPort in the BRIDGE-MIB, RFC 4188).
+//!
+//! Requests and responses are BER encoded; only the handful of types used by GetNextRequest and
+//! its response are supported.
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+use wol_cli::MacAddr;
+
+/// UDP port SNMP agents listen on.
+pub const DEFAULT_PORT: u16 = 161;
+
+/// dot1dTpFdbPort, indexed by the six octets of the MAC address and holding the bridge port it
+/// was learned on.
+const DOT1D_TP_FDB_PORT: &[u32] = &[1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 2];
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+const RETRIES: usize = 3;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_GET_NEXT_REQUEST: u8 = 0xa1;
+const TAG_RESPONSE: u8 = 0xa2;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+const VERSION_2C: i64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FdbEntry {
+    pub mac: MacAddr,
+    pub port: u32,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn encode_tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut tlv = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        tlv.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&byte| byte == 0)
+            .collect();
+        tlv.push(0x80 | len_bytes.len() as u8);
+        tlv.extend(len_bytes);
+    }
+    tlv.extend_from_slice(contents);
+    tlv
+}
+
+fn encode_integer(n: i64) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    // drop leading bytes that only repeat the sign bit of the next one
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    encode_tlv(TAG_INTEGER, &bytes[start..])
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut contents = vec![(oid[0] * 40 + oid[1]) as u8];
+    for &arc in &oid[2..] {
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        contents.extend(groups.into_iter().rev());
+    }
+    encode_tlv(TAG_OID, &contents)
+}
+
+fn get_next_request(community: &str, request_id: i64, oid: &[u32]) -> Vec<u8> {
+    let varbind = encode_tlv(
+        TAG_SEQUENCE,
+        &[encode_oid(oid), encode_tlv(TAG_NULL, &[])].concat(),
+    );
+    let pdu = [
+        encode_integer(request_id),
+        encode_integer(0),
+        encode_integer(0),
+        encode_tlv(TAG_SEQUENCE, &varbind),
+    ]
+    .concat();
+    let message = [
+        encode_integer(VERSION_2C),
+        encode_tlv(TAG_OCTET_STRING, community.as_bytes()),
+        encode_tlv(TAG_GET_NEXT_REQUEST, &pdu),
+    ]
+    .concat();
+    encode_tlv(TAG_SEQUENCE, &message)
+}
+
+/// Splits the next tag, length and value off `input`.
+fn decode_tlv(input: &mut &[u8]) -> io::Result<(u8, Vec<u8>)> {
+    let [tag, first, ..] = **input else {
+        return Err(invalid("truncated message"));
+    };
+    let (len, header) = if first & 0x80 == 0 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        let bytes = input
+            .get(2..2 + count)
+            .filter(|_| count <= 4)
+            .ok_or_else(|| invalid("invalid length"))?;
+        let len = bytes
+            .iter()
+            .fold(0usize, |len, &byte| (len << 8) | byte as usize);
+        (len, 2 + count)
+    };
+    let value = input
+        .get(header..header + len)
+        .ok_or_else(|| invalid("truncated message"))?
+        .to_vec();
+    *input = &input[header + len..];
+    Ok((tag, value))
+}
+
+fn expect_tlv(input: &mut &[u8], expected: u8) -> io::Result<Vec<u8>> {
+    match decode_tlv(input)? {
+        (tag, value) if tag == expected => Ok(value),
+        (tag, _) => Err(invalid(&format!("unexpected tag 0x{:02x}", tag))),
+    }
+}
+
+fn decode_integer(value: &[u8]) -> io::Result<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return Err(invalid("invalid integer"));
+    }
+    let sign = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(value.iter().fold(sign, |n, &byte| (n << 8) | byte as i64))
+}
+
+fn decode_oid(value: &[u8]) -> io::Result<Vec<u32>> {
+    let (&first, rest) = value.split_first().ok_or_else(|| invalid("empty OID"))?;
+    let mut oid = vec![(first / 40) as u32, (first % 40) as u32];
+    let mut arc = 0u32;
+    for &byte in rest {
+        arc = (arc << 7) | (byte & 0x7f) as u32;
+        if byte & 0x80 == 0 {
+            oid.push(arc);
+            arc = 0;
+        }
+    }
+    Ok(oid)
+}
+
+/// An object returned by the agent: its OID, and the tag and contents of its value.
+type Object = (Vec<u32>, u8, Vec<u8>);
+
+/// Decodes a response into the object it holds, or `None` if it answers another request.
+fn decode_response(message: &[u8], request_id: i64) -> io::Result<Option<Object>> {
+    let mut input = message;
+    let message = expect_tlv(&mut input, TAG_SEQUENCE)?;
+    let mut input = message.as_slice();
+    expect_tlv(&mut input, TAG_INTEGER)?;
+    expect_tlv(&mut input, TAG_OCTET_STRING)?;
+    let pdu = expect_tlv(&mut input, TAG_RESPONSE)?;
+
+    let mut input = pdu.as_slice();
+    if decode_integer(&expect_tlv(&mut input, TAG_INTEGER)?)? != request_id {
+        return Ok(None);
+    }
+    match decode_integer(&expect_tlv(&mut input, TAG_INTEGER)?)? {
+        0 => {}
+        status => return Err(io::Error::other(format!("agent returned error {}", status))),
+    }
+    expect_tlv(&mut input, TAG_INTEGER)?;
+
+    let varbinds = expect_tlv(&mut input, TAG_SEQUENCE)?;
+    let mut input = varbinds.as_slice();
+    let varbind = expect_tlv(&mut input, TAG_SEQUENCE)?;
+    let mut input = varbind.as_slice();
+    let oid = decode_oid(&expect_tlv(&mut input, TAG_OID)?)?;
+    let (tag, value) = decode_tlv(&mut input)?;
+    Ok(Some((oid, tag, value)))
+}
+
+/// Sends a request, retrying on timeouts, and returns the decoded response.
+fn exchange(
+    socket: &UdpSocket,
+    community: &str,
+    request_id: i64,
+    oid: &[u32],
+) -> io::Result<Object> {
+    let request = get_next_request(community, request_id, oid);
+    let mut buf = [0u8; 1500];
+    for _ in 0..RETRIES {
+        socket.send(&request)?;
+        loop {
+            match socket.recv(&mut buf) {
+                Ok(len) => match decode_response(&buf[..len], request_id)? {
+                    Some(object) => return Ok(object),
+                    // a late answer to an earlier attempt, keep waiting for ours
+                    None => continue,
+                },
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "no response from the SNMP agent, check the address and community",
+    ))
+}
+
+/// Walks the forwarding database of the switch at `agent`, given as "host" or "host:port".
+pub fn walk_fdb(agent: &str, community: &str) -> io::Result<Vec<FdbEntry>> {
+    let addr = match agent.to_socket_addrs() {
+        Ok(mut addrs) => addrs.next(),
+        Err(_) => (agent, DEFAULT_PORT).to_socket_addrs()?.next(),
+    }
+    .ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("could not resolve '{}'", agent),
+        )
+    })?;
+
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(addr)?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut entries = Vec::new();
+    let mut oid = DOT1D_TP_FDB_PORT.to_vec();
+    for request_id in 1.. {
+        let (next, tag, value) = exchange(&socket, community, request_id, &oid)?;
+        if tag == TAG_END_OF_MIB_VIEW || !next.starts_with(DOT1D_TP_FDB_PORT) || next <= oid {
+            break;
+        }
+        let index = &next[DOT1D_TP_FDB_PORT.len()..];
+        let octets: Option<Vec<u8>> = index.iter().map(|&id| u8::try_from(id).ok()).collect();
+        if let (TAG_INTEGER, Some(Ok(mac))) = (tag, octets.map(|octets| octets[..].try_into())) {
+            entries.push(FdbEntry {
+                mac,
+                port: decode_integer(&value)?.try_into().unwrap_or_default(),
+            });
+        }
+        oid = next;
+    }
+
+    Ok(entries)
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(encode_integer(0), [0x02, 0x01, 0x00]);
+    assert_eq!(encode_integer(128), [0x02, 0x02, 0x00, 0x80]);
+    assert_eq!(encode_integer(-1), [0x02, 0x01, 0xff]);
+    assert_eq!(
+        encode_oid(&[1, 3, 6, 1, 2, 1, 17, 300]),
+        [0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x11, 0x82, 0x2c]
+    );
+    assert_eq!(
+        decode_oid(&encode_oid(DOT1D_TP_FDB_PORT)[2..]).unwrap(),
+        DOT1D_TP_FDB_PORT
+    );
+    assert_eq!(decode_integer(&encode_integer(-300)[2..]).unwrap(), -300);
+
+    let long = encode_tlv(TAG_OCTET_STRING, &[0; 200]);
+    assert_eq!(long[..3], [0x04, 0x81, 200]);
+    let mut input = long.as_slice();
+    assert_eq!(decode_tlv(&mut input).unwrap().1.len(), 200);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn test_walk_fdb() {
+    let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = agent.local_addr().unwrap().to_string();
+
+    // answers like a switch that has learned two MAC addresses
+    std::thread::spawn(move || {
+        let table: [(&[u32], u32); 2] = [
+            (&[170, 187, 204, 221, 238, 255], 3),
+            (&[0, 17, 34, 51, 68, 85], 7),
+        ];
+        let mut buf = [0u8; 1500];
+        loop {
+            let (len, peer) = agent.recv_from(&mut buf).unwrap();
+            let mut input = &buf[..len];
+            let message = expect_tlv(&mut input, TAG_SEQUENCE).unwrap();
+            let mut input = message.as_slice();
+            expect_tlv(&mut input, TAG_INTEGER).unwrap();
+            let community = expect_tlv(&mut input, TAG_OCTET_STRING).unwrap();
+            assert_eq!(community, b"public");
+            let pdu = expect_tlv(&mut input, TAG_GET_NEXT_REQUEST).unwrap();
+            let mut input = pdu.as_slice();
+            let request_id = decode_integer(&expect_tlv(&mut input, TAG_INTEGER).unwrap()).unwrap();
+            expect_tlv(&mut input, TAG_INTEGER).unwrap();
+            expect_tlv(&mut input, TAG_INTEGER).unwrap();
+            let varbinds = expect_tlv(&mut input, TAG_SEQUENCE).unwrap();
+            let mut input = varbinds.as_slice();
+            let varbind = expect_tlv(&mut input, TAG_SEQUENCE).unwrap();
+            let mut input = varbind.as_slice();
+            let oid = decode_oid(&expect_tlv(&mut input, TAG_OID).unwrap()).unwrap();
+
+            // the switch returns its table sorted by index
+            let mut rows: Vec<(Vec<u32>, u32)> = table
+                .iter()
+                .map(|(mac, port)| ([DOT1D_TP_FDB_PORT, mac].concat(), *port))
+                .collect();
+            rows.sort();
+            let varbind = match rows.into_iter().find(|(row, _)| *row > oid) {
+                Some((row, port)) => [encode_oid(&row), encode_integer(port.into())].concat(),
+                None => [
+                    encode_oid(&[1, 3, 6, 1, 2, 1, 17, 5]),
+                    encode_tlv(TAG_END_OF_MIB_VIEW, &[]),
+                ]
+                .concat(),
+            };
+            let pdu = [
+                encode_integer(request_id),
+                encode_integer(0),
+                encode_integer(0),
+                encode_tlv(TAG_SEQUENCE, &encode_tlv(TAG_SEQUENCE, &varbind)),
+            ]
+            .concat();
+            let response = encode_tlv(
+                TAG_SEQUENCE,
+                &[
+                    encode_integer(VERSION_2C),
+                    encode_tlv(TAG_OCTET_STRING, b"public"),
+                    encode_tlv(TAG_RESPONSE, &pdu),
+                ]
+                .concat(),
+            );
+            agent.send_to(&response, peer).unwrap();
+        }
+    });
+
+    assert_eq!(
+        walk_fdb(&addr, "public").unwrap(),
+        [
+            FdbEntry {
+                mac: "00:11:22:33:44:55".parse().unwrap(),
+                port: 7,
+            },
+            FdbEntry {
+                mac: "aa:bb:cc:dd:ee:ff".parse().unwrap(),
+                port: 3,
+            },
+        ]
+    );
+}
@@ -0,0 +1,140 @@
+//! IPv4 subnets written in CIDR notation, e.g. 192.168.10.0/24.
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subnet {
+    addr: Ipv4Addr,
+    prefix: u8,
+}
+
+#[derive(Error, Debug)]
+pub enum SubnetError {
+    /// Missing the "/prefix" part
+    #[error("expected CIDR notation, e.g. 192.168.1.0/24")]
+    MissingPrefix,
+
+    /// The address part is not an IPv4 address
+    #[error("invalid address '{0}'")]
+    InvalidAddress(String),
+
+    /// The prefix length is not a number between 0 and 32
+    #[error("invalid prefix length '{0}'")]
+    InvalidPrefix(String),
+}
+
+impl Subnet {
+    /// The subnet of an address with the given netmask, e.g. one of a local interface.
+    pub fn with_netmask(addr: Ipv4Addr, netmask: Ipv4Addr) -> Subnet {
+        let prefix = u32::from(netmask).leading_ones() as u8;
+        Subnet { addr, prefix }
+    }
+
+    fn netmask(&self) -> u32 {
+        u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0)
+    }
+
+    /// The directed broadcast address of the subnet, i.e. with all host bits set.
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) | !self.netmask())
+    }
+
+    /// The address of the subnet itself, i.e. with all host bits cleared.
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.addr) & self.netmask())
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & self.netmask() == u32::from(self.network())
+    }
+
+    /// The number of addresses in the subnet.
+    pub fn size(&self) -> u64 {
+        1 << (32 - self.prefix as u32)
+    }
+
+    /// The addresses of hosts on the subnet, leaving out the network and broadcast addresses
+    /// unless the subnet is too small to have any others.
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let (first, last) = (u32::from(self.network()), u32::from(self.broadcast()));
+        let (first, last) = if self.prefix >= 31 {
+            (first, last)
+        } else {
+            (first + 1, last - 1)
+        };
+        (first..=last).map(Ipv4Addr::from)
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network(), self.prefix)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = SubnetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').ok_or(SubnetError::MissingPrefix)?;
+        let addr = addr
+            .parse()
+            .map_err(|_| SubnetError::InvalidAddress(addr.to_string()))?;
+        let prefix = prefix
+            .parse()
+            .ok()
+            .filter(|&prefix| prefix <= 32)
+            .ok_or_else(|| SubnetError::InvalidPrefix(prefix.to_string()))?;
+
+        Ok(Subnet { addr, prefix })
+    }
+}
+
+#[test]
+fn test_subnet_broadcast() {
+    let subnet: Subnet = "192.168.10.0/24".parse().unwrap();
+    assert_eq!(subnet.broadcast(), Ipv4Addr::new(192, 168, 10, 255));
+
+    // host bits in the address are ignored
+    let subnet: Subnet = "10.1.2.3/16".parse().unwrap();
+    assert_eq!(subnet.broadcast(), Ipv4Addr::new(10, 1, 255, 255));
+
+    let subnet: Subnet = "10.1.2.3/32".parse().unwrap();
+    assert_eq!(subnet.broadcast(), Ipv4Addr::new(10, 1, 2, 3));
+
+    let subnet: Subnet = "10.1.2.3/0".parse().unwrap();
+    assert_eq!(subnet.broadcast(), Ipv4Addr::BROADCAST);
+}
+
+#[test]
+fn test_subnet_invalid() {
+    assert!("192.168.10.0".parse::<Subnet>().is_err());
+    assert!("192.168.10/24".parse::<Subnet>().is_err());
+    assert!("192.168.10.0/33".parse::<Subnet>().is_err());
+    assert!("192.168.10.0/abc".parse::<Subnet>().is_err());
+}
+
+#[test]
+fn test_subnet_hosts() {
+    let subnet: Subnet = "192.168.10.7/29".parse().unwrap();
+    assert_eq!(subnet.to_string(), "192.168.10.0/29");
+    assert_eq!(subnet.size(), 8);
+    let hosts: Vec<Ipv4Addr> = subnet.hosts().collect();
+    assert_eq!(hosts.len(), 6);
+    assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 10, 1));
+    assert_eq!(hosts[5], Ipv4Addr::new(192, 168, 10, 6));
+    assert!(subnet.contains(Ipv4Addr::new(192, 168, 10, 7)));
+    assert!(!subnet.contains(Ipv4Addr::new(192, 168, 10, 8)));
+
+    let subnet: Subnet = "10.0.0.1/32".parse().unwrap();
+    assert_eq!(
+        subnet.hosts().collect::<Vec<_>>(),
+        [Ipv4Addr::new(10, 0, 0, 1)]
+    );
+
+    let subnet = Subnet::with_netmask(Ipv4Addr::new(10, 1, 2, 3), Ipv4Addr::new(255, 255, 0, 0));
+    assert_eq!(subnet.to_string(), "10.1.0.0/16");
+}
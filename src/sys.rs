@@ -0,0 +1,326 @@
+//! Thin platform bindings for the socket options and raw frames of sending packets, which the
+//! standard library does not expose.
+use std::io;
+use std::net::UdpSocket;
+
+#[cfg(unix)]
+mod ffi {
+    use std::os::raw::{c_char, c_uint};
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd"
+    ))]
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd"
+        ))]
+        pub fn setsockopt(
+            socket: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: c_uint,
+        ) -> c_int;
+        pub fn if_nametoindex(name: *const c_char) -> c_uint;
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+fn setsockopt(socket: &UdpSocket, level: i32, name: i32, value: &[u8]) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let ret = unsafe {
+        ffi::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            value.as_ptr() as *const _,
+            value.len() as u32,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the named network interface.
+#[cfg(unix)]
+pub fn interface_index(interface: &str) -> io::Result<u32> {
+    let name = std::ffi::CString::new(interface)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+    match unsafe { ffi::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no such interface '{}'", interface),
+        )),
+        index => Ok(index),
+    }
+}
+
+/// Returns the index of the named network interface.
+#[cfg(not(unix))]
+pub fn interface_index(_interface: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "looking up interfaces is not supported on this platform",
+    ))
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(target_os = "linux")]
+pub fn bind_to_device(socket: &UdpSocket, interface: &str) -> io::Result<()> {
+    const SOL_SOCKET: i32 = 1;
+    const SO_BINDTODEVICE: i32 = 25;
+
+    // validates the name and gives a friendlier error than ENODEV
+    interface_index(interface)?;
+    setsockopt(socket, SOL_SOCKET, SO_BINDTODEVICE, interface.as_bytes())
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn bind_to_device(socket: &UdpSocket, interface: &str) -> io::Result<()> {
+    const IPPROTO_IP: i32 = 0;
+    const IP_BOUND_IF: i32 = 25;
+
+    let index = interface_index(interface)?;
+    setsockopt(socket, IPPROTO_IP, IP_BOUND_IF, &index.to_ne_bytes())
+}
+
+/// Restricts the socket to send and receive through the named network interface only.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+pub fn bind_to_device(_socket: &UdpSocket, _interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is not supported on this platform",
+    ))
+}
+
+/// Sets the hop limit of unicast and multicast packets sent from an IPv6 socket, the IPv6
+/// counterpart of `UdpSocket::set_ttl`.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+pub fn set_hop_limit(socket: &UdpSocket, hops: u32) -> io::Result<()> {
+    const IPPROTO_IPV6: i32 = 41;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const IPV6_UNICAST_HOPS: i32 = 4;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const IPV6_MULTICAST_HOPS: i32 = 10;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const IPV6_UNICAST_HOPS: i32 = 16;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const IPV6_MULTICAST_HOPS: i32 = 18;
+
+    let hops = (hops as i32).to_ne_bytes();
+    setsockopt(socket, IPPROTO_IPV6, IPV6_UNICAST_HOPS, &hops)?;
+    setsockopt(socket, IPPROTO_IPV6, IPV6_MULTICAST_HOPS, &hops)
+}
+
+/// Sets the hop limit of unicast and multicast packets sent from an IPv6 socket.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+)))]
+pub fn set_hop_limit(_socket: &UdpSocket, _hops: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "setting the IPv6 hop limit is not supported on this platform",
+    ))
+}
+
+/// Marks packets sent from the socket with the given DSCP value (0-63) by setting the upper six
+/// bits of IP_TOS, or of IPV6_TCLASS for IPv6 sockets.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+pub fn set_dscp(socket: &UdpSocket, ipv6: bool, dscp: u8) -> io::Result<()> {
+    const IPPROTO_IP: i32 = 0;
+    const IPPROTO_IPV6: i32 = 41;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    const IPV6_TCLASS: i32 = 36;
+    #[cfg(target_os = "freebsd")]
+    const IPV6_TCLASS: i32 = 61;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const IPV6_TCLASS: i32 = 67;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    const IP_TOS: i32 = 3;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const IP_TOS: i32 = 1;
+
+    let class = ((dscp as i32) << 2).to_ne_bytes();
+    if ipv6 {
+        setsockopt(socket, IPPROTO_IPV6, IPV6_TCLASS, &class)
+    } else {
+        setsockopt(socket, IPPROTO_IP, IP_TOS, &class)
+    }
+}
+
+/// Marks packets sent from the socket with the given DSCP value.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+)))]
+pub fn set_dscp(_socket: &UdpSocket, _ipv6: bool, _dscp: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "DSCP marking is not supported on this platform",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod packet {
+    use std::os::raw::{c_int, c_void};
+
+    pub const AF_PACKET: c_int = 17;
+    pub const SOCK_RAW: c_int = 3;
+
+    #[repr(C)]
+    pub struct sockaddr_ll {
+        pub sll_family: u16,
+        pub sll_protocol: u16,
+        pub sll_ifindex: c_int,
+        pub sll_hatype: u16,
+        pub sll_pkttype: u8,
+        pub sll_halen: u8,
+        pub sll_addr: [u8; 8],
+    }
+
+    extern "C" {
+        pub fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+        pub fn sendto(
+            socket: c_int,
+            buf: *const c_void,
+            len: usize,
+            flags: c_int,
+            addr: *const sockaddr_ll,
+            addrlen: u32,
+        ) -> isize;
+    }
+}
+
+/// Returns the hardware (MAC) address of the named network interface.
+#[cfg(target_os = "linux")]
+pub fn hardware_addr(interface: &str) -> io::Result<[u8; 6]> {
+    let path = format!("/sys/class/net/{}/address", interface);
+    let contents = std::fs::read_to_string(path).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no such interface '{}'", interface),
+        )
+    })?;
+
+    let mut addr = [0u8; 6];
+    let mut octets = contents.trim().split(':');
+    for byte in addr.iter_mut() {
+        *byte = octets
+            .next()
+            .and_then(|octet| u8::from_str_radix(octet, 16).ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("interface '{}' has no ethernet address", interface),
+                )
+            })?;
+    }
+
+    Ok(addr)
+}
+
+/// Transmits a complete ethernet frame (including its header) out of the named interface.
+/// Requires root or CAP_NET_RAW.
+#[cfg(target_os = "linux")]
+pub fn send_frame(interface: &str, frame: &[u8]) -> io::Result<()> {
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    let index = interface_index(interface)?;
+
+    let fd = unsafe { packet::socket(packet::AF_PACKET, packet::SOCK_RAW, 0) };
+    if fd < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "raw sockets require root or CAP_NET_RAW",
+            ));
+        }
+        return Err(err);
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr = packet::sockaddr_ll {
+        sll_family: packet::AF_PACKET as u16,
+        sll_protocol: 0,
+        sll_ifindex: index as i32,
+        sll_hatype: 0,
+        sll_pkttype: 0,
+        sll_halen: 6,
+        sll_addr: [0; 8],
+    };
+    addr.sll_addr[..6].copy_from_slice(&frame[..6]);
+
+    let ret = unsafe {
+        packet::sendto(
+            socket.as_raw_fd(),
+            frame.as_ptr() as *const _,
+            frame.len(),
+            0,
+            &addr,
+            std::mem::size_of::<packet::sockaddr_ll>() as u32,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Returns the hardware (MAC) address of the named network interface.
+#[cfg(not(target_os = "linux"))]
+pub fn hardware_addr(_interface: &str) -> io::Result<[u8; 6]> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw ethernet is only supported on Linux",
+    ))
+}
+
+/// Transmits a complete ethernet frame (including its header) out of the named interface.
+#[cfg(not(target_os = "linux"))]
+pub fn send_frame(_interface: &str, _frame: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "raw ethernet is only supported on Linux",
+    ))
+}
@@ -0,0 +1,51 @@
+//! Plain text tables with aligned columns, whose headers are bold when colors are on.
+use crate::color;
+
+/// Formats rows under a header, padding every column to its widest cell. Cells may be colored.
+pub fn format(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(color::width(cell));
+        }
+    }
+
+    let mut output = String::new();
+    let headers: Vec<String> = headers.iter().map(|header| color::bold(header)).collect();
+    for row in std::iter::once(&headers).chain(rows) {
+        let mut line = String::new();
+        for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if i > 0 {
+                line.push_str("  ");
+            }
+            line.push_str(cell);
+            line.extend(std::iter::repeat_n(' ', width - color::width(cell)));
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+
+    output
+}
+
+#[test]
+fn test_format() {
+    let rows = vec![
+        vec![
+            "nas".to_string(),
+            "aa:bb:cc:dd:ee:ff".to_string(),
+            "ok".to_string(),
+        ],
+        vec![
+            "desktop".to_string(),
+            "11:22:33:44:55:66".to_string(),
+            "failed".to_string(),
+        ],
+    ];
+    assert_eq!(
+        format(&["HOST", "MAC", "RESULT"], &rows),
+        "HOST     MAC                RESULT\n\
+         nas      aa:bb:cc:dd:ee:ff  ok\n\
+         desktop  11:22:33:44:55:66  failed\n"
+    );
+}
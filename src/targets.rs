@@ -0,0 +1,282 @@
+//! Collects the MAC addresses to wake from the command line, input streams and files.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::Path;
+
+use wol_cli::MacAddr;
+
+/// A MAC address to wake, along with where it was read from and any options that override the
+/// command line for this target only.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Target {
+    /// The MAC address, unless it is yet to be resolved from the alias.
+    pub mac: Option<MacAddr>,
+
+    /// The name the target was given by instead of its MAC address: a registry alias, group or
+    /// pattern, an IP address or a hostname of /etc/ethers.
+    pub alias: Option<String>,
+
+    /// The file and line the target was read from, if it was not given on the command line.
+    pub origin: Option<String>,
+
+    /// Address to send this target's packet to.
+    pub addr: Option<IpAddr>,
+
+    /// UDP port to send this target's packet to.
+    pub port: Option<u16>,
+
+    /// SecureOn password for this target.
+    pub password: Option<String>,
+
+    /// Network interface to send this target's packet out of.
+    pub interface: Option<String>,
+
+    /// Number of packets to send to this target.
+    pub count: Option<u32>,
+
+    /// Address the target answers on once it is awake, probed by `--wait`.
+    pub ip: Option<IpAddr>,
+}
+
+impl Target {
+    /// The target given by `name`, a MAC address or a name to resolve one from.
+    pub fn new(name: &str) -> Target {
+        match name.parse() {
+            Ok(mac) => Target {
+                mac: Some(mac),
+                ..Default::default()
+            },
+            Err(_) => Target {
+                alias: Some(name.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// The alias of the target, or else its MAC address.
+    pub fn name(&self) -> String {
+        match (&self.alias, self.mac) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(mac)) => mac.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name();
+        match &self.origin {
+            Some(origin) => write!(f, "'{}' ({})", name, origin),
+            None => write!(f, "'{}'", name),
+        }
+    }
+}
+
+/// Reads one target per line, ignoring blank lines and anything following a '#'. `name` is
+/// used to record the origin of each target.
+pub fn read_lines(reader: impl BufRead, name: &str) -> io::Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = match line.split_once('#') {
+            Some((line, _comment)) => line,
+            None => &line,
+        };
+        let line = line.trim();
+        if !line.is_empty() {
+            targets.push(Target {
+                origin: Some(format!("{}:{}", name, index + 1)),
+                ..Target::new(line)
+            });
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Reads the targets listed in a file, see [`read_lines`].
+pub fn read_file(path: &Path) -> io::Result<Vec<Target>> {
+    let file = File::open(path)?;
+    read_lines(BufReader::new(file), &path.display().to_string())
+}
+
+/// Splits a CSV record into its fields, honoring double quoted fields with "" escapes.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => field.push(c),
+        }
+    }
+
+    fields
+        .iter()
+        .map(|field| field.trim().to_string())
+        .collect()
+}
+
+fn invalid_data(origin: &str, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", origin, message),
+    )
+}
+
+/// Reads targets from a CSV file whose first record is a header naming the columns. The `mac`
+/// column is required, while `broadcast` (or `addr`), `port` and `password` are optional and
+/// empty cells fall back to the command line options. Blank lines and lines starting with '#'
+/// are ignored.
+pub fn read_csv(reader: impl BufRead, name: &str) -> io::Result<Vec<Target>> {
+    let mut header: Option<Vec<String>> = None;
+    let mut targets = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let origin = format!("{}:{}", name, index + 1);
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let fields = split_csv(&line);
+        let Some(columns) = &header else {
+            let columns: Vec<String> = fields.iter().map(|f| f.to_lowercase()).collect();
+            if let Some(column) = columns.iter().find(|column| {
+                !matches!(
+                    column.as_str(),
+                    "mac" | "broadcast" | "addr" | "port" | "password"
+                )
+            }) {
+                return Err(invalid_data(
+                    &origin,
+                    format!("unknown column '{}'", column),
+                ));
+            }
+            if !columns.iter().any(|column| column == "mac") {
+                return Err(invalid_data(&origin, "missing a 'mac' column".into()));
+            }
+            header = Some(columns);
+            continue;
+        };
+
+        let mut target = Target {
+            origin: Some(origin.clone()),
+            ..Default::default()
+        };
+        for (column, value) in columns.iter().zip(fields) {
+            if value.is_empty() {
+                continue;
+            }
+            match column.as_str() {
+                "mac" => {
+                    let named = Target::new(&value);
+                    target.mac = named.mac;
+                    target.alias = named.alias;
+                }
+                "broadcast" | "addr" => {
+                    let addr = value.parse().map_err(|_| {
+                        invalid_data(&origin, format!("invalid address '{}'", value))
+                    })?;
+                    target.addr = Some(addr);
+                }
+                "port" => {
+                    let port = value
+                        .parse()
+                        .map_err(|_| invalid_data(&origin, format!("invalid port '{}'", value)))?;
+                    target.port = Some(port);
+                }
+                _ => target.password = Some(value),
+            }
+        }
+        targets.push(target);
+    }
+
+    Ok(targets)
+}
+
+/// Reads the targets listed in a CSV file, see [`read_csv`].
+pub fn read_csv_file(path: &Path) -> io::Result<Vec<Target>> {
+    let file = File::open(path)?;
+    read_csv(BufReader::new(file), &path.display().to_string())
+}
+
+#[test]
+fn test_read_lines() {
+    let input = "aa:bb:cc:dd:ee:ff\n\n  11-22-33-44-55-66  \n";
+    let targets = read_lines(input.as_bytes(), "<stdin>").unwrap();
+    let macs: Vec<_> = targets.iter().map(|target| target.name()).collect();
+    assert_eq!(macs, ["aa:bb:cc:dd:ee:ff", "11:22:33:44:55:66"]);
+}
+
+#[test]
+fn test_read_lines_comments() {
+    let input = "# lab machines\naa:bb:cc:dd:ee:ff # desktop\n\n#11-22-33-44-55-66\nnope\n";
+    let targets = read_lines(input.as_bytes(), "hosts.txt").unwrap();
+    assert_eq!(
+        targets,
+        [
+            Target {
+                mac: "aa:bb:cc:dd:ee:ff".parse().ok(),
+                origin: Some("hosts.txt:2".into()),
+                ..Default::default()
+            },
+            Target {
+                alias: Some("nope".into()),
+                origin: Some("hosts.txt:5".into()),
+                ..Default::default()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_read_csv() {
+    let input = "mac,broadcast,port,password\n\
+                 aa:bb:cc:dd:ee:ff,192.168.1.255,7,01-02-03-04-05-06\n\
+                 \n\
+                 # the rest use the defaults\n\
+                 \"11-22-33-44-55-66\",,,\n";
+    let targets = read_csv(input.as_bytes(), "hosts.csv").unwrap();
+    assert_eq!(
+        targets,
+        [
+            Target {
+                mac: "aa:bb:cc:dd:ee:ff".parse().ok(),
+                alias: None,
+                origin: Some("hosts.csv:2".into()),
+                addr: Some("192.168.1.255".parse().unwrap()),
+                port: Some(7),
+                password: Some("01-02-03-04-05-06".into()),
+                ..Default::default()
+            },
+            Target {
+                mac: "11-22-33-44-55-66".parse().ok(),
+                origin: Some("hosts.csv:5".into()),
+                ..Default::default()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_read_csv_invalid() {
+    let input = "mac,port\naa:bb:cc:dd:ee:ff,seven\n";
+    let err = read_csv(input.as_bytes(), "hosts.csv").unwrap_err();
+    assert_eq!(err.to_string(), "hosts.csv:2: invalid port 'seven'");
+
+    let input = "mac,vlan\naa:bb:cc:dd:ee:ff,20\n";
+    assert!(read_csv(input.as_bytes(), "hosts.csv").is_err());
+
+    let input = "port\n9\n";
+    assert!(read_csv(input.as_bytes(), "hosts.csv").is_err());
+}
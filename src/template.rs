@@ -0,0 +1,144 @@
+//! Templates of --format, shaping the line printed for each destination, e.g.
+//! `{alias}\t{mac}\t{status}`. Placeholders are names in braces, `{{` and `}}` are literal
+//! braces, and `\t`, `\n` and `\\` are a tab, a newline and a backslash.
+use std::str::FromStr;
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A placeholder that is not one of the fields
+    #[error("unknown placeholder '{{{0}}}', expected one of {1}")]
+    Unknown(String, &'static str),
+
+    /// A brace that is not closed, or closed without being opened
+    #[error("unmatched brace, write {{{{ or }}}} for a literal one")]
+    Unmatched,
+}
+
+/// What a placeholder stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The name of the host, or nothing if it was given by MAC address.
+    Alias,
+    Mac,
+    /// The address, relay or interface the packets were sent to.
+    Destination,
+    /// The UDP port, or nothing.
+    Port,
+    /// ok, failed or invalid, or, when waiting, up, timed-out or failed.
+    Status,
+    /// Why sending failed, or nothing.
+    Error,
+    /// How long sending took, in milliseconds.
+    Duration,
+}
+
+const FIELDS: [(&str, Field); 7] = [
+    ("alias", Field::Alias),
+    ("mac", Field::Mac),
+    ("destination", Field::Destination),
+    ("port", Field::Port),
+    ("status", Field::Status),
+    ("error", Field::Error),
+    ("duration", Field::Duration),
+];
+
+const NAMES: &str = "alias, mac, destination, port, status, error and duration";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(Vec<Piece>);
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Template, TemplateError> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(TemplateError::Unmatched),
+                        }
+                    }
+                    let Some(&(_, field)) = FIELDS.iter().find(|(known, _)| *known == name) else {
+                        return Err(TemplateError::Unknown(name, NAMES));
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err(TemplateError::Unmatched),
+                '\\' => match chars.peek() {
+                    Some('t') => {
+                        chars.next();
+                        text.push('\t');
+                    }
+                    Some('n') => {
+                        chars.next();
+                        text.push('\n');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        text.push('\\');
+                    }
+                    _ => text.push('\\'),
+                },
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Template(pieces))
+    }
+}
+
+impl Template {
+    /// Fills in the placeholders with the values `value` gives for their fields.
+    pub fn render(&self, value: impl Fn(Field) -> String) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text.clone(),
+                Piece::Field(field) => value(*field),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_template() {
+    let template: Template = r"{alias}\t{mac}: {status} {{{port}}}\\".parse().unwrap();
+    let line = template.render(|field| match field {
+        Field::Alias => "nas".into(),
+        Field::Mac => "aa:bb:cc:dd:ee:ff".into(),
+        Field::Status => "ok".into(),
+        Field::Port => "9".into(),
+        _ => unreachable!(),
+    });
+    assert_eq!(line, "nas\taa:bb:cc:dd:ee:ff: ok {9}\\");
+
+    assert_eq!("".parse(), Ok(Template(vec![])));
+    assert_eq!(
+        "{host}".parse::<Template>(),
+        Err(TemplateError::Unknown("host".into(), NAMES))
+    );
+    assert_eq!("{mac".parse::<Template>(), Err(TemplateError::Unmatched));
+    assert_eq!("mac}".parse::<Template>(), Err(TemplateError::Unmatched));
+}
@@ -0,0 +1,474 @@
+//! A small reader and writer for the subset of TOML used by the configuration file: tables,
+//! bare or quoted keys, strings, integers, booleans and (possibly multi-line) arrays.
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+/// A `[table]` and its key/value pairs, in the order they appear in the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    /// The dotted path of the table header, empty for the keys preceding the first header.
+    pub path: Vec<String>,
+    pub entries: Vec<(String, Value)>,
+
+    /// The line the table header appears on.
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub tables: Vec<Table>,
+}
+
+#[derive(Error, Debug)]
+#[error("line {line}: {message}")]
+pub struct SyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Table {
+    pub fn new(path: &[&str]) -> Table {
+        Table {
+            path: path.iter().map(|key| key.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Appends `key = value` to the table.
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.entries.push((key.to_string(), value));
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SyntaxError> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.error(format!("expected '{}', found '{}'", expected, c)),
+            None => self.error(format!("expected '{}'", expected)),
+        }
+    }
+
+    /// Skips spaces and tabs, staying on the current line.
+    fn skip_blank(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.next();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), Some('\n') | None) {
+            self.next();
+        }
+    }
+
+    /// Expects nothing but whitespace or a comment until the end of the line.
+    fn end_of_line(&mut self) -> Result<(), SyntaxError> {
+        self.skip_blank();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.next();
+                Ok(())
+            }
+            Some('\r') => {
+                self.next();
+                self.expect('\n')
+            }
+            Some(c) => self.error(format!("unexpected '{}'", c)),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let mut key = String::new();
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        break;
+                    }
+                    key.push(c);
+                    self.next();
+                }
+                if key.is_empty() {
+                    return self.error("expected a key");
+                }
+                Ok(key)
+            }
+        }
+    }
+
+    fn path(&mut self) -> Result<Vec<String>, SyntaxError> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_blank();
+            path.push(self.key()?);
+            self.skip_blank();
+            if self.peek() != Some('.') {
+                return Ok(path);
+            }
+            self.next();
+        }
+    }
+
+    /// Consumes the next character of a string, which must end on the same line.
+    fn string_char(&mut self) -> Result<char, SyntaxError> {
+        match self.peek() {
+            None | Some('\n') => self.error("unterminated string"),
+            Some(c) => {
+                self.next();
+                Ok(c)
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, SyntaxError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.string_char()? {
+                '"' => return Ok(s),
+                '\\' => match self.string_char()? {
+                    '"' => s.push('"'),
+                    '\\' => s.push('\\'),
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.string_char().ok()).collect();
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => s.push(c),
+                            None => return self.error(format!("invalid escape '\\u{}'", hex)),
+                        }
+                    }
+                    c => return self.error(format!("invalid escape '\\{}'", c)),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, SyntaxError> {
+        self.expect('\'')?;
+        let mut s = String::new();
+        loop {
+            match self.string_char()? {
+                '\'' => return Ok(s),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SyntaxError> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.basic_string()?)),
+            Some('\'') => Ok(Value::String(self.literal_string()?)),
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return self.error("expected ',' or ']' in array"),
+                    }
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = self.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+') {
+                        break;
+                    }
+                    word.push(c);
+                    self.next();
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    "" => self.error("expected a value"),
+                    _ => match word.replace('_', "").parse() {
+                        Ok(n) => Ok(Value::Integer(n)),
+                        Err(_) => self.error(format!("invalid value '{}'", word)),
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str) -> fmt::Result {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        write!(f, "{}", key)
+    } else {
+        write_string(f, key)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write_string(f, s),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for table in &self.tables {
+            if table.path.is_empty() && table.entries.is_empty() {
+                continue;
+            }
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+
+            if !table.path.is_empty() {
+                write!(f, "[")?;
+                for (i, key) in table.path.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write_key(f, key)?;
+                }
+                writeln!(f, "]")?;
+            }
+            for (key, value) in &table.entries {
+                write_key(f, key)?;
+                writeln!(f, " = {}", value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a TOML document. Tables may not be defined twice and keys may not repeat within a
+/// table.
+pub fn parse(input: &str) -> Result<Document, SyntaxError> {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+        line: 1,
+    };
+    let mut document = Document {
+        tables: vec![Table {
+            line: 1,
+            ..Default::default()
+        }],
+    };
+
+    loop {
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => return Ok(document),
+            Some('[') => {
+                parser.next();
+                if parser.peek() == Some('[') {
+                    return parser.error("arrays of tables are not supported");
+                }
+                let line = parser.line;
+                let path = parser.path()?;
+                parser.expect(']')?;
+                parser.end_of_line()?;
+                if document.tables.iter().any(|table| table.path == path) {
+                    return parser.error(format!("table '{}' is defined twice", path.join(".")));
+                }
+                document.tables.push(Table {
+                    path,
+                    entries: Vec::new(),
+                    line,
+                });
+            }
+            Some(_) => {
+                let line = parser.line;
+                let key = parser.key()?;
+                parser.skip_blank();
+                parser.expect('=')?;
+                parser.skip_blank();
+                let value = parser.value()?;
+                parser.end_of_line()?;
+
+                let table = document.tables.last_mut().unwrap();
+                if table.get(&key).is_some() {
+                    return Err(SyntaxError {
+                        line,
+                        message: format!("key '{}' is defined twice", key),
+                    });
+                }
+                table.entries.push((key, value));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse() {
+    let input = r#"
+# hosts I wake regularly
+[hosts.nas]
+mac = "aa:bb:cc:dd:ee:ff" # the big one
+port = 7
+
+[hosts."living room"]
+mac = 'aa-bb-cc-dd-ee-00'
+enabled = true
+groups = [
+    "media",
+    "lab",
+]
+"#;
+    let document = parse(input).unwrap();
+    assert_eq!(document.tables.len(), 3);
+
+    let nas = &document.tables[1];
+    assert_eq!(nas.path, ["hosts", "nas"]);
+    assert_eq!(nas.line, 3);
+    assert_eq!(
+        nas.get("mac"),
+        Some(&Value::String("aa:bb:cc:dd:ee:ff".into()))
+    );
+    assert_eq!(nas.get("port"), Some(&Value::Integer(7)));
+
+    let living_room = &document.tables[2];
+    assert_eq!(living_room.path, ["hosts", "living room"]);
+    assert_eq!(living_room.get("enabled"), Some(&Value::Boolean(true)));
+    assert_eq!(
+        living_room.get("groups"),
+        Some(&Value::Array(vec![
+            Value::String("media".into()),
+            Value::String("lab".into()),
+        ]))
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    let err = parse("[hosts.nas]\nmac = \"aa:bb\n").unwrap_err();
+    assert_eq!(err.line, 2);
+
+    assert!(parse("[hosts.nas]\n[hosts.nas]\n").is_err());
+    assert!(parse("mac = 1\nmac = 2\n").is_err());
+    assert!(parse("mac = \"a\" port = 9\n").is_err());
+    assert!(parse("port = nine\n").is_err());
+    assert!(parse("[[hosts]]\n").is_err());
+}
+
+#[test]
+fn test_roundtrip() {
+    let mut document = Document::default();
+    let mut table = Table::new(&["hosts", "living room"]);
+    table.set("mac", Value::String("aa:bb:cc:dd:ee:ff".into()));
+    table.set("note", Value::String("says \"hi\"\n".into()));
+    table.set("port", Value::Integer(9));
+    table.set("groups", Value::Array(vec![Value::String("lab".into())]));
+    document.tables.push(table);
+
+    let output = document.to_string();
+    assert_eq!(
+        output,
+        "[hosts.\"living room\"]\n\
+         mac = \"aa:bb:cc:dd:ee:ff\"\n\
+         note = \"says \\\"hi\\\"\\n\"\n\
+         port = 9\n\
+         groups = [\"lab\"]\n"
+    );
+
+    let parsed = parse(&output).unwrap();
+    assert_eq!(parsed.tables[1].entries, document.tables[0].entries);
+}
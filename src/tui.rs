@@ -0,0 +1,234 @@
+//! The terminal of `tui`, drawn with ratatui on crossterm: raw mode on the alternate screen, the
+//! keys pressed and the table of hosts drawn anew for every change or resize. The terminal is
+//! restored when the picker is left, and by the panic hook ratatui installs should it panic.
+use std::io;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Cell, Row, Table, TableState};
+use ratatui::DefaultTerminal;
+
+use crate::color;
+
+/// A key that does something in the host picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Home,
+    End,
+    Enter,
+    Refresh,
+    Quit,
+}
+
+impl Key {
+    /// The key of a key press, if it does anything.
+    pub fn of(event: KeyEvent) -> Option<Key> {
+        if event.kind == KeyEventKind::Release {
+            return None;
+        }
+        let key = match event.code {
+            KeyCode::Up | KeyCode::Char('k') => Key::Up,
+            KeyCode::Down | KeyCode::Char('j') => Key::Down,
+            KeyCode::Home | KeyCode::Char('g') => Key::Home,
+            KeyCode::End | KeyCode::Char('G') => Key::End,
+            KeyCode::Enter => Key::Enter,
+            // raw mode keeps the terminal from sending the signal of Ctrl-C
+            KeyCode::Char('c' | 'd') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                Key::Quit
+            }
+            KeyCode::Char('r') => Key::Refresh,
+            KeyCode::Char('q') | KeyCode::Esc => Key::Quit,
+            _ => return None,
+        };
+        Some(key)
+    }
+}
+
+/// Reads the events of the terminal for as long as the process runs, sending the keys that do
+/// something and each resize, which only needs the screen drawn again.
+pub fn read_events<E: Send + 'static>(events: Sender<E>, key: fn(Key) -> E, resize: fn() -> E) {
+    thread::spawn(move || {
+        while let Ok(read) = event::read() {
+            let sent = match read {
+                Event::Key(event) => match Key::of(event) {
+                    Some(pressed) => events.send(key(pressed)),
+                    None => continue,
+                },
+                Event::Resize(..) => events.send(resize()),
+                _ => continue,
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Whether a host is up, as shown in its status column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Probing,
+    Up,
+    Down,
+    /// It could not be told, for the reason given.
+    Unknown(String),
+}
+
+impl Status {
+    fn cell(&self) -> Cell<'static> {
+        match self {
+            Status::Probing => Cell::new("probing").style(paint(Color::Yellow)),
+            Status::Up => Cell::new("up").style(paint(Color::Green)),
+            Status::Down => Cell::new("down").style(paint(Color::Red)),
+            Status::Unknown(reason) => {
+                Cell::new(format!("unknown: {}", reason)).style(paint(Color::Yellow))
+            }
+        }
+    }
+}
+
+/// A host of the picker: its name, MAC address and IP address, and whether it is up.
+pub struct Host {
+    pub cells: [String; 3],
+    pub status: Status,
+}
+
+/// The line under the table, of what happened to the last host woken.
+#[derive(Debug, Clone)]
+pub enum Message {
+    None,
+    Waking(String),
+    Woken(String),
+    Failed(String),
+}
+
+impl Message {
+    fn line(&self) -> Line<'static> {
+        match self {
+            Message::None => Line::default(),
+            Message::Waking(text) => Line::raw(text.clone()),
+            Message::Woken(text) => Line::styled(text.clone(), paint(Color::Green)),
+            Message::Failed(text) => Line::styled(text.clone(), paint(Color::Red)),
+        }
+    }
+}
+
+/// A style of the foreground color, or none when colors are off.
+fn paint(color: Color) -> Style {
+    if color::enabled() {
+        Style::new().fg(color)
+    } else {
+        Style::new()
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+pub struct Terminal {
+    terminal: DefaultTerminal,
+
+    /// The row selected, and the first of those shown so that it stays among them.
+    table: TableState,
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        Ok(Terminal {
+            terminal: ratatui::try_init()?,
+            table: TableState::default(),
+        })
+    }
+
+    /// Draws the title, the table of hosts with the selected one marked and the message under
+    /// it, to the size the terminal has now.
+    pub fn draw(&mut self, hosts: &[Host], selected: usize, message: &Message) -> io::Result<()> {
+        self.table.select(Some(selected));
+        let header = ["HOST", "MAC", "IP", "STATUS"];
+        // each column as wide as its widest cell, leaving the rest to the status
+        let mut widths = [0; 3];
+        for cells in hosts
+            .iter()
+            .map(|host| host.cells.each_ref().map(String::as_str))
+            .chain([[header[0], header[1], header[2]]])
+        {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count() as u16);
+            }
+        }
+        let rows = hosts.iter().map(|host| {
+            let mut cells: Vec<Cell> = host
+                .cells
+                .iter()
+                .map(|cell| Cell::new(cell.clone()))
+                .collect();
+            cells.push(host.status.cell());
+            Row::new(cells)
+        });
+        let bold = if color::enabled() {
+            Style::new().add_modifier(Modifier::BOLD)
+        } else {
+            Style::new()
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(widths[0]),
+                Constraint::Length(widths[1]),
+                Constraint::Length(widths[2]),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(header).style(bold))
+        .column_spacing(2)
+        .highlight_symbol("> ")
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let title = Line::from(vec![
+            Span::styled("wol-cli", bold),
+            Span::raw("  ↑↓ select  enter wake  r probe again  q quit"),
+        ]);
+
+        let state = &mut self.table;
+        self.terminal.draw(|frame| {
+            let [top, middle, bottom] = Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Fill(1),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+            frame.render_widget(title, top);
+            frame.render_stateful_widget(table, middle, state);
+            frame.render_widget(message.line(), bottom);
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        ratatui::restore();
+    }
+}
+
+#[test]
+fn test_key_of() {
+    let key = |code, modifiers| Key::of(KeyEvent::new(code, modifiers));
+    assert_eq!(key(KeyCode::Up, KeyModifiers::NONE), Some(Key::Up));
+    assert_eq!(key(KeyCode::Char('j'), KeyModifiers::NONE), Some(Key::Down));
+    assert_eq!(key(KeyCode::Char('G'), KeyModifiers::SHIFT), Some(Key::End));
+    assert_eq!(key(KeyCode::Enter, KeyModifiers::NONE), Some(Key::Enter));
+    assert_eq!(
+        key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        Some(Key::Quit)
+    );
+    assert_eq!(key(KeyCode::Char('c'), KeyModifiers::NONE), None);
+    assert_eq!(key(KeyCode::Esc, KeyModifiers::NONE), Some(Key::Quit));
+
+    let mut release = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+    release.kind = KeyEventKind::Release;
+    assert_eq!(Key::of(release), None);
+}
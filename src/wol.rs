@@ -1,88 +1,380 @@
 //! Parses an IEEE EUI-48 MAC address and continues to construct a
-//! WakeOnLAN packet (so called "Magic Packet Technology")
-use std::net::UdpSocket;
+//! WakeOnLAN packet (so called "Magic Packet Technology"). None of it needs std or an
+//! allocator; sending the packets is left to [`crate::send`].
+use core::fmt;
+use core::net::Ipv6Addr;
+use core::str::FromStr;
 
 use thiserror::Error;
 
-type Eui48 = u64;
+#[cfg(feature = "std")]
+pub use crate::error::WolError;
+#[cfg(feature = "std")]
+pub use crate::send::{AsyncUdpSocket, MagicPacketBuilder, SendOptions};
 
 const MAGIC_PACKET_LEN: usize = 102;
-pub struct MagicPacket([u8; MAGIC_PACKET_LEN]);
+const SECUREON_PASSWORD_LEN: usize = 6;
 
-#[derive(Error, Debug)]
+#[derive(Debug, Clone)]
+pub struct MagicPacket {
+    bytes: [u8; MAGIC_PACKET_LEN + SECUREON_PASSWORD_LEN],
+    len: usize,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
 pub enum ParseError {
     /// Invalid MAC address
     #[error("invalid length")]
     InvalidLength,
 
     /// Expected a hyphen
-    #[error("expected a hyphen at position '{0}")]
+    #[error("expected a hyphen at position {0}")]
     ExpectedHyphen(usize),
+
+    /// Expected a dot
+    #[error("expected a dot at position {0}")]
+    ExpectedDot(usize),
 }
 
-/// Parses an ASCII representation of an EUI-48 address into a unsigned 64-bit integer.
-fn parse_eui48(input: &str) -> Result<Eui48, ParseError> {
-    // must be exactly 17 characters long
-    if input.len() != 17 {
-        return Err(ParseError::InvalidLength);
+/// Why received bytes are not a magic packet.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PacketError {
+    /// Neither 102 bytes, nor 108 with a password
+    #[error("a magic packet is 102 bytes, or 108 with a password, not {0}")]
+    InvalidLength(usize),
+
+    /// The packet does not start with six bytes of 0xFF
+    #[error("a magic packet starts with six bytes of 0xFF")]
+    InvalidHeader,
+
+    /// The MAC address is not repeated sixteen times
+    #[error("a magic packet repeats the MAC address sixteen times")]
+    InvalidRepetition,
+}
+
+/// An IEEE EUI-48 MAC address, ordered by its octets and displayed as aa:bb:cc:dd:ee:ff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    pub const fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = ParseError;
+
+    /// Parses a MAC address in EUI-48 notation, accepting colons (:) in place of hyphens. Bare
+    /// (aabbccddeeff) and Cisco dotted (aabb.ccdd.eeff) addresses are accepted too.
+    fn from_str(s: &str) -> Result<MacAddr, ParseError> {
+        parse_eui48(s)
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, octet) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02x}", octet)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "macaddr")]
+impl From<macaddr::MacAddr6> for MacAddr {
+    fn from(mac: macaddr::MacAddr6) -> MacAddr {
+        MacAddr(mac.into_array())
+    }
+}
+
+#[cfg(feature = "macaddr")]
+impl From<MacAddr> for macaddr::MacAddr6 {
+    fn from(mac: MacAddr) -> macaddr::MacAddr6 {
+        mac.0.into()
+    }
+}
+
+/// Fails for EUI-64 addresses, which magic packets cannot hold.
+#[cfg(feature = "macaddr")]
+impl TryFrom<macaddr::MacAddr> for MacAddr {
+    type Error = ParseError;
+
+    fn try_from(mac: macaddr::MacAddr) -> Result<MacAddr, ParseError> {
+        match mac {
+            macaddr::MacAddr::V6(mac) => Ok(mac.into()),
+            macaddr::MacAddr::V8(_) => Err(ParseError::InvalidLength),
+        }
+    }
+}
+
+#[cfg(feature = "macaddr")]
+impl From<MacAddr> for macaddr::MacAddr {
+    fn from(mac: MacAddr) -> macaddr::MacAddr {
+        macaddr::MacAddr::V6(mac.into())
+    }
+}
+
+/// Written as aa:bb:cc:dd:ee:ff in human-readable formats, and as its six bytes in others.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&self.0);
+        }
+        // formatted on the stack, as there may be no allocator
+        let digits = b"0123456789abcdef";
+        let mut buf = [0u8; 17];
+        for (i, octet) in self.0.iter().enumerate() {
+            buf[i * 3] = digits[usize::from(octet >> 4)];
+            buf[i * 3 + 1] = digits[usize::from(octet & 0xF)];
+            if i < 5 {
+                buf[i * 3 + 2] = b':';
+            }
+        }
+        serializer.serialize_str(core::str::from_utf8(&buf).unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<MacAddr, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = MacAddr;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a MAC address")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<MacAddr, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, value: &[u8]) -> Result<MacAddr, E> {
+                MacAddr::try_from(value).map_err(|_| E::invalid_length(value.len(), &self))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<MacAddr, A::Error> {
+                let mut octets = [0u8; 6];
+                for (i, octet) in octets.iter_mut().enumerate() {
+                    *octet = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                }
+                if seq.next_element::<u8>()?.is_some() {
+                    return Err(serde::de::Error::invalid_length(7, &self));
+                }
+                Ok(MacAddr(octets))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for MacAddr {
+    type Error = ParseError;
+
+    fn try_from(octets: &[u8]) -> Result<MacAddr, ParseError> {
+        octets
+            .try_into()
+            .map(MacAddr)
+            .map_err(|_| ParseError::InvalidLength)
     }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> MacAddr {
+        MacAddr(octets)
+    }
+}
 
+impl From<MacAddr> for [u8; 6] {
+    fn from(mac: MacAddr) -> [u8; 6] {
+        mac.0
+    }
+}
+
+/// Parses the ASCII representation of an EUI-48 address, separated by hyphens or colons (:),
+/// which may be mixed. Besides it, the 12 digits
+/// may be given without separators (aabbccddeeff) or in groups of four separated by dots as
+/// printed by Cisco switches (aabb.ccdd.eeff).
+fn parse_eui48(input: &str) -> Result<MacAddr, ParseError> {
     // must contain exactly 12 hexadecimal digits
     if input.chars().filter(|x| x.is_ascii_hexdigit()).count() != 12 {
         return Err(ParseError::InvalidLength);
     }
 
-    // every 2nd character must be separated by a hyphen
+    // followed by a separator every 2nd or 4th character, if any
+    let (separator, group) = match input.len() {
+        12 => return parse_hex(input),
+        14 => ('.', 4),
+        17 => ('-', 2),
+        _ => return Err(ParseError::InvalidLength),
+    };
     let mut it = input.chars().enumerate();
-    while let Some((index, c)) = it.nth(2) {
-        if c != '-' {
-            return Err(ParseError::ExpectedHyphen(index));
+    while let Some((index, c)) = it.nth(group) {
+        if c != separator && (separator, c) != ('-', ':') {
+            return Err(match separator {
+                '.' => ParseError::ExpectedDot(index),
+                _ => ParseError::ExpectedHyphen(index),
+            });
         }
     }
 
-    let mut eui: Eui48 = 0;
-    for c in input.chars().filter(|x| x.is_ascii_hexdigit()) {
+    parse_hex(input)
+}
+
+/// Reads the 12 hexadecimal digits of `input` into six octets, skipping anything else.
+fn parse_hex(input: &str) -> Result<MacAddr, ParseError> {
+    let mut octets = [0u8; 6];
+    for (i, c) in input.chars().filter(|x| x.is_ascii_hexdigit()).enumerate() {
         let nibble = c.to_digit(16).ok_or(ParseError::InvalidLength)?;
-        eui = eui << 4 | (nibble as u64 & 0xF);
+        let octet = octets.get_mut(i / 2).ok_or(ParseError::InvalidLength)?;
+        *octet = *octet << 4 | nibble as u8;
     }
 
-    Ok(eui)
+    Ok(MacAddr(octets))
 }
 
 /// Creates a magic packet byte array for the given MAC address. The input address must follow the
 /// IEEE EUI-48 notation (hexadecimal character separated by hyphens), alternatively colons (:) can
-/// be used instead of hyphens.
+/// be used instead of hyphens, or one of the other notations accepted by [`MacAddr`]. For a MAC
+/// address already parsed, [`MagicPacket::new`] is a `const fn`.
 pub fn create_magic_packet(mac: &str) -> Result<MagicPacket, ParseError> {
-    let mut packet = [0xFFu8; 102];
-    let mac_with_hyphens = mac
-        .chars()
-        .map(|c| match c {
-            ':' => '-',
-            _ => c,
+    Ok(MagicPacket::new(mac.parse()?))
+}
+
+/// UDP port conventionally used for WakeOnLAN (the "discard" service).
+pub const DEFAULT_PORT: u16 = 9;
+
+/// UDP ports WakeOnLAN receivers commonly listen on.
+pub const COMMON_PORTS: [u16; 3] = [0, 7, 9];
+
+/// EtherType registered for WakeOnLAN, used when sending magic packets as raw ethernet frames.
+pub const ETHERTYPE_WOL: u16 = 0x0842;
+
+/// The IPv6 link-local all-nodes multicast address, the IPv6 counterpart of a broadcast.
+pub const IPV6_ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+impl MagicPacket {
+    /// The packet waking `mac`. As a `const fn`, it bakes packets for known hosts into static
+    /// data:
+    ///
+    /// ```
+    /// use wol_cli::{MacAddr, MagicPacket};
+    ///
+    /// static NAS: MagicPacket = MagicPacket::new(MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]));
+    /// assert_eq!(NAS.as_bytes().len(), 102);
+    /// ```
+    pub const fn new(mac: MacAddr) -> MagicPacket {
+        let mut bytes = [0xFFu8; MAGIC_PACKET_LEN + SECUREON_PASSWORD_LEN];
+        // the first 6 bytes stay as 0xFF, followed by 16 occurrences of the MAC
+        let mut i = 6;
+        while i < MAGIC_PACKET_LEN {
+            bytes[i] = mac.0[i % 6];
+            i += 1;
+        }
+
+        MagicPacket {
+            bytes,
+            len: MAGIC_PACKET_LEN,
+        }
+    }
+
+    /// Reads a magic packet as received, which has to be exactly the six bytes of 0xFF and
+    /// sixteen repetitions of the MAC address, optionally followed by a SecureOn password.
+    pub fn parse(data: &[u8]) -> Result<MagicPacket, PacketError> {
+        if data.len() != MAGIC_PACKET_LEN && data.len() != MAGIC_PACKET_LEN + SECUREON_PASSWORD_LEN
+        {
+            return Err(PacketError::InvalidLength(data.len()));
+        }
+        if data[..6] != [0xFF; 6] {
+            return Err(PacketError::InvalidHeader);
+        }
+        let mac = &data[6..12];
+        if !data[6..MAGIC_PACKET_LEN]
+            .chunks(6)
+            .all(|chunk| chunk == mac)
+        {
+            return Err(PacketError::InvalidRepetition);
+        }
+        let mut bytes = [0; MAGIC_PACKET_LEN + SECUREON_PASSWORD_LEN];
+        bytes[..data.len()].copy_from_slice(data);
+        Ok(MagicPacket {
+            bytes,
+            len: data.len(),
         })
-        .collect::<String>();
-    let mac = parse_eui48(&mac_with_hyphens)?;
-
-    // fill the packet with 16 occurrences of the MAC
-    // starting at the 7th byte so that the first 6
-    // bytes stay as 0xFF
-    for i in 1..17 {
-        let dst = i * 6;
-        for j in 0..6 {
-            packet[dst + j] = (mac >> 40 - (j * 8) & 0xFF) as u8;
+    }
+
+    /// The MAC address of the host the packet wakes.
+    pub const fn mac(&self) -> MacAddr {
+        let [_, _, _, _, _, _, a, b, c, d, e, f, ..] = self.bytes;
+        MacAddr([a, b, c, d, e, f])
+    }
+
+    /// The SecureOn password of the packet, if it has one.
+    pub fn password(&self) -> Option<[u8; 6]> {
+        let password = self.bytes[MAGIC_PACKET_LEN..].try_into().ok()?;
+        (self.len > MAGIC_PACKET_LEN).then_some(password)
+    }
+
+    /// Appends a SecureOn password to the packet, growing it to 108 bytes. The password is six
+    /// bytes written in the same notation as a MAC address, e.g. "aa-bb-cc-dd-ee-ff".
+    pub fn with_password(self, password: &str) -> Result<MagicPacket, ParseError> {
+        Ok(self.with_password_bytes(password.parse::<MacAddr>()?.octets()))
+    }
+
+    /// Appends a SecureOn password given as its six bytes.
+    pub const fn with_password_bytes(mut self, password: [u8; 6]) -> MagicPacket {
+        let mut i = 0;
+        while i < SECUREON_PASSWORD_LEN {
+            self.bytes[MAGIC_PACKET_LEN + i] = password[i];
+            i += 1;
         }
+        self.len = MAGIC_PACKET_LEN + SECUREON_PASSWORD_LEN;
+
+        self
     }
 
-    Ok(MagicPacket(packet))
+    /// The bytes of the packet as sent: 102, or 108 with a SecureOn password.
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
 }
 
-impl MagicPacket {
-    pub fn broadcast(&self) -> std::io::Result<()> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
-        socket.set_broadcast(true)?;
-        socket.send_to(&self.0, "255.255.255.255:9")?;
+impl PartialEq for MagicPacket {
+    fn eq(&self, other: &MagicPacket) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
 
-        Ok(())
+impl Eq for MagicPacket {}
+
+impl AsRef<[u8]> for MagicPacket {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+/// The 102 bytes of the packet, leaving out the SecureOn password if it has one.
+impl From<MagicPacket> for [u8; MAGIC_PACKET_LEN] {
+    fn from(packet: MagicPacket) -> [u8; MAGIC_PACKET_LEN] {
+        let mut bytes = [0; MAGIC_PACKET_LEN];
+        bytes.copy_from_slice(&packet.bytes[..MAGIC_PACKET_LEN]);
+        bytes
     }
 }
 
@@ -115,19 +407,186 @@ fn test_magic_separator_order() {
     assert!(create_magic_packet("-----abababababab").is_err());
 }
 
+#[test]
+fn test_magic_bare() {
+    assert_eq!(
+        "AABBccddeeff".parse(),
+        Ok(MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]))
+    );
+    assert!("aabbccddeefg".parse::<MacAddr>().is_err());
+}
+
+#[test]
+fn test_magic_cisco() {
+    assert_eq!(
+        "aabb.ccdd.eeff".parse(),
+        Ok(MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]))
+    );
+    assert!(matches!(
+        "aab.bccdd.eeff".parse::<MacAddr>(),
+        Err(ParseError::ExpectedDot(4))
+    ));
+    assert_eq!(
+        ParseError::ExpectedDot(4).to_string(),
+        "expected a dot at position 4"
+    );
+    assert!("aabb-ccdd-eeff".parse::<MacAddr>().is_err());
+}
+
+#[test]
+fn test_mac_addr() {
+    let mac: MacAddr = "AA-BB-CC-DD-EE-0F".parse().unwrap();
+    assert_eq!(mac, MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]));
+    assert_eq!(mac.to_string(), "aa:bb:cc:dd:ee:0f");
+    assert_eq!(mac.to_string().parse(), Ok(mac));
+    assert_eq!("aa:bb".parse::<MacAddr>(), Err(ParseError::InvalidLength));
+
+    let octets: &[u8] = &[0, 1, 2, 3, 4, 5, 6];
+    assert_eq!(
+        MacAddr::try_from(&octets[..6]),
+        Ok(MacAddr([0, 1, 2, 3, 4, 5]))
+    );
+    assert_eq!(MacAddr::try_from(octets), Err(ParseError::InvalidLength));
+    assert_eq!(<[u8; 6]>::from(mac), mac.octets());
+    assert!(MacAddr::from([0, 0, 0, 0, 0, 1]) < MacAddr::from([0, 0, 0, 0, 1, 0]));
+}
+
+#[cfg(feature = "macaddr")]
+#[test]
+fn test_mac_addr_macaddr() {
+    let mac = MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]);
+    let other = macaddr::MacAddr6::new(0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f);
+    assert_eq!(MacAddr::from(other), mac);
+    assert_eq!(macaddr::MacAddr6::from(mac), other);
+    assert_eq!(MacAddr::try_from(macaddr::MacAddr::from(mac)), Ok(mac));
+    let eui64 = macaddr::MacAddr8::new(0, 1, 2, 3, 4, 5, 6, 7);
+    assert_eq!(
+        MacAddr::try_from(macaddr::MacAddr::V8(eui64)),
+        Err(ParseError::InvalidLength)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_mac_addr_serde() {
+    use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Configure, Token};
+
+    let mac = MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]);
+    assert_tokens(&mac.readable(), &[Token::Str("aa:bb:cc:dd:ee:0f")]);
+    assert_tokens(
+        &mac.compact(),
+        &[Token::Bytes(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f])],
+    );
+    assert_de_tokens(&mac.readable(), &[Token::Str("AA-BB-CC-DD-EE-0F")]);
+    let seq = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f].map(Token::U8);
+    let tokens = [&[Token::Seq { len: Some(6) }][..], &seq, &[Token::SeqEnd]].concat();
+    assert_de_tokens(&mac.compact(), &tokens);
+
+    assert_de_tokens_error::<serde_test::Readable<MacAddr>>(
+        &[Token::Str("aa:bb")],
+        "invalid length",
+    );
+    assert_de_tokens_error::<serde_test::Compact<MacAddr>>(
+        &[Token::Bytes(&[1, 2, 3])],
+        "invalid length 3, expected a MAC address",
+    );
+}
+
 #[test]
 fn test_magic() {
     let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
 
     // starts with padding
     let cmp = [255, 255, 255, 255, 255, 255];
-    assert_eq!(&pkt.0[..6], &cmp);
+    assert_eq!(&pkt.as_bytes()[..6], &cmp);
 
     // follows with mac
     let cmp = [170, 170, 170, 170, 170, 170];
-    assert_eq!(&pkt.0[6..12], &cmp);
+    assert_eq!(&pkt.as_bytes()[6..12], &cmp);
 
     // ends with mac
     let cmp = [170, 170, 170, 170, 170, 170];
-    assert_eq!(&pkt.0[102 - 6..102], &cmp);
+    assert_eq!(&pkt.as_bytes()[102 - 6..102], &cmp);
+}
+
+#[test]
+fn test_magic_password() {
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa")
+        .unwrap()
+        .with_password("01-02-03-04-05-06")
+        .unwrap();
+
+    assert_eq!(pkt.as_bytes().len(), 108);
+
+    // mac is still repeated up to the 102nd byte
+    let cmp = [170, 170, 170, 170, 170, 170];
+    assert_eq!(&pkt.as_bytes()[102 - 6..102], &cmp);
+
+    // ends with password
+    let cmp = [1, 2, 3, 4, 5, 6];
+    assert_eq!(&pkt.as_bytes()[102..], &cmp);
+}
+
+#[test]
+fn test_magic_bytes() {
+    let pkt = create_magic_packet("01-02-03-04-05-06").unwrap();
+    assert_eq!(pkt.as_ref(), pkt.as_bytes());
+    let bytes: [u8; 102] = pkt.into();
+    assert_eq!(bytes[..6], [0xff; 6]);
+    assert_eq!(bytes[96..], [1, 2, 3, 4, 5, 6]);
+
+    let pkt = create_magic_packet("01-02-03-04-05-06")
+        .unwrap()
+        .with_password("aa-bb-cc-dd-ee-ff")
+        .unwrap();
+    assert_eq!(pkt.as_bytes().len(), 108);
+    assert_eq!(<[u8; 102]>::from(pkt), bytes);
+}
+
+#[test]
+fn test_magic_const() {
+    const PACKET: MagicPacket =
+        MagicPacket::new(MacAddr([1, 2, 3, 4, 5, 6])).with_password_bytes([7, 8, 9, 10, 11, 12]);
+    const MAC: MacAddr = PACKET.mac();
+    assert_eq!(MAC, MacAddr([1, 2, 3, 4, 5, 6]));
+    assert_eq!(
+        PACKET.as_bytes()[..102],
+        *create_magic_packet("01-02-03-04-05-06").unwrap().as_bytes()
+    );
+    assert_eq!(PACKET.password(), Some([7, 8, 9, 10, 11, 12]));
+}
+
+#[test]
+fn test_magic_parse() {
+    let pkt = create_magic_packet("01-02-03-04-05-06").unwrap();
+    let parsed = MagicPacket::parse(pkt.as_bytes()).unwrap();
+    assert_eq!(parsed, pkt);
+    assert_eq!(parsed.mac(), MacAddr([1, 2, 3, 4, 5, 6]));
+    assert_eq!(parsed.password(), None);
+
+    let pkt = pkt.with_password("aa-bb-cc-dd-ee-ff").unwrap();
+    let parsed = MagicPacket::parse(pkt.as_bytes()).unwrap();
+    assert_eq!(
+        parsed.password(),
+        Some([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff])
+    );
+
+    let mut bytes = pkt.as_bytes().to_vec();
+    assert_eq!(
+        MagicPacket::parse(&bytes[..104]),
+        Err(PacketError::InvalidLength(104))
+    );
+    bytes[101] = 7;
+    assert_eq!(
+        MagicPacket::parse(&bytes),
+        Err(PacketError::InvalidRepetition)
+    );
+    bytes[0] = 0;
+    assert_eq!(MagicPacket::parse(&bytes), Err(PacketError::InvalidHeader));
+}
+
+#[test]
+fn test_magic_password_invalid() {
+    let pkt = create_magic_packet("AA-aa-aa-aa-aa-aa").unwrap();
+    assert!(pkt.with_password("secret").is_err());
 }
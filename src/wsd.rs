@@ -0,0 +1,108 @@
+//! WS-Discovery probes, which Windows hosts answer when network discovery is turned on, even if
+//! they filter ping and do not speak mDNS.
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const WSD_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const WSD_PORT: u16 = 3702;
+
+/// A message ID unique enough to tell the matches of our probe from those of others.
+fn message_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    format!(
+        "urn:uuid:{:08x}-{:04x}-4{:03x}-8{:03x}-{:012x}",
+        nanos >> 32,
+        nanos >> 16 & 0xffff,
+        nanos >> 4 & 0xfff,
+        nanos & 0xfff,
+        process::id()
+    )
+}
+
+/// Builds a probe for devices, as sent by Windows when browsing the network.
+fn probe(message_id: &str) -> String {
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            r#"<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" "#,
+            r#"xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing" "#,
+            r#"xmlns:wsd="http://schemas.xmlsoap.org/ws/2005/04/discovery" "#,
+            r#"xmlns:wsdp="http://schemas.xmlsoap.org/ws/2006/02/devprof">"#,
+            "<soap:Header>",
+            "<wsa:To>urn:schemas-xmlsoap-org:ws:2005:04:discovery</wsa:To>",
+            "<wsa:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</wsa:Action>",
+            "<wsa:MessageID>{}</wsa:MessageID>",
+            "</soap:Header>",
+            "<soap:Body><wsd:Probe><wsd:Types>wsdp:Device</wsd:Types></wsd:Probe></soap:Body>",
+            "</soap:Envelope>"
+        ),
+        message_id
+    )
+}
+
+/// Whether a received message matches the probe with the given ID. The XML is not parsed, as
+/// the namespace prefixes vary between implementations.
+fn is_match(message: &str, message_id: &str) -> bool {
+    message.contains("ProbeMatches") && message.contains(message_id)
+}
+
+/// Probes for devices and returns the addresses of those that answered within `timeout`.
+pub fn discover(timeout: Duration) -> io::Result<Vec<IpAddr>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    let id = message_id();
+    socket.send_to(probe(&id).as_bytes(), (WSD_ADDR, WSD_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found = Vec::new();
+    let mut buf = [0; 65536];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, addr)) => {
+                let message = String::from_utf8_lossy(&buf[..len]);
+                if is_match(&message, &id) && !found.contains(&addr.ip()) {
+                    found.push(addr.ip());
+                }
+            }
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(found)
+}
+
+#[test]
+fn test_probe() {
+    let id = message_id();
+    assert_eq!(id.len(), "urn:uuid:".len() + 36);
+    let probe = probe(&id);
+    assert!(probe.contains(&format!("<wsa:MessageID>{}</wsa:MessageID>", id)));
+
+    let reply = format!(
+        "<s:Envelope><s:Header><a:RelatesTo>{}</a:RelatesTo></s:Header>\
+         <s:Body><d:ProbeMatches><d:ProbeMatch><d:Types>wsdp:Device pub:Computer</d:Types>\
+         </d:ProbeMatch></d:ProbeMatches></s:Body></s:Envelope>",
+        id
+    );
+    assert!(is_match(&reply, &id));
+    assert!(!is_match(
+        &reply,
+        "urn:uuid:00000000-0000-4000-8000-000000000000"
+    ));
+    assert!(!is_match(&probe, &id));
+}
@@ -0,0 +1,227 @@
+//! Runs the wol-cli command itself, for what only shows in its output.
+use std::env;
+use std::fs;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A directory of its own for a test, holding the registry and the wake history.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("wol-cli-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs wol-cli with a registry and the other state files in `dir`.
+fn wol_cli(dir: &PathBuf, args: &[&str]) -> std::process::Output {
+    wol_cli_with(dir, args, &[])
+}
+
+/// Runs wol-cli as [`wol_cli`] does, with more environment variables.
+fn wol_cli_with(dir: &PathBuf, args: &[&str], vars: &[(&str, &str)]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_wol-cli"))
+        .args(args)
+        .envs(vars.iter().copied())
+        .env("WOL_CONFIG", dir.join("hosts.toml"))
+        .env("HOME", dir)
+        .env("XDG_STATE_HOME", dir)
+        .env("XDG_CACHE_HOME", dir)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_all_records() {
+    let dir = scratch("all");
+    fs::write(
+        dir.join("hosts.toml"),
+        "[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\nbroadcast = \"127.0.0.1\"\n\n\
+         [hosts.pc]\nmac = \"aa:bb:cc:dd:ee:01\"\nbroadcast = \"127.0.0.1\"\n",
+    )
+    .unwrap();
+
+    let output = wol_cli(&dir, &["all", "--output", "json"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    for line in lines {
+        assert!(
+            line.starts_with("{\"host\":") && line.ends_with('}'),
+            "{}",
+            line
+        );
+    }
+
+    let output = wol_cli(&dir, &["all", "-q"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+    let output = wol_cli(&dir, &["all"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("HOST"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unsaved_not_reported() {
+    let dir = scratch("unsaved");
+    fs::write(dir.join("hosts.toml"), "").unwrap();
+    // the registry is written through hosts.toml.tmp, which a directory keeps from being written
+    fs::create_dir_all(dir.join("hosts.toml.tmp").join("in-the-way")).unwrap();
+
+    let output = wol_cli(&dir, &["add", "nas", "aa:bb:cc:dd:ee:ff"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unable to write"));
+
+    let leases = dir.join("dnsmasq.leases");
+    fs::write(&leases, "1700000000 aa:bb:cc:dd:ee:ff 192.168.1.20 nas *\n").unwrap();
+    let output = wol_cli(&dir, &["import", "leases", leases.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unable to write"));
+
+    let switch = fake_switch();
+    let output = wol_cli(&dir, &["import", "snmp", &switch]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unable to write"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The TLVs of BER, all of which are short here.
+fn tlvs(mut input: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut tlvs = Vec::new();
+    while let [tag, len, rest @ ..] = input {
+        let (value, rest) = rest.split_at(*len as usize);
+        tlvs.push((*tag, value));
+        input = rest;
+    }
+    tlvs
+}
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    [&[tag, value.len() as u8][..], value].concat()
+}
+
+/// Answers SNMP like a switch that has learned aa:bb:cc:dd:ee:ff on port 3, returning its
+/// address.
+fn fake_switch() -> String {
+    let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = agent.local_addr().unwrap().to_string();
+    std::thread::spawn(move || {
+        // 1.3.6.1.2.1.17.4.3.1.2, the column of ports of the forwarding table
+        let column = [0x2b, 6, 1, 2, 1, 17, 4, 3, 1, 2];
+        let mut buf = [0; 1500];
+        loop {
+            let (len, peer) = agent.recv_from(&mut buf).unwrap();
+            let message = tlvs(&buf[..len]);
+            let fields = tlvs(message[0].1);
+            let pdu = tlvs(fields[2].1);
+            let varbind = tlvs(tlvs(pdu[3].1)[0].1);
+            let varbind = if varbind[0].1 == column {
+                let mac = [
+                    0x81, 0x2a, 0x81, 0x3b, 0x81, 0x4c, 0x81, 0x5d, 0x81, 0x6e, 0x81, 0x7f,
+                ];
+                [tlv(0x06, &[&column[..], &mac].concat()), tlv(0x02, &[3])].concat()
+            } else {
+                [tlv(0x06, &[0x2b, 6, 1, 2, 1, 17, 5]), tlv(0x82, &[])].concat()
+            };
+            let pdu = [
+                tlv(0x02, pdu[0].1),
+                tlv(0x02, &[0]),
+                tlv(0x02, &[0]),
+                tlv(0x30, &tlv(0x30, &varbind)),
+            ]
+            .concat();
+            let response = [tlv(0x02, &[1]), tlv(0x04, b"public"), tlv(0xa2, &pdu)].concat();
+            agent.send_to(&tlv(0x30, &response), peer).unwrap();
+        }
+    });
+    addr
+}
+
+#[test]
+fn test_edit_checked() {
+    let dir = scratch("edit");
+    let registry = "[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\n";
+    fs::write(dir.join("hosts.toml"), registry).unwrap();
+    // parses, but the host could never be woken by name and shares the MAC address of nas
+    let editor = dir.join("editor.sh");
+    fs::write(
+        &editor,
+        "printf '[hosts.\"192.168.1.2\"]\\nmac = \"aa:bb:cc:dd:ee:ff\"\\n' >> \"$1\"\n",
+    )
+    .unwrap();
+    let editor = format!("sh {}", editor.display());
+
+    let output = wol_cli_with(&dir, &["edit"], &[("VISUAL", &editor)]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("192.168.1.2"), "{}", stderr);
+    assert!(stderr.contains("discarding changes"), "{}", stderr);
+    assert_eq!(
+        fs::read_to_string(dir.join("hosts.toml")).unwrap(),
+        registry
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_picker_quiet() {
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::thread;
+    use std::time::Duration;
+
+    // the picker needs a terminal, which script of util-linux gives it
+    if Command::new("script").arg("--version").output().is_err() {
+        return;
+    }
+    let dir = scratch("picker");
+    fs::write(
+        dir.join("hosts.toml"),
+        "[hosts.nas]\nmac = \"aa:bb:cc:dd:ee:ff\"\n",
+    )
+    .unwrap();
+    let stderr = dir.join("stderr");
+    // waking nas fails, for a SecureOn password of the wrong length
+    let command = format!(
+        "stty cols 80 rows 8; {} tui --password x 2>{}",
+        env!("CARGO_BIN_EXE_wol-cli"),
+        stderr.display()
+    );
+    let mut script = Command::new("script")
+        .args(["-qec", &command, "/dev/null"])
+        .env("WOL_CONFIG", dir.join("hosts.toml"))
+        .env("HOME", &dir)
+        .env("XDG_STATE_HOME", &dir)
+        .env("XDG_CACHE_HOME", &dir)
+        .env("NO_COLOR", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut keys = script.stdin.take().unwrap();
+    for key in ["\r", "q"] {
+        thread::sleep(Duration::from_secs(1));
+        keys.write_all(key.as_bytes()).unwrap();
+        keys.flush().unwrap();
+    }
+    drop(keys);
+    let output = script.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let screen = String::from_utf8_lossy(&output.stdout);
+    assert!(screen.contains("SecureOn"), "{}", screen);
+    assert_eq!(fs::read_to_string(&stderr).unwrap(), "");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,53 @@
+//! JavaScript bindings of wol-cli for WebAssembly, building and reading magic packets in
+//! browsers and Node, which send them through a transport of their own. Built with wasm-pack:
+//!
+//! ```sh
+//! wasm-pack build wasm --target web
+//! ```
+use wasm_bindgen::prelude::wasm_bindgen;
+use wol_cli::{MacAddr, MagicPacket};
+
+/// Builds the magic packet waking `mac`, with a SecureOn `password` if given: 102 bytes, or 108
+/// with the password. Throws the parse error as a string if either is invalid.
+#[wasm_bindgen(js_name = magicPacket)]
+pub fn magic_packet(mac: &str, password: Option<String>) -> Result<Vec<u8>, String> {
+    let mac: MacAddr = mac.parse().map_err(|err| format!("invalid MAC: {}", err))?;
+    let packet = MagicPacket::new(mac);
+    let packet = match password {
+        Some(password) => packet
+            .with_password(&password)
+            .map_err(|err| format!("invalid password: {}", err))?,
+        None => packet,
+    };
+    Ok(packet.as_bytes().to_vec())
+}
+
+/// Reads a magic packet, returning the MAC address it wakes, e.g. "aa:bb:cc:dd:ee:ff".
+#[wasm_bindgen(js_name = parseMagicPacket)]
+pub fn parse_magic_packet(data: &[u8]) -> Result<String, String> {
+    MagicPacket::parse(data)
+        .map(|packet| packet.mac().to_string())
+        .map_err(|err| err.to_string())
+}
+
+#[test]
+fn test_magic_packet() {
+    let packet = magic_packet("aa-bb-cc-dd-ee-ff", None).unwrap();
+    assert_eq!(packet.len(), 102);
+    assert_eq!(packet[..6], [0xff; 6]);
+    assert_eq!(
+        parse_magic_packet(&packet).as_deref(),
+        Ok("aa:bb:cc:dd:ee:ff")
+    );
+
+    let packet = magic_packet("aa:bb:cc:dd:ee:ff", Some("01-02-03-04-05-06".into())).unwrap();
+    assert_eq!(packet[102..], [1, 2, 3, 4, 5, 6]);
+
+    assert!(magic_packet("aa:bb", None)
+        .unwrap_err()
+        .starts_with("invalid MAC"));
+    assert!(magic_packet("aa:bb:cc:dd:ee:ff", Some("secret".into()))
+        .unwrap_err()
+        .starts_with("invalid password"));
+    assert!(parse_magic_packet(&packet[..50]).is_err());
+}